@@ -0,0 +1,517 @@
+//! Templates for the manifests created by the bootstrap command, with the build
+//! options of the most common build systems, and the default sources of the
+//! bootstrapped modules.
+use std::fs;
+use std::path;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::{FlatpakBuildOptions, FlatpakBuildOptionsEnv, FlatpakModule};
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+pub const BUILD_SYSTEM_AUTOTOOLS: &str = "autotools";
+pub const BUILD_SYSTEM_CMAKE: &str = "cmake";
+pub const BUILD_SYSTEM_CMAKE_NINJA: &str = "cmake-ninja";
+pub const BUILD_SYSTEM_MESON: &str = "meson";
+pub const BUILD_SYSTEM_QMAKE: &str = "qmake";
+pub const BUILD_SYSTEM_SIMPLE: &str = "simple";
+/// Cargo, Python, Node and Go are not flatpak-builder build systems, so these
+/// projects are built with the simple build system.
+pub const BUILD_SYSTEM_CARGO: &str = "cargo";
+pub const BUILD_SYSTEM_PYTHON: &str = "python";
+pub const BUILD_SYSTEM_NODE: &str = "node";
+pub const BUILD_SYSTEM_GO: &str = "go";
+pub const BUILD_SYSTEMS: [&str; 10] = [
+    BUILD_SYSTEM_AUTOTOOLS,
+    BUILD_SYSTEM_CMAKE,
+    BUILD_SYSTEM_CMAKE_NINJA,
+    BUILD_SYSTEM_MESON,
+    BUILD_SYSTEM_QMAKE,
+    BUILD_SYSTEM_SIMPLE,
+    BUILD_SYSTEM_CARGO,
+    BUILD_SYSTEM_PYTHON,
+    BUILD_SYSTEM_NODE,
+    BUILD_SYSTEM_GO,
+];
+
+/// The SDK extensions providing the toolchains of the build systems, with the
+/// path of their binaries.
+pub const SDK_EXTENSIONS: [(&str, &str, &str); 3] = [
+    (
+        BUILD_SYSTEM_CARGO,
+        "org.freedesktop.Sdk.Extension.rust-stable",
+        "/usr/lib/sdk/rust-stable/bin",
+    ),
+    (
+        BUILD_SYSTEM_NODE,
+        "org.freedesktop.Sdk.Extension.node20",
+        "/usr/lib/sdk/node20/bin",
+    ),
+    (
+        BUILD_SYSTEM_GO,
+        "org.freedesktop.Sdk.Extension.golang",
+        "/usr/lib/sdk/golang/bin",
+    ),
+];
+
+/// The settings of a bootstrapped application manifest.
+#[derive(Debug)]
+pub struct ApplicationSettings {
+    pub app_id: String,
+    pub runtime: String,
+    pub sdk: String,
+    pub runtime_version: String,
+    pub finish_args: Vec<String>,
+}
+impl ApplicationSettings {
+    pub fn new(app_id: &str, runtime: &Runtime) -> ApplicationSettings {
+        ApplicationSettings {
+            app_id: app_id.to_string(),
+            runtime: runtime.runtime.to_string(),
+            sdk: runtime.sdk.to_string(),
+            runtime_version: runtime.version.to_string(),
+            finish_args: PERMISSIONS
+                .iter()
+                .filter(|p| p.2)
+                .map(|p| p.0.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The settings of the first module of a bootstrapped manifest.
+#[derive(Debug, Default)]
+pub struct ModuleSettings {
+    /// The name of the module. Defaults to the name of the project at the URL, or
+    /// of the current directory.
+    pub name: Option<String>,
+    pub build_system: Option<String>,
+    /// The URL of the sources. The sources are in a local directory when there
+    /// is no URL.
+    pub url: Option<String>,
+    /// The ref to build, for a git URL.
+    pub git_ref: crate::git::GitRef,
+}
+
+/// A runtime, with its SDK.
+#[derive(Debug, PartialEq)]
+pub struct Runtime {
+    pub name: &'static str,
+    pub runtime: &'static str,
+    pub sdk: &'static str,
+    pub version: &'static str,
+}
+
+pub const RUNTIMES: [&Runtime; 3] = [&GNOME_RUNTIME, &KDE_RUNTIME, &FREEDESKTOP_RUNTIME];
+
+pub const GNOME_RUNTIME: Runtime = Runtime {
+    name: "GNOME",
+    runtime: "org.gnome.Platform",
+    sdk: "org.gnome.Sdk",
+    version: "49",
+};
+pub const KDE_RUNTIME: Runtime = Runtime {
+    name: "KDE",
+    runtime: "org.kde.Platform",
+    sdk: "org.kde.Sdk",
+    version: "6.9",
+};
+pub const FREEDESKTOP_RUNTIME: Runtime = Runtime {
+    name: "Freedesktop",
+    runtime: "org.freedesktop.Platform",
+    sdk: "org.freedesktop.Sdk",
+    version: "25.08",
+};
+
+/// The permissions offered when bootstrapping an application, with their
+/// description and whether they are granted by default. Only the permissions
+/// needed by most graphical applications are granted by default.
+pub const PERMISSIONS: [(&str, &str, bool); 11] = [
+    (
+        "--share=ipc",
+        "Share IPC with the host, needed by X11",
+        true,
+    ),
+    ("--socket=wayland", "Show windows with Wayland", true),
+    (
+        "--socket=fallback-x11",
+        "Show windows with X11 when Wayland is not available",
+        true,
+    ),
+    ("--device=dri", "Use OpenGL acceleration", true),
+    ("--share=network", "Access the network", false),
+    ("--socket=pulseaudio", "Play and record sound", false),
+    (
+        "--filesystem=xdg-documents",
+        "Access the documents directory",
+        false,
+    ),
+    (
+        "--filesystem=home",
+        "Access all the files of the user",
+        false,
+    ),
+    (
+        "--device=all",
+        "Access all the devices, like webcams",
+        false,
+    ),
+    (
+        "--talk-name=org.freedesktop.Notifications",
+        "Send notifications",
+        false,
+    ),
+    (
+        "--talk-name=org.freedesktop.secrets",
+        "Store passwords in the keyring",
+        false,
+    ),
+];
+
+pub const DEFAULT_APP_ID: &str = "org.example.appName";
+pub const DEFAULT_MODULE_NAME: &str = "module-name";
+
+/// Sets the build system of a module, with the build options usually used with it.
+pub fn apply_build_system(module: &mut FlatpakModule, build_system: &str) -> Result<(), String> {
+    match build_system {
+        BUILD_SYSTEM_AUTOTOOLS => {
+            module.buildsystem = Some(FlatpakBuildSystem::Autotools);
+            module.config_opts = vec!["--disable-static".to_string()];
+        }
+        BUILD_SYSTEM_CMAKE | BUILD_SYSTEM_CMAKE_NINJA => {
+            module.buildsystem = Some(if build_system == BUILD_SYSTEM_CMAKE {
+                FlatpakBuildSystem::CMake
+            } else {
+                FlatpakBuildSystem::CMakeNinja
+            });
+            module.builddir = Some(true);
+            module.config_opts = vec!["-DCMAKE_BUILD_TYPE=RelWithDebInfo".to_string()];
+        }
+        BUILD_SYSTEM_MESON => {
+            module.buildsystem = Some(FlatpakBuildSystem::Meson);
+            module.config_opts = vec!["--buildtype=release".to_string()];
+        }
+        BUILD_SYSTEM_QMAKE => {
+            module.buildsystem = Some(FlatpakBuildSystem::QMake);
+            module.builddir = Some(true);
+            module.config_opts = vec!["PREFIX=/app".to_string()];
+        }
+        BUILD_SYSTEM_SIMPLE => {
+            module.buildsystem = Some(FlatpakBuildSystem::Simple);
+            module.build_commands = vec![
+                "make".to_string(),
+                "make install PREFIX=${FLATPAK_DEST}".to_string(),
+            ];
+        }
+        BUILD_SYSTEM_CARGO => {
+            module.buildsystem = Some(FlatpakBuildSystem::Simple);
+            module.build_options = Some(get_sdk_build_options(
+                build_system,
+                vec![format!("CARGO_HOME=/run/build/{}/cargo", module.name)],
+            ));
+            module.build_commands = vec![
+                "cargo --offline fetch --manifest-path Cargo.toml --verbose".to_string(),
+                "cargo --offline build --release --verbose".to_string(),
+                format!(
+                    "install -Dm755 ./target/release/{} -t ${{FLATPAK_DEST}}/bin/",
+                    get_program_name(module)
+                ),
+            ];
+        }
+        BUILD_SYSTEM_PYTHON => {
+            module.buildsystem = Some(FlatpakBuildSystem::Simple);
+            module.build_commands = vec![
+                "pip3 install --verbose --no-index --find-links=\"file://${PWD}\" --no-build-isolation --prefix=${FLATPAK_DEST} ."
+                    .to_string(),
+            ];
+        }
+        BUILD_SYSTEM_NODE => {
+            module.buildsystem = Some(FlatpakBuildSystem::Simple);
+            module.build_options = Some(get_sdk_build_options(
+                build_system,
+                vec![format!(
+                    "XDG_CACHE_HOME=/run/build/{}/flatpak-node/cache",
+                    module.name
+                )],
+            ));
+            module.build_commands = vec![
+                // The tarballs of the sources generated from the lockfile.
+                format!(
+                    "if [ -d {0} ]; then npm cache add --cache=flatpak-node/npm-cache {0}/*.tgz; fi",
+                    crate::lockfile::NODE_TARBALLS_DIR
+                ),
+                "npm ci --offline --cache=flatpak-node/npm-cache".to_string(),
+                "npm run build --if-present".to_string(),
+                format!(
+                    "mkdir -p ${{FLATPAK_DEST}}/lib/{0} && cp -a . ${{FLATPAK_DEST}}/lib/{0}",
+                    get_program_name(module)
+                ),
+            ];
+        }
+        BUILD_SYSTEM_GO => {
+            module.buildsystem = Some(FlatpakBuildSystem::Simple);
+            module.build_options = Some(get_sdk_build_options(
+                build_system,
+                vec!["GOFLAGS=-mod=vendor".to_string()],
+            ));
+            module.build_commands = vec![
+                format!("go build -trimpath -o {} .", get_program_name(module)),
+                format!(
+                    "install -Dm755 {} -t ${{FLATPAK_DEST}}/bin/",
+                    get_program_name(module)
+                ),
+            ];
+        }
+        _ => {
+            return Err(format!(
+                "Invalid build system {}. The build systems are {}.",
+                build_system,
+                BUILD_SYSTEMS.join(", ")
+            ))
+        }
+    };
+    Ok(())
+}
+
+/// Gets the build options adding the toolchain of the SDK extension of a build
+/// system to the path.
+fn get_sdk_build_options(build_system: &str, env: Vec<String>) -> FlatpakBuildOptions {
+    let mut build_options = FlatpakBuildOptions {
+        env: FlatpakBuildOptionsEnv::Array(env),
+        ..Default::default()
+    };
+    if let Some((_, _, path)) = SDK_EXTENSIONS.iter().find(|e| e.0 == build_system) {
+        build_options.append_path = path.to_string();
+    }
+    build_options
+}
+
+/// Gets the application manifest to bootstrap, building the given module.
+pub fn get_application(
+    settings: &ApplicationSettings,
+    module: FlatpakModule,
+    build_system: Option<&str>,
+) -> FlatpakApplication {
+    let mut flatpak_application = FlatpakApplication {
+        id: settings.app_id.to_string(),
+        runtime: settings.runtime.to_string(),
+        runtime_version: settings.runtime_version.to_string(),
+        sdk: settings.sdk.to_string(),
+        command: Some(get_program_name(&module)),
+        finish_args: settings.finish_args.clone(),
+        ..Default::default()
+    };
+    if let Some((_, sdk_extension, _)) = SDK_EXTENSIONS.iter().find(|e| Some(e.0) == build_system) {
+        flatpak_application
+            .sdk_extensions
+            .push(sdk_extension.to_string());
+    }
+    flatpak_application
+        .modules
+        .push(flatpak_rs::module::FlatpakModuleItem::Description(module));
+    flatpak_application
+}
+
+/// Gets the name of the program built by a bootstrapped module. The names of the
+/// modules bootstrapped from an archive URL end with `.archive`.
+fn get_program_name(module: &FlatpakModule) -> String {
+    match module.name.strip_suffix(".archive") {
+        Some(program_name) => program_name.to_string(),
+        None => module.name.to_string(),
+    }
+}
+
+/// Gets the name of the project in the current directory, to use when there is no
+/// URL to bootstrap from.
+pub fn get_current_project_name() -> String {
+    let current_dir = match std::env::current_dir() {
+        Ok(d) => d,
+        Err(_) => return DEFAULT_MODULE_NAME.to_string(),
+    };
+    match current_dir.file_name() {
+        Some(n) => n.to_string_lossy().to_string(),
+        None => DEFAULT_MODULE_NAME.to_string(),
+    }
+}
+
+/// Gets the source of a bootstrapped module: a git source for the git URLs, an
+/// archive source for the other URLs, and the current directory without a URL.
+pub fn get_default_source(
+    url: Option<String>,
+    git_ref: &crate::git::GitRef,
+) -> Result<FlatpakSource, String> {
+    let mut default_source = FlatpakSource::default();
+
+    if let Some(url) = url {
+        if crate::git::parse_git_url(&url).is_some() {
+            default_source.r#type = Some(FlatpakSourceType::Git);
+            default_source.branch = git_ref.branch.clone();
+            default_source.tag = git_ref.tag.clone();
+            default_source.commit = git_ref.commit.clone();
+        } else {
+            if !git_ref.is_empty() {
+                return Err(format!(
+                    "{} is not a git URL. A branch, a tag or a commit can only be used with a git URL.",
+                    url
+                ));
+            }
+            default_source.r#type = Some(FlatpakSourceType::Archive);
+        }
+        default_source.url = Some(url.clone());
+    } else {
+        if !git_ref.is_empty() {
+            return Err("A branch, a tag or a commit can only be used with a git URL.".to_string());
+        }
+        default_source.r#type = Some(FlatpakSourceType::Dir);
+        default_source.path = Some("./".to_string());
+    }
+    Ok(default_source)
+}
+
+pub fn get_default_module(
+    url: Option<String>,
+    git_ref: &crate::git::GitRef,
+) -> Result<FlatpakModule, String> {
+    let mut default_module = FlatpakModule::default();
+    let default_source = get_default_source(url, git_ref)?;
+    default_module
+        .sources
+        .push(FlatpakSourceItem::Description(default_source.clone()));
+
+    if default_source.url.is_none() {
+        default_module.name = get_current_project_name();
+        return Ok(default_module);
+    }
+
+    if default_source.get_type() == Some(FlatpakSourceType::Git) {
+        if let Some(project_name) =
+            get_project_name_from_git_url(default_source.url.as_ref().unwrap().to_string())
+        {
+            default_module.name = project_name;
+        } else {
+            default_module.name = DEFAULT_MODULE_NAME.to_string();
+        }
+    } else if default_source.get_type() == Some(FlatpakSourceType::Archive) {
+        if let Some(project_name) =
+            flatpak_rs::archive::get_project_name_from_url(default_source.url.as_ref().unwrap())
+        {
+            default_module.name = format!("{}.archive", project_name);
+        } else {
+            default_module.name = format!("{}.archive", DEFAULT_MODULE_NAME);
+        }
+    }
+    Ok(default_module)
+}
+
+/// Gets a dir source for a local directory, relative to the directory of the
+/// manifest at `manifest_path`.
+pub fn get_local_source(manifest_path: &str, dir: &str) -> Result<FlatpakSource, String> {
+    let manifest_dir = match manifest_path {
+        crate::STDOUT_PATH => path::Path::new("."),
+        p => crate::get_manifest_dir(p),
+    };
+    let source_dir = crate::resolve::get_relative_dir(manifest_dir, path::Path::new(dir))?;
+    let source_dir = source_dir.to_string_lossy().to_string();
+    Ok(FlatpakSource {
+        r#type: Some(FlatpakSourceType::Dir),
+        path: Some(if source_dir.is_empty() {
+            "./".to_string()
+        } else {
+            source_dir
+        }),
+        ..Default::default()
+    })
+}
+
+/// Gets the sources of a bootstrapped source manifest, with the default name of the
+/// manifest. When bootstrapping from a lockfile or from a directory with lockfiles,
+/// the sources of the dependencies are generated from the lockfiles.
+pub fn get_bootstrapped_sources(
+    url: Option<String>,
+    git_ref: &crate::git::GitRef,
+    from: Option<&str>,
+) -> Result<(Vec<FlatpakSource>, String), String> {
+    let from = match (from, &url) {
+        (Some(f), None) => path::Path::new(f),
+        (_, Some(u)) => {
+            let git_ref = crate::git::get_git_ref(git_ref, u, from.map(path::Path::new));
+            let project_name = get_default_module(url.clone(), &git_ref)?.name;
+            return Ok((
+                vec![get_default_source(url, &git_ref)?],
+                format!("{}-source.json", project_name),
+            ));
+        }
+        (None, None) => {
+            let project_name = get_default_module(None, git_ref)?.name;
+            return Ok((
+                vec![get_default_source(None, git_ref)?],
+                format!("{}-source.json", project_name),
+            ));
+        }
+    };
+    if !git_ref.is_empty() {
+        return Err("A branch, a tag or a commit can only be used with a git URL.".to_string());
+    }
+    if !from.exists() {
+        return Err(format!("{} does not exist.", from.display()));
+    }
+
+    let lockfile_paths = if from.is_dir() {
+        crate::lockfile::find_lockfiles(from)
+    } else {
+        vec![from.to_path_buf()]
+    };
+    if lockfile_paths.is_empty() {
+        let project_name = crate::detect::detect_project(from)?.name;
+        return Ok((
+            vec![get_default_source(None, git_ref)?],
+            format!("{}-source.json", project_name),
+        ));
+    }
+
+    let mut sources: Vec<FlatpakSource> = vec![];
+    for lockfile_path in &lockfile_paths {
+        let mut generated_sources = crate::lockfile::generate_sources(lockfile_path)?;
+        for warning in &generated_sources.warnings {
+            eprintln!("{}", warning);
+        }
+        eprintln!(
+            "Generated {} sources from {}.",
+            generated_sources.sources.len(),
+            lockfile_path.display()
+        );
+        sources.append(&mut generated_sources.sources);
+    }
+    let file_name = match lockfile_paths.as_slice() {
+        [lockfile_path] => crate::lockfile::get_sources_file_name(
+            &lockfile_path.file_name().unwrap().to_string_lossy(),
+        ),
+        _ => crate::lockfile::get_sources_file_name(""),
+    };
+    Ok((sources, file_name.to_string()))
+}
+
+/// Writes a new manifest, or prints it when the path is -. Existing files are not
+/// replaced.
+pub fn write_new_manifest(output_path: &str, mut content: String) -> Result<(), String> {
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    if output_path == crate::STDOUT_PATH {
+        print!("{}", content);
+        return Ok(());
+    }
+    if path::Path::new(output_path).exists() {
+        return Err(format!("{} already exists.", output_path));
+    }
+    if let Err(e) = fs::write(path::Path::new(output_path), content) {
+        return Err(format!("Could not write file {}: {}.", output_path, e));
+    }
+    eprintln!("Created {}.", output_path);
+    Ok(())
+}
+
+/// Gets the name of the project from a git URL.
+pub fn get_project_name_from_git_url(url: String) -> Option<String> {
+    crate::git::parse_git_url(&url)?.get_project_name()
+}
@@ -0,0 +1,266 @@
+//! The download cache, shared by all the manifests. The cached files are
+//! addressed by their checksum, so a file listed in multiple manifests is only
+//! downloaded once.
+use std::fs;
+use std::path;
+
+use flatpak_rs::source::FlatpakSource;
+
+pub const CACHE_DIR_NAME: &str = "fpcli";
+pub const DOWNLOADS_DIR_NAME: &str = "downloads";
+/// The extension of the files being downloaded into the cache.
+pub const PARTIAL_EXTENSION: &str = "partial";
+/// The prefix of the cache keys for files that only have a sha512 checksum.
+pub const SHA512_KEY_PREFIX: &str = "sha512-";
+/// The number of hex digits of the checksums.
+pub const SHA256_LENGTH: usize = 64;
+pub const SHA512_LENGTH: usize = 128;
+
+/// Gets the directory of the download cache, following the XDG base directory
+/// specification.
+pub fn get_downloads_cache_dir() -> Result<path::PathBuf, String> {
+    let cache_home = match std::env::var("XDG_CACHE_HOME") {
+        Ok(d) if !d.is_empty() => path::PathBuf::from(d),
+        _ => match std::env::var("HOME") {
+            Ok(h) => path::Path::new(&h).join(".cache"),
+            Err(_) => {
+                return Err("Could not find the cache directory: HOME is not set.".to_string())
+            }
+        },
+    };
+    Ok(cache_home.join(CACHE_DIR_NAME).join(DOWNLOADS_DIR_NAME))
+}
+
+/// Gets the key of a source in the cache, or `None` if the source has no checksum.
+/// The checksums come from the manifests and are used as file names in the cache,
+/// so anything that is not a hex-encoded checksum is rejected.
+pub fn get_cache_key(source: &FlatpakSource) -> Result<Option<String>, String> {
+    if let Some(sha256) = &source.sha256 {
+        // The checksums are case-insensitive, but the cache keys are lowercase.
+        let sha256 = sha256.to_ascii_lowercase();
+        if !is_hex_checksum(&sha256, SHA256_LENGTH) {
+            return Err(format!(
+                "Invalid sha256 {} in source {}.",
+                sha256,
+                get_source_name(source)
+            ));
+        }
+        return Ok(Some(sha256));
+    }
+    if let Some(sha512) = &source.sha512 {
+        // The checksums are case-insensitive, but the cache keys are lowercase.
+        let sha512 = sha512.to_ascii_lowercase();
+        if !is_hex_checksum(&sha512, SHA512_LENGTH) {
+            return Err(format!(
+                "Invalid sha512 {} in source {}.",
+                sha512,
+                get_source_name(source)
+            ));
+        }
+        return Ok(Some(format!("{}{}", SHA512_KEY_PREFIX, sha512)));
+    }
+    Ok(None)
+}
+
+/// Checks that a checksum has the given number of lowercase hex digits.
+fn is_hex_checksum(checksum: &str, length: usize) -> bool {
+    checksum.len() == length
+        && checksum
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+}
+
+fn get_source_name(source: &FlatpakSource) -> String {
+    match (&source.url, &source.path) {
+        (Some(url), _) => url.to_string(),
+        (None, Some(path)) => path.to_string(),
+        (None, None) => source.get_type_name(),
+    }
+}
+
+/// Verifies that the content of a cached file matches its key.
+pub fn verify_cache_entry(key: &str, file_path: &path::Path) -> Result<(), String> {
+    let checksum = match key.strip_prefix(SHA512_KEY_PREFIX) {
+        Some(_) => format!(
+            "{}{}",
+            SHA512_KEY_PREFIX,
+            crate::utils::get_file_sha512(file_path)?
+        ),
+        None => crate::utils::get_file_sha256(file_path)?,
+    };
+    if checksum != key {
+        return Err(format!(
+            "Cached file {} does not match its checksum.",
+            file_path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Gets the path of a cached file, if the file is in the cache.
+pub fn get_cached_file(cache_dir: &path::Path, key: &str) -> Option<path::PathBuf> {
+    let file_path = cache_dir.join(key);
+    if !file_path.is_file() {
+        return None;
+    }
+    Some(file_path)
+}
+
+/// Gets the path where a file is downloaded before being added to the cache.
+pub fn get_partial_file_path(cache_dir: &path::Path, key: &str) -> path::PathBuf {
+    cache_dir.join(format!("{}.{}", key, PARTIAL_EXTENSION))
+}
+
+/// Adds a downloaded file to the cache, once its checksum was verified.
+pub fn add_file(
+    cache_dir: &path::Path,
+    key: &str,
+    file_path: &path::Path,
+) -> Result<path::PathBuf, String> {
+    let cached_file_path = cache_dir.join(key);
+    if let Err(e) = fs::rename(file_path, &cached_file_path) {
+        return Err(format!(
+            "Could not add {} to the cache: {}.",
+            file_path.display(),
+            e
+        ));
+    }
+    Ok(cached_file_path)
+}
+
+/// A file in the download cache.
+pub struct CacheEntry {
+    pub key: String,
+    pub path: path::PathBuf,
+    pub size: u64,
+    /// The file is an interrupted download.
+    pub is_partial: bool,
+}
+
+pub fn get_entries(cache_dir: &path::Path) -> Result<Vec<CacheEntry>, String> {
+    if !cache_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let dir_entries = match fs::read_dir(cache_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(format!(
+                "Could not read directory {}: {}.",
+                cache_dir.display(),
+                e
+            ))
+        }
+    };
+
+    let mut entries: Vec<CacheEntry> = vec![];
+    for dir_entry in dir_entries {
+        let dir_entry = match dir_entry {
+            Ok(e) => e,
+            Err(e) => return Err(e.to_string()),
+        };
+        let metadata = match dir_entry.metadata() {
+            Ok(m) => m,
+            Err(e) => return Err(e.to_string()),
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let file_name = dir_entry.file_name().to_string_lossy().to_string();
+        let partial_suffix = format!(".{}", PARTIAL_EXTENSION);
+        entries.push(CacheEntry {
+            key: file_name.trim_end_matches(&partial_suffix).to_string(),
+            is_partial: file_name.ends_with(&partial_suffix),
+            path: dir_entry.path(),
+            size: metadata.len(),
+        });
+    }
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(entries)
+}
+
+/// Formats a size in bytes for humans.
+pub fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = size as f64;
+    let mut unit_index = 0;
+    while size >= 1024.0 && unit_index < units.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+    if unit_index == 0 {
+        return format!("{} {}", size, units[unit_index]);
+    }
+    format!("{:.1} {}", size, units[unit_index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_source(sha256: Option<&str>, sha512: Option<&str>) -> FlatpakSource {
+        FlatpakSource {
+            url: Some("https://example.org/archive.tar.gz".to_string()),
+            sha256: sha256.map(|s| s.to_string()),
+            sha512: sha512.map(|s| s.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cache_key_of_sha256() {
+        let sha256 = "0123456789abcdef".repeat(4);
+        let source = get_source(Some(&sha256), Some(&"a".repeat(128)));
+        assert_eq!(get_cache_key(&source).unwrap(), Some(sha256));
+    }
+
+    #[test]
+    fn cache_key_of_sha512() {
+        let sha512 = "0123456789abcdef".repeat(8);
+        let source = get_source(None, Some(&sha512));
+        assert_eq!(
+            get_cache_key(&source).unwrap(),
+            Some(format!("sha512-{}", sha512))
+        );
+    }
+
+    #[test]
+    fn cache_key_of_uppercase_checksums() {
+        let sha256 = "0123456789ABCDEF".repeat(4);
+        let source = get_source(Some(&sha256), None);
+        assert_eq!(
+            get_cache_key(&source).unwrap(),
+            Some("0123456789abcdef".repeat(4))
+        );
+
+        let sha512 = "0123456789ABCDEF".repeat(8);
+        let source = get_source(None, Some(&sha512));
+        assert_eq!(
+            get_cache_key(&source).unwrap(),
+            Some(format!("sha512-{}", "0123456789abcdef".repeat(8)))
+        );
+    }
+
+    #[test]
+    fn cache_key_without_checksum() {
+        assert_eq!(get_cache_key(&get_source(None, None)).unwrap(), None);
+    }
+
+    #[test]
+    fn cache_key_rejects_paths() {
+        for sha256 in ["/home/user/.bashrc", "../../x", "", "ab/cd"] {
+            let error = get_cache_key(&get_source(Some(sha256), None)).unwrap_err();
+            assert!(error.contains("https://example.org/archive.tar.gz"));
+        }
+        let sha512 = format!("../{}", "a".repeat(125));
+        assert!(get_cache_key(&get_source(None, Some(&sha512))).is_err());
+    }
+
+    #[test]
+    fn cache_key_rejects_invalid_checksums() {
+        for sha256 in ["a".repeat(63), "a".repeat(65), "g".repeat(64)] {
+            assert!(get_cache_key(&get_source(Some(&sha256), None)).is_err());
+        }
+        assert!(get_cache_key(&get_source(None, Some(&"a".repeat(64)))).is_err());
+    }
+}
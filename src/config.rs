@@ -0,0 +1,202 @@
+//! The project configuration, read from a `.fpcli.toml` file in the directory
+//! of the manifest or in one of its parents.
+//!
+//! ```toml
+//! [lint]
+//! # Only run these rules. All the rules are run by default.
+//! enable = ["archive-missing-sha256", "git-source-not-pinned"]
+//! disable = ["git-source-not-pinned"]
+//!
+//! [lint.severity]
+//! eol-runtime = "error"
+//!
+//! # The runtime versions reported by the eol-runtime rule, besides the built-in ones.
+//! [lint.eol-runtimes]
+//! "org.gnome.Platform" = ["49"]
+//!
+//! # The patterns are relative to the directory of the configuration file. A `*`
+//! # does not match the `/` separators, use `**` to match the nested directories.
+//! [lint.per-file-ignores]
+//! "legacy/*.yaml" = ["archive-missing-sha256"]
+//! "vendor/**" = ["*"]
+//! ```
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+
+use serde::Deserialize;
+
+pub const CONFIG_FILE_NAME: &str = ".fpcli.toml";
+/// Matches all the lint rules in the per-file ignores.
+pub const ALL_RULES: &str = "*";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub lint: LintConfig,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LintConfig {
+    /// When not empty, only these rules are run.
+    pub enable: Vec<String>,
+    pub disable: Vec<String>,
+    pub severity: BTreeMap<String, String>,
+    pub per_file_ignores: BTreeMap<String, Vec<String>>,
+    /// The end-of-life versions of each runtime, added to the built-in ones.
+    pub eol_runtimes: BTreeMap<String, Vec<String>>,
+}
+
+/// A configuration file, and the directory its paths are relative to.
+pub struct ProjectConfig {
+    pub base_dir: path::PathBuf,
+    pub config: Config,
+}
+
+/// Finds the configuration file for the manifest at `manifest_path`, starting
+/// from the directory of the manifest and going up to the root.
+pub fn find_config_file(manifest_path: &str) -> Option<path::PathBuf> {
+    let manifest_path = fs::canonicalize(manifest_path).ok()?;
+    let mut dir = manifest_path.parent();
+    while let Some(d) = dir {
+        let config_path = d.join(CONFIG_FILE_NAME);
+        if config_path.is_file() {
+            return Some(config_path);
+        }
+        dir = d.parent();
+    }
+    None
+}
+
+pub fn load_config(config_path: &path::Path) -> Result<ProjectConfig, String> {
+    let config_content = match fs::read_to_string(config_path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(format!(
+                "Could not read file {}: {}!",
+                config_path.display(),
+                e
+            ))
+        }
+    };
+    let config: Config = match toml::from_str(&config_content) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(format!(
+                "Invalid configuration file {}: {}.",
+                config_path.display(),
+                e
+            ))
+        }
+    };
+
+    let lint_config = &config.lint;
+    let mut rule_ids: Vec<&String> = vec![];
+    rule_ids.extend(&lint_config.enable);
+    rule_ids.extend(&lint_config.disable);
+    rule_ids.extend(lint_config.severity.keys());
+    for (pattern, pattern_rule_ids) in &lint_config.per_file_ignores {
+        if let Err(e) = glob::Pattern::new(pattern) {
+            return Err(format!(
+                "Invalid pattern {} in {}: {}.",
+                pattern,
+                config_path.display(),
+                e
+            ));
+        }
+        rule_ids.extend(pattern_rule_ids.iter().filter(|r| *r != ALL_RULES));
+    }
+    for rule_id in rule_ids {
+        if crate::lint::get_rule(rule_id).is_none() {
+            return Err(format!(
+                "Unknown lint rule {} in {}.",
+                rule_id,
+                config_path.display()
+            ));
+        }
+    }
+    for severity in lint_config.severity.values() {
+        crate::lint::Severity::from_string(severity)?;
+    }
+
+    Ok(ProjectConfig {
+        base_dir: config_path.parent().unwrap().to_path_buf(),
+        config,
+    })
+}
+
+/// Loads the configuration file for the manifest at `manifest_path`, if there is one.
+pub fn get_project_config(manifest_path: &str) -> Result<Option<ProjectConfig>, String> {
+    match find_config_file(manifest_path) {
+        Some(config_path) => Ok(Some(load_config(&config_path)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{get_test_dir, write_test_file};
+
+    #[test]
+    fn find_config_file_in_the_parents() {
+        let test_dir = get_test_dir("find_config_file_in_the_parents");
+        write_test_file(&test_dir.join(CONFIG_FILE_NAME), "");
+        write_test_file(&test_dir.join("a/b/module.yaml"), "");
+        write_test_file(&test_dir.join("a/b/c/module.yaml"), "");
+        write_test_file(&test_dir.join("a/b/c").join(CONFIG_FILE_NAME), "");
+
+        let config_path = find_config_file(&test_dir.join("a/b/module.yaml").to_string_lossy());
+        assert_eq!(
+            config_path,
+            Some(fs::canonicalize(&test_dir).unwrap().join(CONFIG_FILE_NAME))
+        );
+        // The closest configuration file is used.
+        let config_path = find_config_file(&test_dir.join("a/b/c/module.yaml").to_string_lossy());
+        assert_eq!(
+            config_path,
+            Some(
+                fs::canonicalize(test_dir.join("a/b/c"))
+                    .unwrap()
+                    .join(CONFIG_FILE_NAME)
+            )
+        );
+    }
+
+    #[test]
+    fn load_config_relative_to_its_directory() {
+        let test_dir = get_test_dir("load_config_relative_to_its_directory");
+        let config_path = test_dir.join(CONFIG_FILE_NAME);
+        write_test_file(
+            &config_path,
+            "[lint]\ndisable = [\"eol-runtime\"]\n\n[lint.per-file-ignores]\n\"legacy/*.yaml\" = [\"*\"]\n",
+        );
+        let project_config = load_config(&config_path).unwrap();
+        assert_eq!(project_config.base_dir, test_dir);
+        assert_eq!(project_config.config.lint.disable, vec!["eol-runtime"]);
+        assert_eq!(project_config.config.lint.per_file_ignores.len(), 1);
+    }
+
+    #[test]
+    fn load_invalid_configs() {
+        let test_dir = get_test_dir("load_invalid_configs");
+        let config_path = test_dir.join(CONFIG_FILE_NAME);
+        for (config_content, expected_error) in [
+            (
+                "[lint]\nenable = [\"unknown\"]\n",
+                "Unknown lint rule unknown",
+            ),
+            ("[lint.severity]\neol-runtime = \"fatal\"\n", "fatal"),
+            (
+                "[lint.per-file-ignores]\n\"[\" = [\"*\"]\n",
+                "Invalid pattern [",
+            ),
+            ("[lint]\nunknown = true\n", "Invalid configuration file"),
+        ] {
+            write_test_file(&config_path, config_content);
+            let error = load_config(&config_path).err().unwrap();
+            assert!(error.contains(expected_error), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! Detects the build system, the name and the runtime of a project from its source
+//! tree, to bootstrap a manifest for it.
+use std::fs;
+use std::path;
+use std::process::Command;
+
+use crate::bootstrap::{Runtime, FREEDESKTOP_RUNTIME, GNOME_RUNTIME, KDE_RUNTIME};
+
+/// The files identifying the build systems, in the order they are checked. The
+/// projects using meson often also have a Cargo.toml or a setup.py, so meson comes
+/// first.
+pub const BUILD_SYSTEM_FILES: [(&str, &str); 8] = [
+    ("meson.build", crate::bootstrap::BUILD_SYSTEM_MESON),
+    ("CMakeLists.txt", crate::bootstrap::BUILD_SYSTEM_CMAKE_NINJA),
+    ("configure.ac", crate::bootstrap::BUILD_SYSTEM_AUTOTOOLS),
+    ("Cargo.toml", crate::bootstrap::BUILD_SYSTEM_CARGO),
+    ("setup.py", crate::bootstrap::BUILD_SYSTEM_PYTHON),
+    ("pyproject.toml", crate::bootstrap::BUILD_SYSTEM_PYTHON),
+    ("package.json", crate::bootstrap::BUILD_SYSTEM_NODE),
+    ("go.mod", crate::bootstrap::BUILD_SYSTEM_GO),
+];
+
+/// The files listing the dependencies of a project, besides the build system files.
+pub const DEPENDENCY_FILES: [&str; 2] = ["requirements.txt", "configure.in"];
+
+/// The dependencies showing that a project uses Qt or the KDE frameworks.
+pub const KDE_DEPENDENCIES: [&str; 8] = [
+    "qt5",
+    "qt6",
+    "kf5",
+    "kf6",
+    "pyqt",
+    "pyside",
+    "qmetaobject",
+    "cxx-qt",
+];
+/// The dependencies showing that a project uses GTK.
+pub const GNOME_DEPENDENCIES: [&str; 5] = ["gtk", "adwaita", "libhandy", "pygobject", "relm4"];
+
+#[derive(Debug)]
+pub struct Project {
+    /// The bootstrap build system of the project, if it could be detected.
+    pub build_system: Option<String>,
+    pub name: String,
+    pub runtime: &'static Runtime,
+    /// The app ID derived from the URL of the git remote of the project.
+    pub app_id: Option<String>,
+}
+
+/// Inspects the source tree in `dir`.
+pub fn detect_project(dir: &path::Path) -> Result<Project, String> {
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory.", dir.display()));
+    }
+
+    let mut build_system: Option<String> = None;
+    let mut name: Option<String> = None;
+    for (file_name, file_build_system) in BUILD_SYSTEM_FILES {
+        let content = match fs::read_to_string(dir.join(file_name)) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        if build_system.is_none() {
+            build_system = Some(file_build_system.to_string());
+        }
+        if name.is_none() {
+            name = get_project_name(file_name, &content);
+        }
+    }
+    if build_system.is_none() && has_file_with_extension(dir, "pro") {
+        build_system = Some(crate::bootstrap::BUILD_SYSTEM_QMAKE.to_string());
+    }
+
+    let name = match name {
+        Some(n) => n,
+        None => get_dir_name(dir)?,
+    };
+    Ok(Project {
+        build_system,
+        name,
+        runtime: get_runtime(dir),
+        app_id: get_git_remote_url(dir).and_then(|u| get_app_id(&u)),
+    })
+}
+
+/// Gets the name of a project declared in one of the build system files.
+fn get_project_name(file_name: &str, content: &str) -> Option<String> {
+    let name = match file_name {
+        "meson.build" => get_call_argument(content, "project(").and_then(|a| get_quoted_string(&a)),
+        "CMakeLists.txt" => {
+            // The CMake commands are case insensitive, but not the name of the project.
+            let index = content.to_lowercase().find("project(")? + "project(".len();
+            content[index..]
+                .split(|c: char| c.is_whitespace() || c == ')')
+                .find(|w| !w.is_empty())
+                .map(|w| w.trim_matches('"').to_string())
+        }
+        "configure.ac" => get_call_argument(content, "AC_INIT(").map(|a| {
+            a.split(',')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string()
+        }),
+        "Cargo.toml" => get_toml_string(content, &["package", "name"]),
+        "pyproject.toml" => get_toml_string(content, &["project", "name"])
+            .or_else(|| get_toml_string(content, &["tool", "poetry", "name"])),
+        "setup.py" => content
+            .find("name=")
+            .and_then(|i| get_quoted_string(&content[i + "name=".len()..])),
+        "package.json" => match serde_json::from_str::<serde_json::Value>(content) {
+            // The scope of the package is not part of the name of the program.
+            Ok(v) => v["name"]
+                .as_str()
+                .map(|n| n.rsplit('/').next().unwrap_or(n).to_string()),
+            Err(_) => None,
+        },
+        "go.mod" => content
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("module "))
+            .and_then(|m| m.trim().rsplit('/').next())
+            .map(|n| n.to_string()),
+        _ => None,
+    };
+    name.filter(|n| !n.is_empty())
+}
+
+/// Gets the arguments of the first call of a function, like `project(`.
+fn get_call_argument(content: &str, function: &str) -> Option<String> {
+    let start = content.find(function)? + function.len();
+    let end = content[start..].find(')')?;
+    Some(content[start..start + end].to_string())
+}
+
+/// Gets the first quoted string in some text.
+fn get_quoted_string(text: &str) -> Option<String> {
+    let start = text.find(['\'', '"'])?;
+    let quote = text[start..].chars().next()?;
+    let end = text[start + 1..].find(quote)?;
+    Some(text[start + 1..start + 1 + end].to_string())
+}
+
+fn get_toml_string(content: &str, keys: &[&str]) -> Option<String> {
+    let mut value: &toml::Value = &toml::from_str(content).ok()?;
+    for key in keys {
+        value = value.get(key)?;
+    }
+    value.as_str().map(|v| v.to_string())
+}
+
+fn has_file_with_extension(dir: &path::Path, extension: &str) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return false,
+    };
+    entries
+        .flatten()
+        .any(|e| e.path().extension().and_then(|e| e.to_str()) == Some(extension))
+}
+
+fn get_dir_name(dir: &path::Path) -> Result<String, String> {
+    let dir = match fs::canonicalize(dir) {
+        Ok(d) => d,
+        Err(e) => return Err(format!("Could not read {}: {}.", dir.display(), e)),
+    };
+    match dir.file_name() {
+        Some(n) => Ok(n.to_string_lossy().to_string()),
+        None => Ok(crate::bootstrap::DEFAULT_MODULE_NAME.to_string()),
+    }
+}
+
+/// Picks the runtime from the dependencies found in the files of the project.
+/// Projects using neither Qt nor GTK use the Freedesktop runtime.
+fn get_runtime(dir: &path::Path) -> &'static Runtime {
+    let mut dependencies = String::new();
+    let file_names = BUILD_SYSTEM_FILES
+        .iter()
+        .map(|(f, _)| *f)
+        .chain(DEPENDENCY_FILES);
+    for file_name in file_names {
+        if let Ok(content) = fs::read_to_string(dir.join(file_name)) {
+            dependencies.push_str(&content.to_lowercase());
+        }
+    }
+
+    if KDE_DEPENDENCIES.iter().any(|d| dependencies.contains(d))
+        || has_file_with_extension(dir, "pro")
+    {
+        return &KDE_RUNTIME;
+    }
+    if GNOME_DEPENDENCIES.iter().any(|d| dependencies.contains(d)) {
+        return &GNOME_RUNTIME;
+    }
+    &FREEDESKTOP_RUNTIME
+}
+
+/// Gets the URL of the origin remote of the git repository in `dir`.
+fn get_git_remote_url(dir: &path::Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if url.is_empty() {
+        return None;
+    }
+    Some(url)
+}
+
+/// Gets the app ID of a project from the URL of its git repository.
+pub fn get_app_id(url: &str) -> Option<String> {
+    let git_url = crate::git::parse_git_url(url)?;
+    if git_url.host.is_empty() {
+        return None;
+    }
+    let url = format!("https://{}/{}", git_url.host, git_url.path);
+    Some(flatpak_rs::reverse_dns::from_url(&url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{get_test_dir, write_test_file};
+
+    #[test]
+    fn detect_meson_gtk4_project() {
+        let dir = get_test_dir("detect_meson_gtk4_project");
+        write_test_file(
+            &dir.join("meson.build"),
+            "project('hello-gtk', 'rust', version: '1.0')\ndependency('gtk4', version: '>= 4.10')\n",
+        );
+        // The Cargo.toml of a meson project does not change the build system.
+        write_test_file(
+            &dir.join("Cargo.toml"),
+            "[package]\nname = \"hello_gtk\"\n\n[dependencies]\ngtk = { package = \"gtk4\", version = \"0.9\" }\n",
+        );
+
+        let project = detect_project(&dir).unwrap();
+        assert_eq!(
+            project.build_system.as_deref(),
+            Some(crate::bootstrap::BUILD_SYSTEM_MESON)
+        );
+        assert_eq!(project.name, "hello-gtk");
+        assert_eq!(project.runtime.name, GNOME_RUNTIME.name);
+    }
+
+    #[test]
+    fn detect_qmake_project() {
+        let dir = get_test_dir("detect_qmake_project");
+        write_test_file(
+            &dir.join("hello.pro"),
+            "QT += widgets\nSOURCES += main.cpp\n",
+        );
+        write_test_file(&dir.join("main.cpp"), "int main() { return 0; }\n");
+
+        let project = detect_project(&dir).unwrap();
+        assert_eq!(
+            project.build_system.as_deref(),
+            Some(crate::bootstrap::BUILD_SYSTEM_QMAKE)
+        );
+        assert_eq!(project.name, "detect_qmake_project");
+        assert_eq!(project.runtime.name, KDE_RUNTIME.name);
+    }
+
+    #[test]
+    fn detect_cargo_project() {
+        let dir = get_test_dir("detect_cargo_project");
+        write_test_file(
+            &dir.join("Cargo.toml"),
+            "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\n",
+        );
+
+        let project = detect_project(&dir).unwrap();
+        assert_eq!(
+            project.build_system.as_deref(),
+            Some(crate::bootstrap::BUILD_SYSTEM_CARGO)
+        );
+        assert_eq!(project.name, "hello");
+        assert_eq!(project.runtime.name, FREEDESKTOP_RUNTIME.name);
+    }
+
+    #[test]
+    fn detect_app_id_from_the_git_remote() {
+        let dir = get_test_dir("detect_app_id_from_the_git_remote");
+        write_test_file(&dir.join("go.mod"), "module github.com/example/hello\n");
+        for args in [
+            vec!["init", "--quiet"],
+            vec![
+                "remote",
+                "add",
+                "origin",
+                "https://git.example.org/example/hello.git",
+            ],
+        ] {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(&dir)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+
+        let project = detect_project(&dir).unwrap();
+        assert_eq!(project.name, "hello");
+        assert_eq!(
+            project.app_id.as_deref(),
+            Some("org.example.git.example.hello")
+        );
+    }
+
+    #[test]
+    fn detect_unknown_project() {
+        let dir = get_test_dir("detect_unknown_project");
+        write_test_file(&dir.join("README"), "Hello\n");
+
+        let project = detect_project(&dir).unwrap();
+        assert_eq!(project.build_system, None);
+        assert_eq!(project.name, "detect_unknown_project");
+        assert_eq!(project.runtime.name, FREEDESKTOP_RUNTIME.name);
+        assert!(detect_project(&dir.join("README")).is_err());
+    }
+}
@@ -0,0 +1,459 @@
+//! Edits the modules of a manifest. The modules are selected by name, including the
+//! modules imported by path, which are loaded to get their name, or by their import
+//! path. A selector matching several modules is an error.
+use std::fs;
+use std::path;
+
+use flatpak_rs::format::FlatpakManifestFormat;
+use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+
+/// Where to add a module in a manifest.
+#[derive(Debug, Default)]
+pub struct ModulePosition {
+    /// The name of the module to add the module to. The module is added to the
+    /// manifest when there is no parent.
+    pub parent: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Gets the name of a module. The modules imported by path are loaded relative to
+/// `manifest_dir`.
+pub fn get_module_name(
+    manifest_dir: &path::Path,
+    module_item: &FlatpakModuleItem,
+) -> Option<String> {
+    match module_item {
+        FlatpakModuleItem::Description(m) => Some(m.name.to_string()),
+        FlatpakModuleItem::Path(p) => {
+            let module_path = manifest_dir.join(p);
+            match FlatpakModule::load_from_file(module_path.to_string_lossy().to_string()) {
+                Ok(m) => Some(m.name),
+                Err(_) => None,
+            }
+        }
+    }
+}
+
+/// Whether a module is selected by `selector`, which is a module name or the path
+/// of an imported module.
+pub fn is_module_selected(
+    manifest_dir: &path::Path,
+    module_item: &FlatpakModuleItem,
+    selector: &str,
+) -> bool {
+    if let FlatpakModuleItem::Path(p) = module_item {
+        if path::Path::new(p) == path::Path::new(selector) {
+            return true;
+        }
+    }
+    get_module_name(manifest_dir, module_item).as_deref() == Some(selector)
+}
+
+/// Gets the locations of the modules selected by `selector`, at any depth, like
+/// `modules[1].modules[0]`.
+fn get_module_locations(
+    manifest_dir: &path::Path,
+    module_items: &[FlatpakModuleItem],
+    selector: &str,
+    parent_location: &str,
+    locations: &mut Vec<String>,
+) {
+    for (index, module_item) in module_items.iter().enumerate() {
+        let location = format!("{}modules[{}]", parent_location, index);
+        if is_module_selected(manifest_dir, module_item, selector) {
+            match module_item {
+                FlatpakModuleItem::Description(_) => locations.push(location.to_string()),
+                FlatpakModuleItem::Path(p) => locations.push(format!("{} ({})", location, p)),
+            };
+        }
+        if let FlatpakModuleItem::Description(module) = module_item {
+            let parent_location = format!("{}.", location);
+            get_module_locations(
+                manifest_dir,
+                &module.modules,
+                selector,
+                &parent_location,
+                locations,
+            );
+        }
+    }
+}
+
+/// Checks that `selector` selects exactly one module, at any depth.
+pub fn check_module_selector(
+    manifest_dir: &path::Path,
+    module_items: &[FlatpakModuleItem],
+    selector: &str,
+) -> Result<(), String> {
+    let mut locations: Vec<String> = vec![];
+    get_module_locations(manifest_dir, module_items, selector, "", &mut locations);
+    match locations.len() {
+        0 => Err(format!("Could not find module {}.", selector)),
+        1 => Ok(()),
+        _ => Err(format!(
+            "Module {} is ambiguous, it matches the modules at {}. An imported module can be selected by its path.",
+            selector,
+            locations.join(", ")
+        )),
+    }
+}
+
+/// Finds an inline module by name, at any depth.
+pub fn find_module_mut<'a>(
+    module_items: &'a mut [FlatpakModuleItem],
+    module_name: &str,
+) -> Option<&'a mut FlatpakModule> {
+    for module_item in module_items.iter_mut() {
+        if let FlatpakModuleItem::Description(module) = module_item {
+            if module.name == module_name {
+                return Some(module);
+            }
+            if let Some(child_module) = find_module_mut(&mut module.modules, module_name) {
+                return Some(child_module);
+            }
+        }
+    }
+    None
+}
+
+/// Finds the list of modules containing a module, at any depth.
+pub fn find_containing_modules<'a>(
+    manifest_dir: &path::Path,
+    module_items: &'a mut Vec<FlatpakModuleItem>,
+    module_name: &str,
+) -> Option<&'a mut Vec<FlatpakModuleItem>> {
+    if get_module_index(manifest_dir, module_items, module_name).is_some() {
+        return Some(module_items);
+    }
+    for module_item in module_items.iter_mut() {
+        if let FlatpakModuleItem::Description(module) = module_item {
+            if let Some(modules) =
+                find_containing_modules(manifest_dir, &mut module.modules, module_name)
+            {
+                return Some(modules);
+            }
+        }
+    }
+    None
+}
+
+/// Gets the index of a module in a list of modules.
+pub fn get_module_index(
+    manifest_dir: &path::Path,
+    module_items: &[FlatpakModuleItem],
+    selector: &str,
+) -> Option<usize> {
+    module_items
+        .iter()
+        .position(|m| is_module_selected(manifest_dir, m, selector))
+}
+
+/// Gets the modules of the parent module, or the modules of the manifest when there
+/// is no parent.
+pub fn get_parent_modules<'a>(
+    manifest_dir: &path::Path,
+    module_items: &'a mut Vec<FlatpakModuleItem>,
+    parent_name: Option<&str>,
+) -> Result<&'a mut Vec<FlatpakModuleItem>, String> {
+    let parent_name = match parent_name {
+        Some(n) => n,
+        None => return Ok(module_items),
+    };
+    check_module_selector(manifest_dir, module_items, parent_name)?;
+    // The module was found, so it is imported when it is not an inline module.
+    if find_module_mut(module_items, parent_name).is_none() {
+        return Err(format!(
+            "Module {} is imported from another manifest. Edit that manifest instead.",
+            parent_name
+        ));
+    }
+    Ok(&mut find_module_mut(module_items, parent_name).unwrap().modules)
+}
+
+/// Adds a module to a list of modules, at the given position. Returns the index of
+/// the module in the list it was added to.
+pub fn insert_module(
+    manifest_dir: &path::Path,
+    module_items: &mut Vec<FlatpakModuleItem>,
+    module_item: FlatpakModuleItem,
+    position: &ModulePosition,
+) -> Result<usize, String> {
+    let sibling_name = position.before.as_ref().or(position.after.as_ref());
+    if let (None, Some(sibling_name)) = (&position.parent, sibling_name) {
+        check_module_selector(manifest_dir, module_items, sibling_name)?;
+    }
+    let parent_modules = match (&position.parent, sibling_name) {
+        // Without a parent, the module is added next to the other module, at any depth.
+        (None, Some(sibling_name)) => {
+            match find_containing_modules(manifest_dir, module_items, sibling_name) {
+                Some(m) => m,
+                None => return Err(format!("Could not find module {}.", sibling_name)),
+            }
+        }
+        _ => get_parent_modules(manifest_dir, module_items, position.parent.as_deref())?,
+    };
+    if let (Some(parent), Some(sibling_name)) = (&position.parent, sibling_name) {
+        let sibling_count = parent_modules
+            .iter()
+            .filter(|m| is_module_selected(manifest_dir, m, sibling_name))
+            .count();
+        if sibling_count > 1 {
+            return Err(format!(
+                "Module {} is ambiguous, module {} has {} modules matching it.",
+                sibling_name, parent, sibling_count
+            ));
+        }
+    }
+
+    let index = if let Some(before) = &position.before {
+        match get_module_index(manifest_dir, parent_modules, before) {
+            Some(i) => i,
+            None => return Err(format!("Could not find module {}.", before)),
+        }
+    } else if let Some(after) = &position.after {
+        match get_module_index(manifest_dir, parent_modules, after) {
+            Some(i) => i + 1,
+            None => return Err(format!("Could not find module {}.", after)),
+        }
+    } else {
+        parent_modules.len()
+    };
+    parent_modules.insert(index, module_item);
+    Ok(index)
+}
+
+/// Gets the content of a YAML module manifest once its module is edited, like when
+/// its local paths are rebased, so the module can be inlined with its comments.
+/// Returns `None` for the other formats.
+pub fn get_module_content(
+    module_path: &str,
+    initial_module: &FlatpakModule,
+    module: &FlatpakModule,
+) -> Result<Option<String>, String> {
+    if !matches!(
+        FlatpakManifestFormat::from_path(module_path),
+        Some(FlatpakManifestFormat::YAML)
+    ) {
+        return Ok(None);
+    }
+    let content = match fs::read_to_string(module_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read file {}: {}!", module_path, e)),
+    };
+    let initial_value = serde_yaml::to_value(initial_module).map_err(|e| e.to_string())?;
+    let value = serde_yaml::to_value(module).map_err(|e| e.to_string())?;
+    crate::yaml::update_content(&content, &initial_value, &value).map(Some)
+}
+
+/// Gets the JSON pointer of the list of modules that has a different number of
+/// modules than before an edit, like `/modules/1/modules`. `pointer` is the JSON
+/// pointer of `module_items`.
+pub fn get_edited_modules_pointer(
+    initial_module_items: &[FlatpakModuleItem],
+    module_items: &[FlatpakModuleItem],
+    pointer: &str,
+) -> Option<String> {
+    if initial_module_items.len() != module_items.len() {
+        return Some(pointer.to_string());
+    }
+    for (index, (initial_module_item, module_item)) in
+        initial_module_items.iter().zip(module_items).enumerate()
+    {
+        if let (
+            FlatpakModuleItem::Description(initial_module),
+            FlatpakModuleItem::Description(module),
+        ) = (initial_module_item, module_item)
+        {
+            let modules_pointer = format!("{}/{}/modules", pointer, index);
+            if let Some(p) = get_edited_modules_pointer(
+                &initial_module.modules,
+                &module.modules,
+                &modules_pointer,
+            ) {
+                return Some(p);
+            }
+        }
+    }
+    None
+}
+
+/// Removes the module selected by `selector`, at any depth, and returns it.
+pub fn remove_module(
+    manifest_dir: &path::Path,
+    module_items: &mut Vec<FlatpakModuleItem>,
+    selector: &str,
+) -> Result<FlatpakModuleItem, String> {
+    check_module_selector(manifest_dir, module_items, selector)?;
+    match take_module(manifest_dir, module_items, selector) {
+        Some(m) => Ok(m),
+        None => Err(format!("Could not find module {}.", selector)),
+    }
+}
+
+fn take_module(
+    manifest_dir: &path::Path,
+    module_items: &mut Vec<FlatpakModuleItem>,
+    selector: &str,
+) -> Option<FlatpakModuleItem> {
+    if let Some(index) = get_module_index(manifest_dir, module_items, selector) {
+        return Some(module_items.remove(index));
+    }
+    for module_item in module_items.iter_mut() {
+        if let FlatpakModuleItem::Description(module) = module_item {
+            if let Some(removed_module) = take_module(manifest_dir, &mut module.modules, selector) {
+                return Some(removed_module);
+            }
+        }
+    }
+    None
+}
+
+/// Moves the module selected by `module_name`, at any depth, to the given position.
+pub fn move_module(
+    manifest_dir: &path::Path,
+    module_items: &mut Vec<FlatpakModuleItem>,
+    module_name: &str,
+    position: &ModulePosition,
+) -> Result<(), String> {
+    let target_names = [&position.parent, &position.before, &position.after];
+    if target_names
+        .iter()
+        .any(|n| n.as_deref() == Some(module_name))
+    {
+        return Err(format!(
+            "Cannot move module {} relative to itself.",
+            module_name
+        ));
+    }
+
+    let module_item = remove_module(manifest_dir, module_items, module_name)?;
+    if let (Some(parent), FlatpakModuleItem::Description(module)) = (&position.parent, &module_item)
+    {
+        if find_module_mut(&mut module.modules.clone(), parent).is_some() {
+            return Err(format!(
+                "Cannot move module {} into its own module {}.",
+                module_name, parent
+            ));
+        }
+    }
+    insert_module(manifest_dir, module_items, module_item, position).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the modules of a manifest with an inline zlib module in the app module,
+    /// and another zlib module imported from `sub/zlib.yaml`.
+    fn get_module_items(test_name: &str) -> (path::PathBuf, Vec<FlatpakModuleItem>) {
+        let manifest_dir = crate::utils::get_test_dir(test_name);
+        crate::utils::write_test_file(
+            &manifest_dir.join("sub/zlib.yaml"),
+            "name: zlib\nsources:\n  - type: dir\n    path: .\n",
+        );
+        let module_items = vec![
+            FlatpakModuleItem::Path("sub/zlib.yaml".to_string()),
+            FlatpakModuleItem::Description(FlatpakModule {
+                name: "app".to_string(),
+                modules: vec![FlatpakModuleItem::Description(FlatpakModule {
+                    name: "zlib".to_string(),
+                    ..Default::default()
+                })],
+                ..Default::default()
+            }),
+        ];
+        (manifest_dir, module_items)
+    }
+
+    #[test]
+    fn ambiguous_module_names() {
+        let (manifest_dir, mut module_items) = get_module_items("ambiguous_module_names");
+        let error = remove_module(&manifest_dir, &mut module_items, "zlib").unwrap_err();
+        assert_eq!(
+            error,
+            "Module zlib is ambiguous, it matches the modules at modules[0] (sub/zlib.yaml), modules[1].modules[0]. An imported module can be selected by its path."
+        );
+        assert_eq!(module_items.len(), 2);
+
+        let position = ModulePosition {
+            after: Some("zlib".to_string()),
+            ..Default::default()
+        };
+        let module_item = FlatpakModuleItem::Path("other.yaml".to_string());
+        assert!(
+            insert_module(&manifest_dir, &mut module_items, module_item, &position)
+                .unwrap_err()
+                .starts_with("Module zlib is ambiguous")
+        );
+    }
+
+    #[test]
+    fn edited_modules_pointer() {
+        let (manifest_dir, mut module_items) = get_module_items("edited_modules_pointer");
+        let initial_module_items = module_items.clone();
+        assert_eq!(
+            get_edited_modules_pointer(&initial_module_items, &module_items, "/modules"),
+            None
+        );
+
+        let position = ModulePosition {
+            parent: Some("app".to_string()),
+            ..Default::default()
+        };
+        let module_item = FlatpakModuleItem::Path("other.yaml".to_string());
+        let index =
+            insert_module(&manifest_dir, &mut module_items, module_item, &position).unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(
+            get_edited_modules_pointer(&initial_module_items, &module_items, "/modules").unwrap(),
+            "/modules/1/modules"
+        );
+    }
+
+    #[test]
+    fn select_imported_modules_by_path() {
+        let (manifest_dir, mut module_items) = get_module_items("select_imported_modules_by_path");
+        let removed_module =
+            remove_module(&manifest_dir, &mut module_items, "sub/zlib.yaml").unwrap();
+        assert!(matches!(removed_module, FlatpakModuleItem::Path(p) if p == "sub/zlib.yaml"));
+
+        // Once the imported module is removed, the name is not ambiguous anymore.
+        remove_module(&manifest_dir, &mut module_items, "zlib").unwrap();
+        match &module_items[0] {
+            FlatpakModuleItem::Description(m) => assert!(m.modules.is_empty()),
+            FlatpakModuleItem::Path(_) => panic!("The app module was removed."),
+        };
+        assert_eq!(
+            remove_module(&manifest_dir, &mut module_items, "zlib").unwrap_err(),
+            "Could not find module zlib."
+        );
+    }
+
+    #[test]
+    fn move_imported_modules_by_path() {
+        let (manifest_dir, mut module_items) = get_module_items("move_imported_modules_by_path");
+        let position = ModulePosition {
+            parent: Some("app".to_string()),
+            ..Default::default()
+        };
+        move_module(&manifest_dir, &mut module_items, "sub/zlib.yaml", &position).unwrap();
+        match &module_items[0] {
+            FlatpakModuleItem::Description(m) => {
+                assert!(matches!(&m.modules[1], FlatpakModuleItem::Path(p) if p == "sub/zlib.yaml"))
+            }
+            FlatpakModuleItem::Path(_) => panic!("The imported module was not moved."),
+        };
+
+        // An imported module cannot be the parent of another module.
+        let position = ModulePosition {
+            parent: Some("sub/zlib.yaml".to_string()),
+            ..Default::default()
+        };
+        let module_item = FlatpakModuleItem::Path("other.yaml".to_string());
+        assert_eq!(
+            insert_module(&manifest_dir, &mut module_items, module_item, &position).unwrap_err(),
+            "Module sub/zlib.yaml is imported from another manifest. Edit that manifest instead."
+        );
+    }
+}
@@ -0,0 +1,921 @@
+//! Fetching and extraction of the sources of the modules.
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path;
+use std::process::Command;
+
+use flatpak_rs::archive::FlatpakArchiveType;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+pub const DEFAULT_SCRIPT_FILENAME: &str = "autogen.sh";
+pub const DEFAULT_STRIP_COMPONENTS: i64 = 1;
+
+/// The name of the temporary directory used while extracting the sources,
+/// inside the temporary directory of the fetch.
+pub const EXTRACT_DIR_NAME: &str = "extract";
+
+/// The location of the files needed to fetch the sources.
+pub struct FetchConfig {
+    /// The directory relative to which the local paths of the sources are resolved.
+    /// This is normally the directory of the manifest.
+    pub base_dir: path::PathBuf,
+    /// The directory where the archives are extracted and the repositories are cloned,
+    /// before being moved to the source directory.
+    pub tmp_dir: path::PathBuf,
+    /// The directory of the download cache.
+    pub cache_dir: path::PathBuf,
+    /// The application directory of the build sandbox in which the commands of the
+    /// shell sources are run. They are run on the host when there is no sandbox.
+    pub sandbox_app_dir: Option<path::PathBuf>,
+}
+
+/// Gets the name of the current architecture, as used in the `only-arches` and
+/// `skip-arches` fields of the manifests.
+pub fn get_current_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86" => "i386",
+        a => a,
+    }
+}
+
+fn source_applies_to_arch(source: &FlatpakSource, arch: &str) -> bool {
+    if let Some(only_arches) = &source.only_arches {
+        if !only_arches.is_empty() && !only_arches.iter().any(|a| a == arch) {
+            return false;
+        }
+    }
+    if let Some(skip_arches) = &source.skip_arches {
+        if skip_arches.iter().any(|a| a == arch) {
+            return false;
+        }
+    }
+    true
+}
+
+fn run_command(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = match command.output() {
+        Ok(o) => o,
+        Err(e) => return Err(format!("Could not run {}: {}.", program, e)),
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "`{}` failed: {}",
+            crate::install::command_to_string(command),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn create_dir(dir: &path::Path) -> Result<(), String> {
+    if let Err(e) = fs::create_dir_all(dir) {
+        return Err(format!(
+            "Could not create directory {}: {}.",
+            dir.display(),
+            e
+        ));
+    }
+    Ok(())
+}
+
+fn remove_dir(dir: &path::Path) -> Result<(), String> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    if let Err(e) = fs::remove_dir_all(dir) {
+        return Err(format!(
+            "Could not remove directory {}: {}.",
+            dir.display(),
+            e
+        ));
+    }
+    Ok(())
+}
+
+/// Gets the directory, inside the source directory, where a source is extracted.
+fn get_dest_dir(source: &FlatpakSource, source_dir: &path::Path) -> Result<path::PathBuf, String> {
+    let dest = match &source.dest {
+        Some(d) => d,
+        None => return Ok(source_dir.to_path_buf()),
+    };
+    let dest_path = path::Path::new(dest);
+    if dest_path.is_absolute()
+        || dest_path
+            .components()
+            .any(|c| c == path::Component::ParentDir)
+    {
+        return Err(format!(
+            "Invalid dest {}: it must be inside the source directory.",
+            dest
+        ));
+    }
+    Ok(source_dir.join(dest_path))
+}
+
+/// Gets the file name of a downloaded file, from the last segment of its URL.
+pub fn get_url_filename(url: &str) -> String {
+    let url = url.split(&['?', '#'][..]).next().unwrap_or(url);
+    match url.trim_end_matches('/').rsplit('/').next() {
+        Some(f) if !f.is_empty() => f.to_string(),
+        _ => "download".to_string(),
+    }
+}
+
+/// Verifies the checksums of a downloaded or local file.
+pub fn verify_checksums(source: &FlatpakSource, file_path: &path::Path) -> Result<(), String> {
+    if let Some(expected_sha256) = &source.sha256 {
+        let sha256 = crate::utils::get_file_sha256(file_path)?;
+        if sha256 != expected_sha256.to_ascii_lowercase() {
+            return Err(format!(
+                "Wrong sha256 for {}: expected {}, got {}.",
+                file_path.display(),
+                expected_sha256,
+                sha256
+            ));
+        }
+    }
+    if let Some(expected_sha512) = &source.sha512 {
+        let sha512 = crate::utils::get_file_sha512(file_path)?;
+        if sha512 != expected_sha512.to_ascii_lowercase() {
+            return Err(format!(
+                "Wrong sha512 for {}: expected {}, got {}.",
+                file_path.display(),
+                expected_sha512,
+                sha512
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Downloads a URL to `file_path`. `file://` URLs are copied from the local filesystem.
+pub fn download_url(url: &str, file_path: &path::Path) -> Result<(), String> {
+    if let Some(local_path) = url.strip_prefix("file://") {
+        if let Err(e) = fs::copy(local_path, file_path) {
+            return Err(format!("Could not copy {}: {}.", local_path, e));
+        }
+        return Ok(());
+    }
+
+    let mut command = Command::new("curl");
+    command.args(["--fail", "--silent", "--show-error", "--location"]);
+    command.args(["--retry", "3"]);
+    command.arg("--output");
+    command.arg(file_path);
+    command.arg(url);
+    run_command(&mut command)
+}
+
+/// Gets a local copy of the file of an archive or file source. Files with a URL
+/// are taken from the download cache, and downloaded into the cache if needed.
+/// The checksums of the file are always verified.
+pub fn get_source_file(
+    source: &FlatpakSource,
+    config: &FetchConfig,
+) -> Result<path::PathBuf, String> {
+    if let Some(source_path) = &source.path {
+        let file_path = config.base_dir.join(source_path);
+        if !file_path.is_file() {
+            return Err(format!("{} is not a file.", file_path.display()));
+        }
+        verify_checksums(source, &file_path)?;
+        return Ok(file_path);
+    }
+
+    let url = match &source.url {
+        Some(u) => u,
+        None => return Err("There should be a url or a path in the source.".to_string()),
+    };
+    let cache_key = match crate::cache::get_cache_key(source)? {
+        Some(k) => k,
+        None => return Err(format!("Source {} has no sha256 or sha512 checksum.", url)),
+    };
+
+    create_dir(&config.cache_dir)?;
+    if let Some(cached_file_path) = crate::cache::get_cached_file(&config.cache_dir, &cache_key) {
+        if verify_checksums(source, &cached_file_path).is_ok() {
+            return Ok(cached_file_path);
+        }
+        // The cached file is corrupted, so we download it again.
+        let _ = fs::remove_file(&cached_file_path);
+    }
+
+    let mut urls = vec![url.to_string()];
+    urls.append(&mut source.get_mirror_urls());
+
+    let partial_file_path = crate::cache::get_partial_file_path(&config.cache_dir, &cache_key);
+    let mut errors: Vec<String> = vec![];
+    for url in urls {
+        match download_url(&url, &partial_file_path)
+            .and_then(|_| verify_checksums(source, &partial_file_path))
+        {
+            Ok(_) => {
+                return crate::cache::add_file(&config.cache_dir, &cache_key, &partial_file_path)
+            }
+            Err(e) => errors.push(e),
+        };
+    }
+    let _ = fs::remove_file(&partial_file_path);
+    Err(errors.join("\n"))
+}
+
+/// Moves the content of `from_dir` into `to_dir`, after stripping the first
+/// `strip_components` levels of directories.
+fn move_dir_content(
+    from_dir: &path::Path,
+    to_dir: &path::Path,
+    strip_components: i64,
+) -> Result<(), String> {
+    let mut dirs = vec![from_dir.to_path_buf()];
+    for _ in 0..strip_components {
+        let mut next_dirs = vec![];
+        for dir in dirs {
+            for entry_path in read_dir(&dir)? {
+                if entry_path.is_dir() {
+                    next_dirs.push(entry_path);
+                }
+            }
+        }
+        dirs = next_dirs;
+    }
+
+    create_dir(to_dir)?;
+    for dir in dirs {
+        for entry_path in read_dir(&dir)? {
+            let target_path = to_dir.join(entry_path.file_name().unwrap());
+            if target_path.is_dir() && entry_path.is_dir() {
+                move_dir_content(&entry_path, &target_path, 0)?;
+                continue;
+            }
+            if let Err(e) = fs::rename(&entry_path, &target_path) {
+                return Err(format!(
+                    "Could not move {} to {}: {}.",
+                    entry_path.display(),
+                    target_path.display(),
+                    e
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_dir(dir: &path::Path) -> Result<Vec<path::PathBuf>, String> {
+    let dir_entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(format!(
+                "Could not read directory {}: {}.",
+                dir.display(),
+                e
+            ))
+        }
+    };
+    let mut entry_paths = vec![];
+    for entry in dir_entries {
+        match entry {
+            Ok(e) => entry_paths.push(e.path()),
+            Err(e) => return Err(e.to_string()),
+        };
+    }
+    entry_paths.sort();
+    Ok(entry_paths)
+}
+
+/// Copies a directory recursively. The entries listed in `skip` (relative to `from_dir`)
+/// and the directories containing `to_dir` are not copied.
+fn copy_dir(
+    from_dir: &path::Path,
+    to_dir: &path::Path,
+    skip: &[path::PathBuf],
+) -> Result<(), String> {
+    create_dir(to_dir)?;
+    let canonical_to_dir = fs::canonicalize(to_dir).unwrap_or_else(|_| to_dir.to_path_buf());
+
+    for entry_path in read_dir(from_dir)? {
+        if skip.contains(&entry_path) {
+            continue;
+        }
+        let target_path = to_dir.join(entry_path.file_name().unwrap());
+        if entry_path.is_dir() {
+            // Do not copy the source directory into itself.
+            if let Ok(canonical_entry_path) = fs::canonicalize(&entry_path) {
+                if canonical_to_dir.starts_with(canonical_entry_path) {
+                    continue;
+                }
+            }
+            copy_dir(&entry_path, &target_path, skip)?;
+            continue;
+        }
+        if let Err(e) = fs::copy(&entry_path, &target_path) {
+            return Err(format!(
+                "Could not copy {} to {}: {}.",
+                entry_path.display(),
+                target_path.display(),
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn extract_archive(
+    source: &FlatpakSource,
+    archive_path: &path::Path,
+    dest_dir: &path::Path,
+    config: &FetchConfig,
+) -> Result<(), String> {
+    let archive_type = match &source.archive_type {
+        Some(t) => t.clone(),
+        None => {
+            let archive_name = match (&source.url, &source.path) {
+                (Some(url), _) => url.to_string(),
+                (None, Some(p)) => p.to_string(),
+                (None, None) => archive_path.to_string_lossy().to_string(),
+            };
+            match FlatpakArchiveType::from_path(&archive_name) {
+                Some(t) => t,
+                None => {
+                    return Err(format!(
+                        "Could not detect the archive type of {}. Use the archive-type field.",
+                        archive_name
+                    ))
+                }
+            }
+        }
+    };
+
+    let extract_dir = config.tmp_dir.join(EXTRACT_DIR_NAME);
+    remove_dir(&extract_dir)?;
+    create_dir(&extract_dir)?;
+
+    let mut command = match archive_type {
+        FlatpakArchiveType::Zip => {
+            let mut command = Command::new("unzip");
+            command
+                .arg("-q")
+                .arg(archive_path)
+                .arg("-d")
+                .arg(&extract_dir);
+            command
+        }
+        FlatpakArchiveType::SevenZip => {
+            let mut command = Command::new("7z");
+            command.arg("x").arg(archive_path);
+            command.arg(format!("-o{}", extract_dir.display()));
+            command
+        }
+        FlatpakArchiveType::Rpm => {
+            return Err("Extracting rpm archives is not supported yet.".to_string());
+        }
+        _ => {
+            // tar detects the compression method by itself.
+            let mut command = Command::new("tar");
+            command
+                .arg("-xf")
+                .arg(archive_path)
+                .arg("-C")
+                .arg(&extract_dir);
+            command
+        }
+    };
+    run_command(&mut command)?;
+
+    let strip_components = source.strip_components.unwrap_or(DEFAULT_STRIP_COMPONENTS);
+    move_dir_content(&extract_dir, dest_dir, strip_components)?;
+    remove_dir(&extract_dir)
+}
+
+fn apply_patch(
+    source: &FlatpakSource,
+    patch_path: &path::Path,
+    dest_dir: &path::Path,
+) -> Result<(), String> {
+    let strip_components = source.strip_components.unwrap_or(DEFAULT_STRIP_COMPONENTS);
+    let options = source.options.clone().unwrap_or_default();
+
+    let mut command = if source.use_git_am.unwrap_or(false) {
+        let mut command = Command::new("git");
+        command.arg("am");
+        command.arg(format!("-p{}", strip_components));
+        command.args(options);
+        command.arg(patch_path);
+        command
+    } else if source.use_git.unwrap_or(false) {
+        let mut command = Command::new("git");
+        command.arg("apply");
+        command.arg(format!("-p{}", strip_components));
+        command.args(options);
+        command.arg(patch_path);
+        command
+    } else {
+        let mut command = Command::new("patch");
+        command.arg(format!("-p{}", strip_components));
+        command.arg("--batch");
+        command.args(options);
+        command.arg("-i");
+        command.arg(patch_path);
+        command
+    };
+    command.current_dir(dest_dir);
+    run_command(&mut command)
+}
+
+fn fetch_git_source(
+    source: &FlatpakSource,
+    dest_dir: &path::Path,
+    config: &FetchConfig,
+) -> Result<(), String> {
+    let url = match (&source.url, &source.path) {
+        (Some(url), _) => url.to_string(),
+        (None, Some(p)) => p.to_string(),
+        (None, None) => {
+            return Err("There should be a url or a path in the git source.".to_string())
+        }
+    };
+    // Relative paths to local repositories are relative to the manifest.
+    let url = if url.contains("://") || url.contains('@') || path::Path::new(&url).is_absolute() {
+        url
+    } else {
+        config.base_dir.join(&url).to_string_lossy().to_string()
+    };
+
+    let clone_dir = config.tmp_dir.join(EXTRACT_DIR_NAME);
+    remove_dir(&clone_dir)?;
+    create_dir(&config.tmp_dir)?;
+
+    let mut command = Command::new("git");
+    command.arg("clone").arg("--quiet");
+    if !source.disable_submodules.unwrap_or(false) {
+        command.arg("--recurse-submodules");
+    }
+    command.arg(&url).arg(&clone_dir);
+    run_command(&mut command)?;
+
+    let revision = match (&source.commit, &source.tag, &source.branch) {
+        (_, Some(tag), _) => Some(tag.to_string()),
+        (Some(commit), None, _) => Some(commit.to_string()),
+        (None, None, Some(branch)) => Some(format!("origin/{}", branch)),
+        (None, None, None) => None,
+    };
+    if let Some(revision) = revision {
+        let mut command = Command::new("git");
+        command.args(["checkout", "--quiet", &revision]);
+        command.current_dir(&clone_dir);
+        run_command(&mut command)?;
+
+        if !source.disable_submodules.unwrap_or(false) {
+            let mut command = Command::new("git");
+            command.args(["submodule", "update", "--init", "--recursive", "--quiet"]);
+            command.current_dir(&clone_dir);
+            run_command(&mut command)?;
+        }
+    }
+
+    // When both a tag and a commit are given, the tag must point to the commit.
+    if let (Some(commit), Some(tag)) = (&source.commit, &source.tag) {
+        let output = match Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .current_dir(&clone_dir)
+            .output()
+        {
+            Ok(o) => o,
+            Err(e) => return Err(format!("Could not run git: {}.", e)),
+        };
+        let head_commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if &head_commit != commit {
+            return Err(format!(
+                "Tag {} of {} points to {}, not to commit {}.",
+                tag, url, head_commit, commit
+            ));
+        }
+    }
+
+    move_dir_content(&clone_dir, dest_dir, 0)?;
+    remove_dir(&clone_dir)
+}
+
+/// Fetches a single source and extracts it into the source directory.
+pub fn fetch_source(
+    source: &FlatpakSource,
+    source_dir: &path::Path,
+    config: &FetchConfig,
+) -> Result<(), String> {
+    let dest_dir = get_dest_dir(source, source_dir)?;
+    create_dir(&dest_dir)?;
+
+    let source_type = match source.get_type() {
+        Some(t) => t,
+        None => return Err("Sources without a type are not supported.".to_string()),
+    };
+
+    match source_type {
+        FlatpakSourceType::Archive => {
+            let archive_path = get_source_file(source, config)?;
+            extract_archive(source, &archive_path, &dest_dir, config)
+        }
+        FlatpakSourceType::File => {
+            let file_path = get_source_file(source, config)?;
+            let filename = match (&source.dest_filename, &source.url) {
+                (Some(f), _) => f.to_string(),
+                (None, Some(url)) => get_url_filename(url),
+                (None, None) => file_path.file_name().unwrap().to_string_lossy().to_string(),
+            };
+            let target_path = dest_dir.join(filename);
+            if let Err(e) = fs::copy(&file_path, &target_path) {
+                return Err(format!(
+                    "Could not copy {} to {}: {}.",
+                    file_path.display(),
+                    target_path.display(),
+                    e
+                ));
+            }
+            Ok(())
+        }
+        FlatpakSourceType::Dir => {
+            let dir_path = match &source.path {
+                Some(p) => config.base_dir.join(p),
+                None => return Err("There should be a path in the dir source.".to_string()),
+            };
+            if !dir_path.is_dir() {
+                return Err(format!("{} is not a directory.", dir_path.display()));
+            }
+            let skip: Vec<path::PathBuf> = source
+                .skip
+                .clone()
+                .unwrap_or_default()
+                .iter()
+                .map(|s| dir_path.join(s))
+                .collect();
+            copy_dir(&dir_path, &dest_dir, &skip)
+        }
+        FlatpakSourceType::Patch => {
+            let mut patch_paths: Vec<String> = vec![];
+            if let Some(p) = &source.path {
+                patch_paths.push(p.to_string());
+            }
+            if let Some(paths) = &source.paths {
+                patch_paths.extend(paths.iter().cloned());
+            }
+            for patch_path in patch_paths {
+                let patch_path = config.base_dir.join(patch_path);
+                let patch_path = match fs::canonicalize(&patch_path) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        return Err(format!("Invalid patch {}: {}.", patch_path.display(), e))
+                    }
+                };
+                apply_patch(source, &patch_path, &dest_dir)?;
+            }
+            Ok(())
+        }
+        FlatpakSourceType::Script => {
+            let filename = source
+                .dest_filename
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SCRIPT_FILENAME.to_string());
+            let script_path = dest_dir.join(filename);
+            let mut script_content = "#!/bin/sh\n".to_string();
+            for command in source.commands.clone().unwrap_or_default() {
+                script_content += &command;
+                script_content += "\n";
+            }
+            if let Err(e) = fs::write(&script_path, script_content) {
+                return Err(format!(
+                    "Could not write file {}: {}.",
+                    script_path.display(),
+                    e
+                ));
+            }
+            if let Err(e) = fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755)) {
+                return Err(format!(
+                    "Could not make {} executable: {}.",
+                    script_path.display(),
+                    e
+                ));
+            }
+            Ok(())
+        }
+        FlatpakSourceType::Shell => {
+            for command in source.commands.clone().unwrap_or_default() {
+                let mut shell_command = Command::new("/bin/sh");
+                shell_command.arg("-c").arg(&command);
+                shell_command.current_dir(&dest_dir);
+                if let Some(app_dir) = &config.sandbox_app_dir {
+                    shell_command = crate::install::get_sandboxed_command(
+                        &shell_command,
+                        app_dir,
+                        source_dir,
+                        &[],
+                    );
+                }
+                run_command(&mut shell_command)?;
+            }
+            Ok(())
+        }
+        FlatpakSourceType::Git => fetch_git_source(source, &dest_dir, config),
+        // The extra data is downloaded when the application is installed, not when it is built.
+        FlatpakSourceType::ExtraData => Ok(()),
+        FlatpakSourceType::Bazaar | FlatpakSourceType::Svn => Err(format!(
+            "Fetching {} sources is not supported yet.",
+            source_type.to_string()
+        )),
+    }
+}
+
+/// Gets the sources of a module, in order. The sources imported by path are loaded
+/// relative to `base_dir`.
+pub fn get_module_sources(
+    module: &FlatpakModule,
+    base_dir: &path::Path,
+) -> Result<Vec<FlatpakSource>, String> {
+    let mut sources: Vec<FlatpakSource> = vec![];
+    for source_item in &module.sources {
+        match source_item {
+            FlatpakSourceItem::Description(d) => sources.push(d.clone()),
+            FlatpakSourceItem::Path(p) => {
+                let source_path = base_dir.join(p);
+                sources.append(&mut FlatpakSource::load_from_file(
+                    source_path.to_string_lossy().to_string(),
+                )?);
+            }
+        };
+    }
+    Ok(sources)
+}
+
+/// Fetches all the sources of a module, in order, into a clean source directory.
+pub fn fetch_module_sources(
+    module: &FlatpakModule,
+    source_dir: &path::Path,
+    config: &FetchConfig,
+) -> Result<(), String> {
+    remove_dir(source_dir)?;
+    create_dir(source_dir)?;
+
+    let arch = get_current_arch();
+    for source in get_module_sources(module, &config.base_dir)? {
+        if !source_applies_to_arch(&source, arch) {
+            continue;
+        }
+        if let Err(e) = fetch_source(&source, source_dir, config) {
+            return Err(format!(
+                "Could not fetch {} source for module {}: {}",
+                source.get_type_name(),
+                module.name,
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{get_test_dir, write_test_file};
+
+    fn get_config(test_dir: &path::Path) -> FetchConfig {
+        FetchConfig {
+            base_dir: test_dir.to_path_buf(),
+            tmp_dir: test_dir.join("tmp"),
+            cache_dir: test_dir.join("cache"),
+            sandbox_app_dir: None,
+        }
+    }
+
+    fn get_module(sources: Vec<FlatpakSource>) -> FlatpakModule {
+        FlatpakModule {
+            name: "module".to_string(),
+            sources: sources
+                .into_iter()
+                .map(FlatpakSourceItem::Description)
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn get_source(source_type: FlatpakSourceType) -> FlatpakSource {
+        FlatpakSource {
+            r#type: Some(source_type),
+            ..Default::default()
+        }
+    }
+
+    fn git(dir: &path::Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args([
+                "-c",
+                "user.name=fpcli",
+                "-c",
+                "user.email=fpcli@example.org",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}: {:?}", args, output);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// Creates a tar archive of `project-1.0/`, and gets its archive source.
+    fn get_archive_source(test_dir: &path::Path) -> FlatpakSource {
+        write_test_file(&test_dir.join("project-1.0/README"), "project\n");
+        write_test_file(&test_dir.join("project-1.0/src/main.c"), "int main;\n");
+        let output = Command::new("tar")
+            .args(["-czf", "project-1.0.tar.gz", "project-1.0"])
+            .current_dir(test_dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        fs::remove_dir_all(test_dir.join("project-1.0")).unwrap();
+
+        let archive_path = test_dir.join("project-1.0.tar.gz");
+        let mut source = get_source(FlatpakSourceType::Archive);
+        source.url = Some(format!("file://{}", archive_path.display()));
+        source.sha256 = Some(crate::utils::get_file_sha256(&archive_path).unwrap());
+        source
+    }
+
+    #[test]
+    fn fetch_archive_from_file_url() {
+        let test_dir = get_test_dir("fetch_archive_from_file_url");
+        let source = get_archive_source(&test_dir);
+        let source_dir = test_dir.join("build");
+        fetch_module_sources(
+            &get_module(vec![source]),
+            &source_dir,
+            &get_config(&test_dir),
+        )
+        .unwrap();
+
+        // The first directory of the archive is stripped by default.
+        assert_eq!(
+            fs::read_to_string(source_dir.join("README")).unwrap(),
+            "project\n"
+        );
+        assert!(source_dir.join("src/main.c").is_file());
+        // The archive is kept in the cache.
+        let sha256 = crate::utils::get_file_sha256(&test_dir.join("project-1.0.tar.gz")).unwrap();
+        assert!(test_dir.join("cache").join(sha256).is_file());
+    }
+
+    #[test]
+    fn fetch_archive_with_dest_and_strip_components() {
+        let test_dir = get_test_dir("fetch_archive_with_dest_and_strip_components");
+        let mut source = get_archive_source(&test_dir);
+        source.dest = Some("vendor".to_string());
+        source.strip_components = Some(0);
+        let source_dir = test_dir.join("build");
+        fetch_module_sources(
+            &get_module(vec![source]),
+            &source_dir,
+            &get_config(&test_dir),
+        )
+        .unwrap();
+        assert!(source_dir.join("vendor/project-1.0/README").is_file());
+    }
+
+    #[test]
+    fn fetch_archive_with_wrong_checksum() {
+        let test_dir = get_test_dir("fetch_archive_with_wrong_checksum");
+        let mut source = get_archive_source(&test_dir);
+        source.sha256 = Some("0".repeat(64));
+        let source_dir = test_dir.join("build");
+        let error = fetch_module_sources(
+            &get_module(vec![source]),
+            &source_dir,
+            &get_config(&test_dir),
+        )
+        .unwrap_err();
+        assert!(error.contains("Wrong sha256"), "{}", error);
+        assert!(!source_dir.join("README").exists());
+    }
+
+    #[test]
+    fn fetch_dest_outside_of_the_source_dir() {
+        let test_dir = get_test_dir("fetch_dest_outside_of_the_source_dir");
+        let mut source = get_archive_source(&test_dir);
+        source.dest = Some("../outside".to_string());
+        let error = fetch_module_sources(
+            &get_module(vec![source]),
+            &test_dir.join("build"),
+            &get_config(&test_dir),
+        )
+        .unwrap_err();
+        assert!(error.contains("Invalid dest"), "{}", error);
+        assert!(!test_dir.join("outside").exists());
+    }
+
+    #[test]
+    fn fetch_file_and_dir_sources() {
+        let test_dir = get_test_dir("fetch_file_and_dir_sources");
+        write_test_file(&test_dir.join("data.txt"), "data\n");
+        write_test_file(&test_dir.join("project/main.c"), "int main;\n");
+        write_test_file(&test_dir.join("project/skipped.txt"), "skipped\n");
+
+        let mut file_source = get_source(FlatpakSourceType::File);
+        file_source.url = Some(format!("file://{}", test_dir.join("data.txt").display()));
+        file_source.sha256 =
+            Some(crate::utils::get_file_sha256(&test_dir.join("data.txt")).unwrap());
+        file_source.dest_filename = Some("renamed.txt".to_string());
+        let mut dir_source = get_source(FlatpakSourceType::Dir);
+        dir_source.path = Some("project".to_string());
+        dir_source.skip = Some(vec!["skipped.txt".to_string()]);
+
+        let source_dir = test_dir.join("build");
+        let module = get_module(vec![file_source, dir_source]);
+        fetch_module_sources(&module, &source_dir, &get_config(&test_dir)).unwrap();
+        assert_eq!(
+            fs::read_to_string(source_dir.join("renamed.txt")).unwrap(),
+            "data\n"
+        );
+        assert!(source_dir.join("main.c").is_file());
+        assert!(!source_dir.join("skipped.txt").exists());
+    }
+
+    #[test]
+    fn fetch_patches_in_order() {
+        let test_dir = get_test_dir("fetch_patches_in_order");
+        write_test_file(&test_dir.join("project/file.txt"), "one\n");
+        write_test_file(
+            &test_dir.join("first.patch"),
+            "--- a/file.txt\n+++ b/file.txt\n@@ -1 +1 @@\n-one\n+two\n",
+        );
+        write_test_file(
+            &test_dir.join("second.patch"),
+            "--- a/file.txt\n+++ b/file.txt\n@@ -1 +1 @@\n-two\n+three\n",
+        );
+
+        let mut dir_source = get_source(FlatpakSourceType::Dir);
+        dir_source.path = Some("project".to_string());
+        let mut first_patch = get_source(FlatpakSourceType::Patch);
+        first_patch.path = Some("first.patch".to_string());
+        let mut second_patch = get_source(FlatpakSourceType::Patch);
+        second_patch.paths = Some(vec!["second.patch".to_string()]);
+
+        let source_dir = test_dir.join("build");
+        let module = get_module(vec![dir_source, first_patch, second_patch]);
+        fetch_module_sources(&module, &source_dir, &get_config(&test_dir)).unwrap();
+        assert_eq!(
+            fs::read_to_string(source_dir.join("file.txt")).unwrap(),
+            "three\n"
+        );
+    }
+
+    /// Creates a local git repository with a tagged first commit and a second
+    /// commit, and gets the hash of the first commit.
+    fn create_repository(repo_dir: &path::Path) -> String {
+        fs::create_dir_all(repo_dir).unwrap();
+        git(repo_dir, &["init", "--quiet", "--initial-branch=main"]);
+        write_test_file(&repo_dir.join("version.txt"), "1\n");
+        git(repo_dir, &["add", "."]);
+        git(repo_dir, &["commit", "--quiet", "-m", "First"]);
+        git(repo_dir, &["tag", "v1"]);
+        let first_commit = git(repo_dir, &["rev-parse", "HEAD"]);
+        write_test_file(&repo_dir.join("version.txt"), "2\n");
+        git(repo_dir, &["commit", "--quiet", "-am", "Second"]);
+        first_commit
+    }
+
+    fn fetch_version(test_dir: &path::Path, source: FlatpakSource) -> Result<String, String> {
+        let source_dir = test_dir.join("build");
+        fetch_module_sources(
+            &get_module(vec![source]),
+            &source_dir,
+            &get_config(test_dir),
+        )?;
+        Ok(fs::read_to_string(source_dir.join("version.txt")).unwrap())
+    }
+
+    #[test]
+    fn fetch_local_git_repository() {
+        let test_dir = get_test_dir("fetch_local_git_repository");
+        let first_commit = create_repository(&test_dir.join("repo"));
+
+        let mut source = get_source(FlatpakSourceType::Git);
+        source.path = Some("repo".to_string());
+        assert_eq!(fetch_version(&test_dir, source.clone()).unwrap(), "2\n");
+
+        let mut tag_source = source.clone();
+        tag_source.tag = Some("v1".to_string());
+        assert_eq!(fetch_version(&test_dir, tag_source.clone()).unwrap(), "1\n");
+
+        let mut commit_source = source.clone();
+        commit_source.commit = Some(first_commit.clone());
+        assert_eq!(fetch_version(&test_dir, commit_source).unwrap(), "1\n");
+
+        let mut branch_source = get_source(FlatpakSourceType::Git);
+        branch_source.url = Some(format!("file://{}", test_dir.join("repo").display()));
+        branch_source.branch = Some("main".to_string());
+        assert_eq!(fetch_version(&test_dir, branch_source).unwrap(), "2\n");
+
+        // The tag has to point to the commit.
+        let second_commit = git(&test_dir.join("repo"), &["rev-parse", "HEAD"]);
+        tag_source.commit = Some(second_commit);
+        let error = fetch_version(&test_dir, tag_source).unwrap_err();
+        assert!(error.contains("Tag v1"), "{}", error);
+    }
+}
@@ -0,0 +1,336 @@
+//! Classifies the git URLs, and reads the default branch of local clones.
+use std::path;
+use std::process::Command;
+
+use flatpak_rs::archive::FlatpakArchiveType;
+
+/// The forges hosting git repositories at URLs without the .git suffix, like
+/// `https://github.com/owner/repo`.
+pub const KNOWN_FORGES: [&str; 10] = [
+    "github.com",
+    "gitlab.com",
+    "codeberg.org",
+    "bitbucket.org",
+    "git.sr.ht",
+    "gitlab.gnome.org",
+    "invent.kde.org",
+    "gitlab.freedesktop.org",
+    "salsa.debian.org",
+    "framagit.org",
+];
+
+/// The schemes only used for git repositories.
+pub const GIT_SCHEMES: [&str; 4] = ["git://", "ssh://", "git+ssh://", "ssh+git://"];
+
+/// A git repository URL.
+#[derive(Debug, PartialEq)]
+pub struct GitUrl {
+    /// The host of the repository, empty for the local repositories.
+    pub host: String,
+    /// The path of the repository on the host, without the .git suffix.
+    pub path: String,
+}
+impl GitUrl {
+    /// Gets the name of the project, which is the last component of the path.
+    pub fn get_project_name(&self) -> Option<String> {
+        match self.path.rsplit('/').next() {
+            Some(n) if !n.is_empty() => Some(n.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// The ref of a git source to build. Without a ref, flatpak-builder builds the
+/// default branch of the repository.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GitRef {
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub commit: Option<String>,
+}
+impl GitRef {
+    pub fn is_empty(&self) -> bool {
+        self.branch.is_none() && self.tag.is_none() && self.commit.is_none()
+    }
+}
+
+/// Parses a git URL. Returns None when the URL is not a git URL. The https
+/// URLs are git URLs when they end with .git, or when they point to a
+/// repository of a known forge.
+pub fn parse_git_url(url: &str) -> Option<GitUrl> {
+    let url = url.trim().trim_end_matches('/');
+
+    if let Some(repo_path) = url.strip_prefix("file://") {
+        return Some(GitUrl {
+            host: "".to_string(),
+            path: strip_git_suffix(repo_path).to_string(),
+        });
+    }
+
+    let (host, repo_path, is_git) =
+        if let Some(scheme) = GIT_SCHEMES.iter().find(|s| url.starts_with(*s)) {
+            let (host, repo_path) = url[scheme.len()..].split_once('/')?;
+            (host, repo_path, true)
+        } else if let Some((_, u)) = url.split_once("://") {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                return None;
+            }
+            let (host, repo_path) = u.split_once('/')?;
+            (host, repo_path, repo_path.ends_with(".git"))
+        } else {
+            // The scp-like syntax, like git@github.com:owner/repo.git.
+            let (host, repo_path) = url.split_once(':')?;
+            if host.contains('/') || !(host.contains('@') || host.contains('.')) {
+                return None;
+            }
+            (host, repo_path, true)
+        };
+
+    // Removing the user and the port.
+    let host = host.rsplit('@').next().unwrap_or(host);
+    let host = host.split(':').next().unwrap_or(host).to_lowercase();
+    let repo_path = repo_path.split(['?', '#']).next().unwrap_or(repo_path);
+    let repo_path = repo_path.trim_start_matches('/');
+    if host.is_empty() || repo_path.is_empty() {
+        return None;
+    }
+
+    if !is_git && !is_forge_repository(&host, repo_path) {
+        return None;
+    }
+    Some(GitUrl {
+        host,
+        path: strip_git_suffix(repo_path).to_string(),
+    })
+}
+
+/// Checks if a path on a known forge points to a repository, and not to a file
+/// or a page of the repository.
+fn is_forge_repository(host: &str, repo_path: &str) -> bool {
+    if !KNOWN_FORGES.contains(&host) {
+        return false;
+    }
+    let components: Vec<&str> = repo_path.split('/').collect();
+    if components.len() < 2 || components.contains(&"-") {
+        return false;
+    }
+    // Only GitLab has nested groups, like GNOME/World/app.
+    if components.len() > 2 && !host.contains("gitlab") && host != "invent.kde.org" {
+        return false;
+    }
+    FlatpakArchiveType::from_path(repo_path).is_none()
+}
+
+fn strip_git_suffix(repo_path: &str) -> &str {
+    repo_path.strip_suffix(".git").unwrap_or(repo_path)
+}
+
+/// Gets the local directory of the repository at a URL, for the file:// URLs.
+pub fn get_local_repo_dir(url: &str) -> Option<path::PathBuf> {
+    let repo_dir = path::PathBuf::from(url.strip_prefix("file://")?);
+    if repo_dir.is_dir() {
+        return Some(repo_dir);
+    }
+    None
+}
+
+/// Gets the default branch of the origin remote of a local clone. The branch
+/// checked out in the clone is not used, since it can be any branch.
+pub fn get_default_branch(repo_dir: &path::Path) -> Option<String> {
+    let remote_head = run_git(
+        repo_dir,
+        &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    )?;
+    remote_head.strip_prefix("origin/").map(|b| b.to_string())
+}
+
+/// Gets the ref to build from a git URL. When no ref is given, the default branch
+/// is read from the local repository of the URL, or from `checkout_dir`, which is
+/// a local clone of the repository. Otherwise the ref stays empty, so the default
+/// branch of the remote is built.
+pub fn get_git_ref(git_ref: &GitRef, url: &str, checkout_dir: Option<&path::Path>) -> GitRef {
+    if !git_ref.is_empty() {
+        return git_ref.clone();
+    }
+    let repo_dir = match get_local_repo_dir(url) {
+        Some(d) => d,
+        None => match checkout_dir {
+            Some(d) => d.to_path_buf(),
+            None => return GitRef::default(),
+        },
+    };
+    GitRef {
+        branch: get_default_branch(&repo_dir),
+        ..Default::default()
+    }
+}
+
+fn run_git(repo_dir: &path::Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo_dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.is_empty() {
+        return None;
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_git_url(host: &str, path: &str) -> Option<GitUrl> {
+        Some(GitUrl {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    #[test]
+    fn parse_scp_like_urls() {
+        assert_eq!(
+            parse_git_url("git@github.com:louib/flatpak-rs.git"),
+            get_git_url("github.com", "louib/flatpak-rs")
+        );
+        assert_eq!(
+            parse_git_url("example.org:project"),
+            get_git_url("example.org", "project")
+        );
+        assert_eq!(parse_git_url("C:/Users/project"), None);
+    }
+
+    #[test]
+    fn parse_git_scheme_urls() {
+        assert_eq!(
+            parse_git_url("ssh://git@gitlab.com:2222/group/subgroup/project.git"),
+            get_git_url("gitlab.com", "group/subgroup/project")
+        );
+        assert_eq!(
+            parse_git_url("git://git.kernel.org/pub/scm/git/git.git"),
+            get_git_url("git.kernel.org", "pub/scm/git/git")
+        );
+        assert_eq!(
+            parse_git_url("git+ssh://git@example.org/project"),
+            get_git_url("example.org", "project")
+        );
+        assert_eq!(
+            parse_git_url("file:///srv/git/project.git"),
+            get_git_url("", "/srv/git/project")
+        );
+    }
+
+    #[test]
+    fn parse_forge_urls_without_the_git_suffix() {
+        assert_eq!(
+            parse_git_url("https://gitlab.gnome.org/GNOME/gtk"),
+            get_git_url("gitlab.gnome.org", "GNOME/gtk")
+        );
+        assert_eq!(
+            parse_git_url("https://GitHub.com/louib/fpcli/"),
+            get_git_url("github.com", "louib/fpcli")
+        );
+        assert_eq!(
+            parse_git_url("https://gitlab.gnome.org/World/design/contrast"),
+            get_git_url("gitlab.gnome.org", "World/design/contrast")
+        );
+        // Only GitLab has nested groups.
+        assert_eq!(parse_git_url("https://github.com/louib/fpcli/issues"), None);
+        assert_eq!(parse_git_url("https://example.org/louib/fpcli"), None);
+    }
+
+    #[test]
+    fn parse_names_containing_git() {
+        assert_eq!(
+            parse_git_url("https://github.com/louib/louib.github.io"),
+            get_git_url("github.com", "louib/louib.github.io")
+        );
+        assert_eq!(
+            parse_git_url("https://example.org/owner/project.github.io.git"),
+            get_git_url("example.org", "owner/project.github.io")
+        );
+        assert_eq!(
+            parse_git_url("https://example.org/owner/project.gitlab"),
+            None
+        );
+        assert_eq!(
+            get_git_url("github.com", "louib/louib.github.io")
+                .unwrap()
+                .get_project_name(),
+            Some("louib.github.io".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_archive_urls_on_forges() {
+        for url in [
+            "https://github.com/louib/fpcli/archive/v1.0.tar.gz",
+            "https://github.com/louib/fpcli/archive/refs/tags/v1.0.zip",
+            "https://github.com/louib/fpcli/releases/download/v1.0/fpcli-1.0.tar.xz",
+            "https://gitlab.com/group/project/-/archive/v1.0/project-v1.0.tar.gz",
+            "https://gitlab.gnome.org/GNOME/gtk/-/archive/4.14.0/gtk-4.14.0.tar.bz2",
+            "https://gitlab.com/group/project.tar.gz",
+            "https://example.org/releases/project-1.0.tar.gz",
+        ] {
+            assert_eq!(parse_git_url(url), None, "{}", url);
+        }
+    }
+
+    fn git(dir: &path::Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=fpcli",
+                "-c",
+                "user.email=fpcli@example.org",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn default_branch_of_the_origin_remote() {
+        let test_dir = crate::utils::get_test_dir("default_branch_of_the_origin_remote");
+        let origin_dir = test_dir.join("origin");
+        std::fs::create_dir_all(&origin_dir).unwrap();
+        git(
+            &origin_dir,
+            &["init", "--quiet", "--initial-branch", "trunk"],
+        );
+        git(
+            &origin_dir,
+            &["commit", "--quiet", "--allow-empty", "-m", "Initial"],
+        );
+        // A repository without an origin remote has no known default branch.
+        assert_eq!(get_default_branch(&origin_dir), None);
+
+        git(&test_dir, &["clone", "--quiet", "origin", "clone"]);
+        let clone_dir = test_dir.join("clone");
+        git(&clone_dir, &["checkout", "--quiet", "-b", "feature"]);
+        assert_eq!(get_default_branch(&clone_dir), Some("trunk".to_string()));
+
+        let url = format!("file://{}", origin_dir.display());
+        assert_eq!(
+            get_git_ref(&GitRef::default(), &url, None),
+            GitRef::default()
+        );
+        assert_eq!(
+            get_git_ref(
+                &GitRef::default(),
+                "https://example.org/origin.git",
+                Some(&clone_dir)
+            )
+            .branch,
+            Some("trunk".to_string())
+        );
+    }
+}
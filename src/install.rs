@@ -0,0 +1,617 @@
+//! Installation of the modules of a Flatpak manifest.
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::{
+    FlatpakBuildOptions, FlatpakBuildOptionsEnv, FlatpakModule, FlatpakModuleItem,
+};
+
+use crate::fetch::FetchConfig;
+
+/// The directory where fpcli keeps its build state, relative to the current directory.
+pub const DEFAULT_STATE_DIR: &str = ".fpcli";
+/// The install prefix of the modules, inside the sandbox.
+pub const SANDBOX_PREFIX: &str = "/app";
+/// The default install prefix of the modules, when installing on the host.
+pub const DEFAULT_HOST_PREFIX: &str = "/usr/local";
+/// The name of the build directory for out-of-tree builds, inside the module build directory.
+pub const OUT_OF_TREE_BUILD_DIR: &str = "_flatpak_build";
+
+pub const FLATPAK_COMMAND: &str = "flatpak";
+
+/// The search paths inside the build sandbox, before the build options are applied.
+pub const SANDBOX_PATH: &str = "/app/bin:/usr/bin";
+pub const SANDBOX_LD_LIBRARY_PATH: &str = "/app/lib";
+pub const SANDBOX_PKG_CONFIG_PATH: &str =
+    "/app/lib/pkgconfig:/app/share/pkgconfig:/usr/lib/pkgconfig:/usr/share/pkgconfig";
+
+/// The Flatpak environment in which the modules are built.
+pub struct SandboxConfig {
+    pub app_id: String,
+    pub sdk: String,
+    pub runtime: String,
+    pub runtime_version: String,
+}
+impl SandboxConfig {
+    pub fn from_application(application: &FlatpakApplication) -> SandboxConfig {
+        SandboxConfig {
+            app_id: application.get_id(),
+            sdk: application.sdk.to_string(),
+            runtime: application.runtime.to_string(),
+            runtime_version: application.runtime_version.to_string(),
+        }
+    }
+}
+
+/// Gets the modules in the order in which they have to be built. Nested modules are
+/// dependencies of their parent module, so they are built first.
+/// The module items must already be resolved.
+pub fn get_build_order(module_items: &[FlatpakModuleItem]) -> Result<Vec<FlatpakModule>, String> {
+    let mut modules: Vec<FlatpakModule> = vec![];
+    for module_item in module_items {
+        let module = match module_item {
+            FlatpakModuleItem::Description(m) => m,
+            FlatpakModuleItem::Path(p) => {
+                return Err(format!("Module {} was not resolved.", p));
+            }
+        };
+        if module.disabled.unwrap_or(false) {
+            continue;
+        }
+        modules.append(&mut get_build_order(&module.modules)?);
+        modules.push(module.clone());
+    }
+    Ok(modules)
+}
+
+/// Gets the absolute path of the state directory. The directory is only created
+/// when `create` is true.
+pub fn get_state_dir(state_dir: &str, create: bool) -> Result<path::PathBuf, String> {
+    if create {
+        if let Err(e) = fs::create_dir_all(state_dir) {
+            return Err(format!("Could not create directory {}: {}.", state_dir, e));
+        }
+    }
+    if path::Path::new(state_dir).exists() {
+        return match fs::canonicalize(state_dir) {
+            Ok(d) => Ok(d),
+            Err(e) => Err(format!("Invalid state directory {}: {}.", state_dir, e)),
+        };
+    }
+    match std::env::current_dir() {
+        Ok(d) => Ok(d.join(state_dir)),
+        Err(e) => Err(format!("Invalid state directory {}: {}.", state_dir, e)),
+    }
+}
+
+pub fn get_app_dir(state_dir: &path::Path) -> path::PathBuf {
+    state_dir.join("app")
+}
+
+/// Checks that the name of a module can be used as a file name in the state directory,
+/// so that the build directory and the log file of the module stay inside it.
+pub fn check_module_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name.contains('/') || name.contains("..") {
+        return Err(format!(
+            "Invalid module name {:?}: it must be a valid file name.",
+            name
+        ));
+    }
+    Ok(())
+}
+
+pub fn get_module_build_dir(
+    state_dir: &path::Path,
+    module: &FlatpakModule,
+) -> Result<path::PathBuf, String> {
+    check_module_name(&module.name)?;
+    Ok(state_dir.join("build").join(&module.name))
+}
+
+pub fn get_tmp_dir(state_dir: &path::Path) -> path::PathBuf {
+    state_dir.join("tmp")
+}
+
+pub fn get_module_log_path(
+    state_dir: &path::Path,
+    module: &FlatpakModule,
+) -> Result<path::PathBuf, String> {
+    check_module_name(&module.name)?;
+    Ok(state_dir.join("logs").join(format!("{}.log", module.name)))
+}
+
+fn get_num_cpus() -> i64 {
+    match thread::available_parallelism() {
+        Ok(n) => n.get() as i64,
+        Err(_) => 1,
+    }
+}
+
+/// Gets the build commands of a module, to be executed from `build_dir`.
+pub fn get_module_commands(
+    module: &FlatpakModule,
+    build_dir: &path::Path,
+    prefix: &str,
+) -> Result<Vec<Command>, String> {
+    if module.buildsystem == Some(FlatpakBuildSystem::QMake) {
+        return Err(format!(
+            "Module {} uses the qmake build system, which is not supported yet.",
+            module.name
+        ));
+    }
+
+    let out_of_tree_build_path = build_dir.join(OUT_OF_TREE_BUILD_DIR);
+    let (root_path, build_path) = match (build_dir.to_str(), out_of_tree_build_path.to_str()) {
+        (Some(r), Some(b)) => (r, b),
+        _ => {
+            return Err(format!(
+                "Invalid build directory {}: the path is not valid UTF-8.",
+                build_dir.display()
+            ))
+        }
+    };
+
+    let args: Vec<OsString> = vec![];
+    Ok(module.get_commands(
+        args,
+        false,
+        root_path,
+        build_path,
+        Some(prefix),
+        get_num_cpus(),
+    ))
+}
+
+/// Wraps a module build command so that it is executed inside the `flatpak build`
+/// sandbox rooted at `app_dir`, with the given environment variables.
+pub fn get_sandboxed_command(
+    command: &Command,
+    app_dir: &path::Path,
+    build_dir: &path::Path,
+    build_environment: &[(String, String)],
+) -> Command {
+    let command_dir = match command.get_current_dir() {
+        Some(d) => d.to_path_buf(),
+        None => build_dir.to_path_buf(),
+    };
+
+    let mut sandboxed_command = Command::new(FLATPAK_COMMAND);
+    sandboxed_command.arg("build");
+    sandboxed_command.arg("--die-with-parent");
+    sandboxed_command.arg(format!("--filesystem={}", build_dir.display()));
+    sandboxed_command.arg(format!("--build-dir={}", command_dir.display()));
+    for (name, value) in build_environment {
+        sandboxed_command.arg(format!("--env={}={}", name, value));
+    }
+    sandboxed_command.arg(app_dir);
+    sandboxed_command.arg(command.get_program());
+    sandboxed_command.args(command.get_args());
+    sandboxed_command.current_dir(build_dir);
+    sandboxed_command
+}
+
+/// Quotes a word for a POSIX shell, when it contains special characters.
+pub fn shell_quote(word: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !word.is_empty() && word.chars().all(is_safe) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Prints a command the way it would be typed in a shell.
+pub fn command_to_string(command: &Command) -> String {
+    let mut command_string = shell_quote(&command.get_program().to_string_lossy());
+    for arg in command.get_args() {
+        command_string += " ";
+        command_string += &shell_quote(&arg.to_string_lossy());
+    }
+    command_string
+}
+
+/// Prints a command as a shell command line that can be executed as is, changing
+/// to its working directory and setting its environment variables first.
+pub fn command_to_shell_line(command: &Command) -> String {
+    let mut shell_line = String::new();
+    if let Some(dir) = command.get_current_dir() {
+        shell_line += &format!("cd {} && ", shell_quote(&dir.to_string_lossy()));
+    }
+    for (name, value) in command.get_envs() {
+        if let Some(value) = value {
+            shell_line += &format!(
+                "{}={} ",
+                name.to_string_lossy(),
+                shell_quote(&value.to_string_lossy())
+            );
+        }
+    }
+    shell_line + &command_to_string(command)
+}
+
+/// Initializes the application directory used as the sandbox for the builds,
+/// unless it was already initialized by a previous install.
+pub fn init_sandbox(config: &SandboxConfig, app_dir: &path::Path) -> Result<(), String> {
+    if app_dir.join("metadata").is_file() {
+        return Ok(());
+    }
+
+    if let Err(e) = fs::create_dir_all(app_dir) {
+        return Err(format!(
+            "Could not create directory {}: {}.",
+            app_dir.display(),
+            e
+        ));
+    }
+
+    let mut command = Command::new(FLATPAK_COMMAND);
+    command.arg("build-init");
+    command.arg(app_dir);
+    command.arg(&config.app_id);
+    command.arg(&config.sdk);
+    command.arg(&config.runtime);
+    command.arg(&config.runtime_version);
+
+    let status = match command.status() {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Could not run {}: {}.", FLATPAK_COMMAND, e)),
+    };
+    if !status.success() {
+        return Err(format!(
+            "Could not initialize the build sandbox with {} {}: {}.",
+            config.sdk, config.runtime_version, status
+        ));
+    }
+    Ok(())
+}
+
+/// Runs a command, streaming its output line by line prefixed with the module name,
+/// and copying it to the module log file.
+pub fn run_module_command(
+    command: &mut Command,
+    module_name: &str,
+    log_file: &Arc<Mutex<fs::File>>,
+) -> Result<(), String> {
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    let mut child = match command.spawn() {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(format!(
+                "Could not run {}: {}.",
+                command.get_program().to_string_lossy(),
+                e
+            ))
+        }
+    };
+
+    let mut output_threads = vec![];
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let outputs: Vec<(Box<dyn std::io::Read + Send>, bool)> =
+        vec![(Box::new(stdout), false), (Box::new(stderr), true)];
+    for (output, is_stderr) in outputs {
+        let module_name = module_name.to_string();
+        let log_file = log_file.clone();
+        output_threads.push(thread::spawn(move || {
+            for line in BufReader::new(output).lines() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(_) => break,
+                };
+                if is_stderr {
+                    eprintln!("[{}] {}", module_name, line);
+                } else {
+                    println!("[{}] {}", module_name, line);
+                }
+                let _ = writeln!(log_file.lock().unwrap(), "{}", line);
+            }
+        }));
+    }
+    for output_thread in output_threads {
+        let _ = output_thread.join();
+    }
+
+    let status = match child.wait() {
+        Ok(s) => s,
+        Err(e) => return Err(e.to_string()),
+    };
+    if !status.success() {
+        return Err(format!(
+            "`{}` failed: {}",
+            command_to_string(command),
+            status
+        ));
+    }
+    Ok(())
+}
+
+/// Creates the build directory of a module and its log file.
+fn create_module_dirs(
+    build_dir: &path::Path,
+    log_path: &path::Path,
+) -> Result<Arc<Mutex<fs::File>>, String> {
+    for dir in [build_dir, log_path.parent().unwrap()] {
+        if let Err(e) = fs::create_dir_all(dir) {
+            return Err(format!(
+                "Could not create directory {}: {}.",
+                dir.display(),
+                e
+            ));
+        }
+    }
+
+    match fs::File::create(log_path) {
+        Ok(f) => Ok(Arc::new(Mutex::new(f))),
+        Err(e) => Err(format!(
+            "Could not create log file {}: {}.",
+            log_path.display(),
+            e
+        )),
+    }
+}
+
+/// Builds and installs a module inside the build sandbox.
+pub fn install_module_in_sandbox(
+    module: &FlatpakModule,
+    build_options: &[&FlatpakBuildOptions],
+    state_dir: &path::Path,
+    fetch_config: &FetchConfig,
+) -> Result<(), String> {
+    let app_dir = get_app_dir(state_dir);
+    let build_dir = get_module_build_dir(state_dir, module)?;
+    let log_path = get_module_log_path(state_dir, module)?;
+    let log_file = create_module_dirs(&build_dir, &log_path)?;
+    crate::fetch::fetch_module_sources(module, &build_dir, fetch_config)?;
+
+    for mut command in get_sandbox_commands(module, build_options, &build_dir, &app_dir)? {
+        if let Err(e) = run_module_command(&mut command, &module.name, &log_file) {
+            return Err(format!(
+                "Failed to install module {} (see {} for the full logs): {}",
+                module.name,
+                log_path.display(),
+                e
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn add_flags(flags: &mut Option<String>, new_flags: &str, is_override: Option<bool>) {
+    if is_override.unwrap_or(false) {
+        *flags = None;
+    }
+    if new_flags.is_empty() {
+        return;
+    }
+    *flags = match flags {
+        Some(f) => Some(format!("{} {}", f, new_flags)),
+        None => Some(new_flags.to_string()),
+    };
+}
+
+fn add_to_path_list(path_list: &mut Option<String>, prepended: &str, appended: &str) {
+    if !prepended.is_empty() {
+        *path_list = match path_list {
+            Some(p) if !p.is_empty() => Some(format!("{}:{}", prepended, p)),
+            _ => Some(prepended.to_string()),
+        };
+    }
+    if !appended.is_empty() {
+        *path_list = match path_list {
+            Some(p) if !p.is_empty() => Some(format!("{}:{}", p, appended)),
+            _ => Some(appended.to_string()),
+        };
+    }
+}
+
+/// Gets the environment variables defined by a list of build options, applied in order
+/// on top of the current environment. Later build options extend or override the
+/// previous ones, the same way the build options of a module extend the build options
+/// of the application.
+pub fn get_build_environment(build_options: &[&FlatpakBuildOptions]) -> Vec<(String, String)> {
+    compute_build_environment(
+        build_options,
+        std::env::var("PATH").ok(),
+        std::env::var("LD_LIBRARY_PATH").ok(),
+        std::env::var("PKG_CONFIG_PATH").ok(),
+    )
+}
+
+/// Gets the environment variables defined by a list of build options, applied on top
+/// of the environment of the build sandbox. The search paths are only included when
+/// the build options change them.
+pub fn get_sandbox_environment(build_options: &[&FlatpakBuildOptions]) -> Vec<(String, String)> {
+    let defaults = [
+        ("PATH", SANDBOX_PATH),
+        ("LD_LIBRARY_PATH", SANDBOX_LD_LIBRARY_PATH),
+        ("PKG_CONFIG_PATH", SANDBOX_PKG_CONFIG_PATH),
+    ];
+    compute_build_environment(
+        build_options,
+        Some(SANDBOX_PATH.to_string()),
+        Some(SANDBOX_LD_LIBRARY_PATH.to_string()),
+        Some(SANDBOX_PKG_CONFIG_PATH.to_string()),
+    )
+    .into_iter()
+    .filter(|(name, value)| !defaults.contains(&(name.as_str(), value.as_str())))
+    .collect()
+}
+
+fn compute_build_environment(
+    build_options: &[&FlatpakBuildOptions],
+    mut path_var: Option<String>,
+    mut ld_library_path: Option<String>,
+    mut pkg_config_path: Option<String>,
+) -> Vec<(String, String)> {
+    let mut cflags: Option<String> = None;
+    let mut cppflags: Option<String> = None;
+    let mut cxxflags: Option<String> = None;
+    let mut ldflags: Option<String> = None;
+    let mut env: Vec<(String, String)> = vec![];
+
+    for options in build_options {
+        add_flags(&mut cflags, &options.cflags, options.cflags_override);
+        add_flags(&mut cppflags, &options.cppflags, options.cppflags_override);
+        add_flags(&mut cxxflags, &options.cxxflags, options.cxxflags_override);
+        add_flags(&mut ldflags, &options.ldflags, options.ldflags_override);
+        add_to_path_list(&mut path_var, &options.prepend_path, &options.append_path);
+        add_to_path_list(
+            &mut ld_library_path,
+            &options.prepend_ld_library_path,
+            &options.append_ld_library_path,
+        );
+        add_to_path_list(
+            &mut pkg_config_path,
+            &options.prepend_pkg_config_path,
+            &options.append_pkg_config_path,
+        );
+
+        match &options.env {
+            FlatpakBuildOptionsEnv::Dict(variables) => {
+                for (name, value) in variables {
+                    env.push((name.to_string(), value.to_string()));
+                }
+            }
+            FlatpakBuildOptionsEnv::Array(variables) => {
+                for variable in variables {
+                    if let Some((name, value)) = variable.split_once('=') {
+                        env.push((name.to_string(), value.to_string()));
+                    }
+                }
+            }
+        };
+    }
+
+    let mut build_environment: Vec<(String, String)> = vec![];
+    for (name, value) in [
+        ("CFLAGS", cflags),
+        ("CPPFLAGS", cppflags),
+        ("CXXFLAGS", cxxflags),
+        ("LDFLAGS", ldflags),
+        ("PATH", path_var),
+        ("LD_LIBRARY_PATH", ld_library_path),
+        ("PKG_CONFIG_PATH", pkg_config_path),
+    ] {
+        if let Some(value) = value {
+            build_environment.push((name.to_string(), value));
+        }
+    }
+    // The variables from the env field override the ones computed from the other fields.
+    build_environment.append(&mut env);
+    build_environment
+}
+
+/// Gets the install prefix of a module, which can be overridden by the build options.
+pub fn get_install_prefix(build_options: &[&FlatpakBuildOptions], default_prefix: &str) -> String {
+    let mut prefix = default_prefix.to_string();
+    for options in build_options {
+        if !options.prefix.is_empty() {
+            prefix = options.prefix.to_string();
+        }
+    }
+    prefix
+}
+
+/// Gets the build options that apply to a module, in the order in which they apply.
+pub fn get_module_build_options<'a>(
+    app_build_options: Option<&'a FlatpakBuildOptions>,
+    module: &'a FlatpakModule,
+) -> Vec<&'a FlatpakBuildOptions> {
+    let mut build_options: Vec<&FlatpakBuildOptions> = vec![];
+    if let Some(options) = app_build_options {
+        build_options.push(options);
+    }
+    if let Some(options) = &module.build_options {
+        build_options.push(options);
+    }
+    build_options
+}
+
+/// Gets the build commands of a module with the configure options and the install
+/// prefix from the build options.
+fn get_configured_commands(
+    module: &FlatpakModule,
+    build_options: &[&FlatpakBuildOptions],
+    build_dir: &path::Path,
+    default_prefix: &str,
+) -> Result<Vec<Command>, String> {
+    let prefix = get_install_prefix(build_options, default_prefix);
+
+    // The configure options from the build options are added to the ones of the module.
+    let mut module = module.clone();
+    for options in build_options {
+        module
+            .config_opts
+            .extend(options.config_opts.iter().cloned());
+    }
+    get_module_commands(&module, build_dir, &prefix)
+}
+
+/// Gets the commands to build and install a module in the build sandbox rooted at
+/// `app_dir`, with the environment and the install prefix from the build options.
+pub fn get_sandbox_commands(
+    module: &FlatpakModule,
+    build_options: &[&FlatpakBuildOptions],
+    build_dir: &path::Path,
+    app_dir: &path::Path,
+) -> Result<Vec<Command>, String> {
+    let build_environment = get_sandbox_environment(build_options);
+    let commands = get_configured_commands(module, build_options, build_dir, SANDBOX_PREFIX)?;
+    Ok(commands
+        .iter()
+        .map(|c| get_sandboxed_command(c, app_dir, build_dir, &build_environment))
+        .collect())
+}
+
+/// Gets the commands to build and install a module directly on the host, with the
+/// environment and the install prefix from the build options.
+pub fn get_host_commands(
+    module: &FlatpakModule,
+    build_options: &[&FlatpakBuildOptions],
+    build_dir: &path::Path,
+    default_prefix: &str,
+) -> Result<Vec<Command>, String> {
+    let build_environment = get_build_environment(build_options);
+    let mut commands = get_configured_commands(module, build_options, build_dir, default_prefix)?;
+    for command in &mut commands {
+        if command.get_current_dir().is_none() {
+            command.current_dir(build_dir);
+        }
+        command.envs(build_environment.clone());
+    }
+    Ok(commands)
+}
+
+/// Builds and installs a module on the host.
+pub fn install_module_on_host(
+    module: &FlatpakModule,
+    build_options: &[&FlatpakBuildOptions],
+    state_dir: &path::Path,
+    default_prefix: &str,
+    fetch_config: &FetchConfig,
+) -> Result<(), String> {
+    let build_dir = get_module_build_dir(state_dir, module)?;
+    let log_path = get_module_log_path(state_dir, module)?;
+    let log_file = create_module_dirs(&build_dir, &log_path)?;
+    crate::fetch::fetch_module_sources(module, &build_dir, fetch_config)?;
+
+    for mut command in get_host_commands(module, build_options, &build_dir, default_prefix)? {
+        if let Err(e) = run_module_command(&mut command, &module.name, &log_file) {
+            return Err(format!(
+                "Failed to install module {} (see {} for the full logs): {}",
+                module.name,
+                log_path.display(),
+                e
+            ));
+        }
+    }
+    Ok(())
+}
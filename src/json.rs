@@ -0,0 +1,172 @@
+//! Finds the location of the elements of a JSON manifest from their JSON pointer.
+//! serde_json does not keep the locations of the values it parses. Like
+//! flatpak-builder, the comments are allowed in the manifests.
+
+struct Scanner<'a> {
+    content: &'a str,
+    position: usize,
+}
+impl<'a> Scanner<'a> {
+    fn current_byte(&self) -> Option<u8> {
+        self.content.as_bytes().get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let bytes = self.content.as_bytes();
+        while let Some(byte) = self.current_byte() {
+            if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else if byte == b'/' && bytes.get(self.position + 1) == Some(&b'/') {
+                while !matches!(self.current_byte(), Some(b'\n') | None) {
+                    self.position += 1;
+                }
+            } else if byte == b'/' && bytes.get(self.position + 1) == Some(&b'*') {
+                self.position += 2;
+                while self.position < bytes.len() && !bytes[self.position..].starts_with(b"*/") {
+                    self.position += 1;
+                }
+                self.position += 2;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Skips a string, and returns its unescaped value.
+    fn read_string(&mut self) -> Option<String> {
+        let start = self.position;
+        if self.current_byte()? != b'"' {
+            return None;
+        }
+        self.position += 1;
+        loop {
+            match self.current_byte()? {
+                b'\\' => self.position += 2,
+                b'"' => {
+                    self.position += 1;
+                    break;
+                }
+                _ => self.position += 1,
+            };
+        }
+        serde_json::from_str::<String>(&self.content[start..self.position]).ok()
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        match self.current_byte()? {
+            b'"' => {
+                self.read_string()?;
+            }
+            b'{' | b'[' => {
+                let mut depth = 0;
+                loop {
+                    self.skip_whitespace();
+                    match self.current_byte()? {
+                        b'"' => {
+                            self.read_string()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.position += 1;
+                                break;
+                            }
+                        }
+                        _ => {}
+                    };
+                    self.position += 1;
+                }
+            }
+            _ => {
+                while let Some(byte) = self.current_byte() {
+                    if byte == b',' || byte == b'}' || byte == b']' || byte.is_ascii_whitespace() {
+                        break;
+                    }
+                    self.position += 1;
+                }
+            }
+        };
+        Some(())
+    }
+
+    /// Moves to the value of `key` in the object at the current position, and
+    /// returns the position of the key.
+    fn find_member(&mut self, key: &str) -> Option<usize> {
+        if self.current_byte()? != b'{' {
+            return None;
+        }
+        self.position += 1;
+        loop {
+            self.skip_whitespace();
+            let key_position = self.position;
+            let member_key = self.read_string()?;
+            self.skip_whitespace();
+            if self.current_byte()? != b':' {
+                return None;
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            if member_key == key {
+                return Some(key_position);
+            }
+            self.skip_value()?;
+            self.skip_whitespace();
+            if self.current_byte()? != b',' {
+                return None;
+            }
+            self.position += 1;
+        }
+    }
+
+    /// Moves to the item at `index` in the array at the current position.
+    fn find_item(&mut self, index: usize) -> Option<usize> {
+        if self.current_byte()? != b'[' {
+            return None;
+        }
+        self.position += 1;
+        let mut item_index = 0;
+        loop {
+            self.skip_whitespace();
+            if self.current_byte()? == b']' {
+                return None;
+            }
+            if item_index == index {
+                return Some(self.position);
+            }
+            self.skip_value()?;
+            self.skip_whitespace();
+            if self.current_byte()? != b',' {
+                return None;
+            }
+            self.position += 1;
+            item_index += 1;
+        }
+    }
+}
+
+/// Gets the line and column where the element at the given JSON pointer starts.
+/// For the members of an object, this is the location of their key.
+pub fn get_location(content: &str, pointer: &str) -> Option<(usize, usize)> {
+    let mut scanner = Scanner {
+        content,
+        position: 0,
+    };
+    scanner.skip_whitespace();
+    let mut element_position = scanner.position;
+
+    for component in crate::yaml::get_pointer_components(pointer) {
+        element_position = match scanner.current_byte()? {
+            b'{' => scanner.find_member(&component)?,
+            b'[' => scanner.find_item(component.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    let content_before = &content[..element_position];
+    let line = content_before.matches('\n').count();
+    let line_start = content_before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = content_before[line_start..].chars().count();
+    Some((line, column))
+}
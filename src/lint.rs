@@ -0,0 +1,911 @@
+//! The lint rules. Besides the formatting, these rules report problems with the
+//! content of the manifests, using the criteria applied when reviewing Flathub
+//! submissions.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+use serde::Serialize;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::format::FlatpakManifestFormat;
+use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+pub const FORMATTING: &str = "formatting";
+pub const ARCHIVE_MISSING_SHA256: &str = "archive-missing-sha256";
+pub const GIT_SOURCE_NOT_PINNED: &str = "git-source-not-pinned";
+pub const DUPLICATE_MODULE_NAME: &str = "duplicate-module-name";
+pub const BROAD_FILESYSTEM_ACCESS: &str = "broad-filesystem-access";
+pub const INVALID_APP_ID: &str = "invalid-app-id";
+pub const EOL_RUNTIME: &str = "eol-runtime";
+
+pub const SARIF_VERSION: &str = "2.1.0";
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Suppresses diagnostics on the line of the comment, or on the next line.
+pub const SUPPRESSION_COMMENT: &str = "fpcli: ignore";
+/// Suppresses diagnostics in the whole file.
+pub const FILE_SUPPRESSION_COMMENT: &str = "fpcli: ignore-file";
+
+/// The filesystem locations that give access to all the files of the user, whatever
+/// the access mode that follows them.
+pub const BROAD_FILESYSTEMS: [&str; 5] = ["home", "~", "host", "host-os", "host-etc"];
+
+/// The runtime versions that do not receive updates anymore, as of October 2026.
+/// This list is a snapshot, so more versions can be added with the `eol-runtimes`
+/// table of the lint configuration.
+pub const EOL_RUNTIMES: [(&str, &[&str]); 3] = [
+    (
+        "org.freedesktop.Platform",
+        &[
+            "1.6", "18.08", "19.08", "20.08", "21.08", "22.08", "23.08", "24.08",
+        ],
+    ),
+    (
+        "org.gnome.Platform",
+        &[
+            "3.24", "3.26", "3.28", "3.30", "3.32", "3.34", "3.36", "3.38", "40", "41", "42", "43",
+            "44", "45", "46", "47", "48",
+        ],
+    ),
+    (
+        "org.kde.Platform",
+        &[
+            "5.9", "5.10", "5.11", "5.12", "5.13", "5.14", "6.2", "6.3", "6.4", "6.5", "6.6",
+            "6.7", "6.8",
+        ],
+    ),
+];
+
+/// The maximum length of an application ID.
+pub const MAX_APP_ID_LENGTH: usize = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match &self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}", severity)
+    }
+}
+impl Severity {
+    pub fn from_string(severity: &str) -> Result<Severity, String> {
+        match severity {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!("Invalid severity {}.", severity)),
+        }
+    }
+}
+
+/// A lint rule, and its default severity.
+pub struct Rule {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+}
+
+pub const RULES: [Rule; 7] = [
+    Rule {
+        id: FORMATTING,
+        severity: Severity::Error,
+        description: "Manifests must be formatted with `fpcli lint`.",
+    },
+    Rule {
+        id: ARCHIVE_MISSING_SHA256,
+        severity: Severity::Error,
+        description: "Archive sources must have a sha256 checksum.",
+    },
+    Rule {
+        id: GIT_SOURCE_NOT_PINNED,
+        severity: Severity::Warning,
+        description: "Git sources should be pinned to a commit or a tag.",
+    },
+    Rule {
+        id: DUPLICATE_MODULE_NAME,
+        severity: Severity::Error,
+        description: "Module names must be unique.",
+    },
+    Rule {
+        id: BROAD_FILESYSTEM_ACCESS,
+        severity: Severity::Warning,
+        description: "Applications should not have access to the whole home directory or host.",
+    },
+    Rule {
+        id: INVALID_APP_ID,
+        severity: Severity::Error,
+        description: "The application id must be a reverse DNS name.",
+    },
+    Rule {
+        id: EOL_RUNTIME,
+        severity: Severity::Warning,
+        description: "The runtime version should still receive updates.",
+    },
+];
+
+pub fn get_rule(rule_id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|r| r.id == rule_id)
+}
+
+#[derive(Clone, Debug, Serialize)]
+/// A problem found in a manifest.
+pub struct Diagnostic {
+    pub rule_id: String,
+    pub severity: Severity,
+    pub message: String,
+    /// The path of the manifest. Set when locating the diagnostic.
+    pub path: String,
+    /// The JSON pointer of the element of the manifest with the problem.
+    pub pointer: String,
+    /// The line of the element, starting at 1, when it can be found in the manifest.
+    pub line: Option<usize>,
+    /// The column of the element, starting at 1, when it can be found in the manifest.
+    pub column: Option<usize>,
+}
+impl Diagnostic {
+    pub fn new(rule_id: &str, message: String, pointer: String) -> Diagnostic {
+        let severity = match get_rule(rule_id) {
+            Some(r) => r.severity,
+            None => Severity::Error,
+        };
+        Diagnostic {
+            rule_id: rule_id.to_string(),
+            severity,
+            message,
+            path: "".to_string(),
+            pointer,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+/// Sets the path of the diagnostics of a manifest, and the location of their
+/// element in the manifest.
+pub fn locate_diagnostics(
+    diagnostics: &mut [Diagnostic],
+    manifest_path: &str,
+    manifest_content: &str,
+) {
+    let yaml_document = match FlatpakManifestFormat::from_path(manifest_path) {
+        Some(FlatpakManifestFormat::YAML) => {
+            crate::yaml::YamlDocument::parse(manifest_content).ok()
+        }
+        _ => None,
+    };
+    for diagnostic in diagnostics {
+        diagnostic.path = manifest_path.to_string();
+        let location = match FlatpakManifestFormat::from_path(manifest_path) {
+            Some(FlatpakManifestFormat::YAML) => yaml_document
+                .as_ref()
+                .and_then(|d| d.get_location(&diagnostic.pointer)),
+            Some(FlatpakManifestFormat::JSON) => {
+                crate::json::get_location(manifest_content, &diagnostic.pointer)
+            }
+            _ => None,
+        };
+        if let Some((line, column)) = location {
+            diagnostic.line = Some(line + 1);
+            diagnostic.column = Some(column + 1);
+        }
+    }
+}
+
+/// Checks an application ID with the rules of Flatpak: at least 3 elements
+/// separated by dots, made of ASCII letters, digits and underscores, and not
+/// starting with a digit. Only the last element can contain dashes.
+pub fn is_valid_app_id(app_id: &str) -> bool {
+    if app_id.len() > MAX_APP_ID_LENGTH {
+        return false;
+    }
+    let elements: Vec<&str> = app_id.split('.').collect();
+    if elements.len() < 3 {
+        return false;
+    }
+    for (element_index, element) in elements.iter().enumerate() {
+        let is_last_element = element_index == elements.len() - 1;
+        let first_char = match element.chars().next() {
+            Some(c) => c,
+            None => return false,
+        };
+        if first_char.is_ascii_digit() {
+            return false;
+        }
+        if !element
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || (c == '-' && is_last_element))
+        {
+            return false;
+        }
+    }
+    true
+}
+
+/// Checks if a runtime version is end-of-life, using the built-in list and the
+/// versions added in the lint configuration.
+pub fn is_eol_runtime(
+    runtime: &str,
+    runtime_version: &str,
+    lint_config: &crate::config::LintConfig,
+) -> bool {
+    is_listed_eol_runtime(&EOL_RUNTIMES, runtime, runtime_version, lint_config)
+}
+
+fn is_listed_eol_runtime(
+    eol_runtimes: &[(&str, &[&str])],
+    runtime: &str,
+    runtime_version: &str,
+    lint_config: &crate::config::LintConfig,
+) -> bool {
+    let is_builtin_eol = eol_runtimes
+        .iter()
+        .any(|(r, versions)| *r == runtime && versions.contains(&runtime_version));
+    let is_configured_eol = match lint_config.eol_runtimes.get(runtime) {
+        Some(versions) => versions.iter().any(|v| v == runtime_version),
+        None => false,
+    };
+    is_builtin_eol || is_configured_eol
+}
+
+/// Checks if a finish argument gives access to one of the broad filesystem locations,
+/// with any access mode.
+pub fn is_broad_filesystem_arg(finish_arg: &str) -> bool {
+    let filesystem = match finish_arg.strip_prefix("--filesystem=") {
+        Some(f) => f,
+        None => return false,
+    };
+    let location = match filesystem.split_once(':') {
+        Some((l, _)) => l,
+        None => filesystem,
+    };
+    BROAD_FILESYSTEMS.contains(&location)
+}
+
+pub fn lint_application(
+    application: &FlatpakApplication,
+    lint_config: &crate::config::LintConfig,
+) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+
+    let app_id = application.get_id();
+    let app_id_pointer = if application.app_id.is_empty() {
+        "/id"
+    } else {
+        "/app-id"
+    };
+    if !is_valid_app_id(&app_id) {
+        diagnostics.push(Diagnostic::new(
+            INVALID_APP_ID,
+            format!("Application id {} is not a valid reverse DNS name.", app_id),
+            app_id_pointer.to_string(),
+        ));
+    }
+
+    if is_eol_runtime(
+        &application.runtime,
+        &application.runtime_version,
+        lint_config,
+    ) {
+        diagnostics.push(Diagnostic::new(
+            EOL_RUNTIME,
+            format!(
+                "Runtime {} {} is end-of-life.",
+                application.runtime, application.runtime_version
+            ),
+            "/runtime-version".to_string(),
+        ));
+    }
+
+    for (arg_index, finish_arg) in application.finish_args.iter().enumerate() {
+        if is_broad_filesystem_arg(finish_arg) {
+            diagnostics.push(Diagnostic::new(
+                BROAD_FILESYSTEM_ACCESS,
+                format!(
+                    "Finish argument {} gives access to too many files.",
+                    finish_arg
+                ),
+                format!("/finish-args/{}", arg_index),
+            ));
+        }
+    }
+
+    let mut module_names: HashMap<String, String> = HashMap::new();
+    lint_module_items(
+        &application.modules,
+        "",
+        &mut module_names,
+        &mut diagnostics,
+    );
+
+    diagnostics
+}
+
+pub fn lint_module(module: &FlatpakModule) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    let mut module_names: HashMap<String, String> = HashMap::new();
+    module_names.insert(module.name.to_string(), "".to_string());
+    lint_module_content(module, "", &mut module_names, &mut diagnostics);
+    diagnostics
+}
+
+/// Lints the modules declared in a manifest. The modules imported by path are
+/// linted with their own manifest.
+fn lint_module_items(
+    module_items: &[FlatpakModuleItem],
+    parent_pointer: &str,
+    module_names: &mut HashMap<String, String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for (module_index, module_item) in module_items.iter().enumerate() {
+        let module = match module_item {
+            FlatpakModuleItem::Description(m) => m,
+            FlatpakModuleItem::Path(_) => continue,
+        };
+        let module_pointer = format!("{}/modules/{}", parent_pointer, module_index);
+
+        match module_names.get(&module.name) {
+            Some(first_pointer) => diagnostics.push(Diagnostic::new(
+                DUPLICATE_MODULE_NAME,
+                format!(
+                    "Module name {} is already used by the module at {}.",
+                    module.name,
+                    if first_pointer.is_empty() {
+                        "/"
+                    } else {
+                        first_pointer
+                    }
+                ),
+                module_pointer.to_string(),
+            )),
+            None => {
+                module_names.insert(module.name.to_string(), module_pointer.to_string());
+            }
+        };
+
+        lint_module_content(module, &module_pointer, module_names, diagnostics);
+    }
+}
+
+fn lint_module_content(
+    module: &FlatpakModule,
+    module_pointer: &str,
+    module_names: &mut HashMap<String, String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for (source_index, source_item) in module.sources.iter().enumerate() {
+        if let FlatpakSourceItem::Description(source) = source_item {
+            let source_pointer = format!("{}/sources/{}", module_pointer, source_index);
+            diagnostics.append(&mut lint_source(source, &source_pointer));
+        }
+    }
+    lint_module_items(&module.modules, module_pointer, module_names, diagnostics);
+}
+
+/// Lints the sources of a source manifest, which contains either a single source
+/// or an array of sources.
+pub fn lint_sources(sources: &[FlatpakSource], is_single_source: bool) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    for (source_index, source) in sources.iter().enumerate() {
+        let source_pointer = if is_single_source {
+            "".to_string()
+        } else {
+            format!("/{}", source_index)
+        };
+        diagnostics.append(&mut lint_source(source, &source_pointer));
+    }
+    diagnostics
+}
+
+pub fn lint_source(source: &FlatpakSource, source_pointer: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+
+    match source.get_type() {
+        Some(FlatpakSourceType::Archive) => {
+            // Local archives are part of the repository, so they don't need a checksum.
+            if let (Some(url), None) = (&source.url, &source.sha256) {
+                diagnostics.push(Diagnostic::new(
+                    ARCHIVE_MISSING_SHA256,
+                    format!("Archive source {} has no sha256 checksum.", url),
+                    source_pointer.to_string(),
+                ));
+            }
+        }
+        Some(FlatpakSourceType::Git) if source.commit.is_none() && source.tag.is_none() => {
+            let url = source.url.as_deref().unwrap_or("");
+            let message = match &source.branch {
+                Some(branch) => format!(
+                    "Git source {} is pinned to branch {} instead of a commit or a tag.",
+                    url, branch
+                ),
+                None => format!("Git source {} is not pinned to a commit or a tag.", url),
+            };
+            diagnostics.push(Diagnostic::new(
+                GIT_SOURCE_NOT_PINNED,
+                message,
+                source_pointer.to_string(),
+            ));
+        }
+        _ => {}
+    };
+
+    diagnostics
+}
+
+/// Removes the diagnostics of the rules disabled in the project configuration,
+/// and applies the severities of the configuration.
+pub fn apply_config(
+    diagnostics: Vec<Diagnostic>,
+    project_config: &crate::config::ProjectConfig,
+    manifest_path: &str,
+) -> Vec<Diagnostic> {
+    let lint_config = &project_config.config.lint;
+
+    let manifest_path = fs::canonicalize(manifest_path).unwrap_or_else(|_| manifest_path.into());
+    let relative_path = manifest_path
+        .strip_prefix(&project_config.base_dir)
+        .unwrap_or(&manifest_path);
+    let mut ignored_rule_ids: Vec<&String> = vec![];
+    for (pattern, rule_ids) in &lint_config.per_file_ignores {
+        // The patterns were validated when loading the configuration.
+        // A `*` does not match the path separators, so that `build/*` only matches the
+        // files directly in `build`.
+        let match_options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        if glob::Pattern::new(pattern)
+            .unwrap()
+            .matches_path_with(relative_path, match_options)
+        {
+            ignored_rule_ids.extend(rule_ids);
+        }
+    }
+
+    let mut filtered_diagnostics: Vec<Diagnostic> = vec![];
+    for mut diagnostic in diagnostics {
+        if !lint_config.enable.is_empty() && !lint_config.enable.contains(&diagnostic.rule_id) {
+            continue;
+        }
+        if lint_config.disable.contains(&diagnostic.rule_id) {
+            continue;
+        }
+        if ignored_rule_ids
+            .iter()
+            .any(|r| *r == crate::config::ALL_RULES || **r == diagnostic.rule_id)
+        {
+            continue;
+        }
+        if let Some(severity) = lint_config.severity.get(&diagnostic.rule_id) {
+            diagnostic.severity = Severity::from_string(severity).unwrap();
+        }
+        filtered_diagnostics.push(diagnostic);
+    }
+    filtered_diagnostics
+}
+
+/// A suppression comment. The comment applies to its own line when it follows
+/// some content, or else to the next line with content.
+struct Suppression {
+    line: Option<usize>,
+    /// The suppressed rules. All the rules are suppressed when empty.
+    rule_ids: Vec<String>,
+}
+impl Suppression {
+    fn matches(&self, diagnostic: &Diagnostic) -> bool {
+        self.rule_ids.is_empty() || self.rule_ids.contains(&diagnostic.rule_id)
+    }
+}
+
+/// Parses a comment like `# fpcli: ignore[rule-id, other-rule-id]`, and returns
+/// whether the comment applies to the whole file, and the suppressed rules.
+fn parse_suppression_comment(comment: &str) -> Option<(bool, Vec<String>)> {
+    let comment = comment.trim_start_matches('#').trim();
+    let (is_file_suppression, rule_ids) =
+        if let Some(rule_ids) = comment.strip_prefix(FILE_SUPPRESSION_COMMENT) {
+            (true, rule_ids)
+        } else if let Some(rule_ids) = comment.strip_prefix(SUPPRESSION_COMMENT) {
+            (false, rule_ids)
+        } else {
+            return None;
+        };
+
+    let rule_ids = rule_ids.trim();
+    if rule_ids.is_empty() {
+        return Some((is_file_suppression, vec![]));
+    }
+    let rule_ids = rule_ids.strip_prefix('[')?.strip_suffix(']')?;
+    Some((
+        is_file_suppression,
+        rule_ids
+            .split(',')
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty())
+            .collect(),
+    ))
+}
+
+/// Removes the diagnostics suppressed by the comments of a YAML manifest.
+pub fn apply_suppression_comments(
+    diagnostics: Vec<Diagnostic>,
+    manifest_content: &str,
+) -> Vec<Diagnostic> {
+    let document = match crate::yaml::YamlDocument::parse(manifest_content) {
+        Ok(d) => d,
+        Err(_) => return diagnostics,
+    };
+
+    let mut suppressions: Vec<Suppression> = vec![];
+    let mut pending_rule_ids: Vec<Vec<String>> = vec![];
+    for (line_index, line) in document.lines.iter().enumerate() {
+        let comment_index = crate::yaml::find_comment(line);
+        let content = match comment_index {
+            Some(i) => line[..i].trim(),
+            None => line.trim(),
+        };
+        if !content.is_empty() {
+            for rule_ids in pending_rule_ids.drain(..) {
+                suppressions.push(Suppression {
+                    line: Some(line_index),
+                    rule_ids,
+                });
+            }
+        }
+
+        let comment_index = match comment_index {
+            Some(i) => i,
+            None => continue,
+        };
+        let (is_file_suppression, rule_ids) =
+            match parse_suppression_comment(&line[comment_index..]) {
+                Some(s) => s,
+                None => continue,
+            };
+        if is_file_suppression {
+            suppressions.push(Suppression {
+                line: None,
+                rule_ids,
+            });
+        } else if content.is_empty() {
+            pending_rule_ids.push(rule_ids);
+        } else {
+            suppressions.push(Suppression {
+                line: Some(line_index),
+                rule_ids,
+            });
+        }
+    }
+
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| {
+            let diagnostic_line = diagnostic.line.map(|l| l - 1);
+            !suppressions
+                .iter()
+                .any(|s| s.matches(diagnostic) && (s.line.is_none() || s.line == diagnostic_line))
+        })
+        .collect()
+}
+
+/// The SARIF level matching a severity.
+fn get_sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+/// Builds a SARIF 2.1.0 log, as used by the code scanning tools.
+pub fn get_sarif_log(diagnostics: &[Diagnostic]) -> serde_json::Value {
+    let rules: Vec<serde_json::Value> = RULES
+        .iter()
+        .map(|rule| {
+            serde_json::json!({
+                "id": rule.id,
+                "shortDescription": { "text": rule.description },
+                "defaultConfiguration": { "level": get_sarif_level(rule.severity) },
+            })
+        })
+        .collect();
+
+    let results: Vec<serde_json::Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut physical_location = serde_json::json!({
+                "artifactLocation": { "uri": diagnostic.path },
+            });
+            if let (Some(line), Some(column)) = (diagnostic.line, diagnostic.column) {
+                physical_location["region"] = serde_json::json!({
+                    "startLine": line,
+                    "startColumn": column,
+                });
+            }
+            serde_json::json!({
+                "ruleId": diagnostic.rule_id,
+                "level": get_sarif_level(diagnostic.severity),
+                "message": { "text": diagnostic.message },
+                "locations": [{
+                    "physicalLocation": physical_location,
+                    "logicalLocations": [{ "fullyQualifiedName": diagnostic.pointer }],
+                }],
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_app_ids() {
+        for app_id in [
+            "org.gnome.Builder",
+            "io.github.user_name.App",
+            "org.example.my-app",
+            "com.example.App2",
+        ] {
+            assert!(is_valid_app_id(app_id), "{}", app_id);
+        }
+    }
+
+    #[test]
+    fn invalid_app_ids() {
+        for app_id in [
+            "",
+            "org.example",
+            "0rg.example.App",
+            "org.2example.App",
+            "org..App",
+            "org.example.App.",
+            ".org.example.App",
+            "org.my-example.App",
+            "org.example.App name",
+            "org.example/App.json",
+        ] {
+            assert!(!is_valid_app_id(app_id), "{}", app_id);
+        }
+        assert!(!is_valid_app_id(&format!(
+            "org.example.{}",
+            "a".repeat(250)
+        )));
+    }
+
+    const EOL_RUNTIMES_FIXTURE: [(&str, &[&str]); 2] = [
+        ("org.example.Platform", &["1.0", "2.0"]),
+        ("org.example.Other", &["3.0"]),
+    ];
+
+    #[test]
+    fn eol_runtimes() {
+        let lint_config = crate::config::LintConfig::default();
+        let is_eol = |runtime, version| {
+            is_listed_eol_runtime(&EOL_RUNTIMES_FIXTURE, runtime, version, &lint_config)
+        };
+        assert!(is_eol("org.example.Platform", "1.0"));
+        assert!(is_eol("org.example.Other", "3.0"));
+        assert!(!is_eol("org.example.Platform", "3.0"));
+        assert!(!is_eol("org.example.Platform", "1"));
+        assert!(!is_eol("org.example.Unknown", "1.0"));
+    }
+
+    #[test]
+    fn eol_runtimes_from_the_config() {
+        let mut lint_config = crate::config::LintConfig::default();
+        lint_config
+            .eol_runtimes
+            .insert("org.example.Platform".to_string(), vec!["3.0".to_string()]);
+        let is_eol = |runtime, version| {
+            is_listed_eol_runtime(&EOL_RUNTIMES_FIXTURE, runtime, version, &lint_config)
+        };
+        assert!(is_eol("org.example.Platform", "3.0"));
+        assert!(is_eol("org.example.Platform", "1.0"));
+        assert!(!is_eol("org.example.Other", "1.0"));
+    }
+
+    #[test]
+    fn broad_filesystem_args() {
+        for finish_arg in [
+            "--filesystem=home",
+            "--filesystem=home:ro",
+            "--filesystem=home:create",
+            "--filesystem=~",
+            "--filesystem=~:rw",
+            "--filesystem=host",
+            "--filesystem=host:ro",
+            "--filesystem=host-os",
+            "--filesystem=host-etc:ro",
+        ] {
+            assert!(is_broad_filesystem_arg(finish_arg), "{}", finish_arg);
+        }
+        for finish_arg in [
+            "--filesystem=home/Music",
+            "--filesystem=~/Music:ro",
+            "--filesystem=xdg-download",
+            "--filesystem=/home",
+            "--share=home",
+        ] {
+            assert!(!is_broad_filesystem_arg(finish_arg), "{}", finish_arg);
+        }
+    }
+
+    #[test]
+    fn bootstrap_runtimes_are_not_eol() {
+        let lint_config = crate::config::LintConfig::default();
+        for runtime in crate::bootstrap::RUNTIMES {
+            assert!(!is_eol_runtime(
+                runtime.runtime,
+                runtime.version,
+                &lint_config
+            ));
+        }
+    }
+
+    fn get_project_config(config_content: &str) -> crate::config::ProjectConfig {
+        crate::config::ProjectConfig {
+            base_dir: std::path::PathBuf::from("/project"),
+            config: toml::from_str(config_content).unwrap(),
+        }
+    }
+
+    fn get_diagnostics(rule_ids: &[&str]) -> Vec<Diagnostic> {
+        rule_ids
+            .iter()
+            .map(|r| Diagnostic::new(r, "".to_string(), "".to_string()))
+            .collect()
+    }
+
+    fn get_rule_ids(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().map(|d| d.rule_id.as_str()).collect()
+    }
+
+    #[test]
+    fn apply_config_enable_and_disable() {
+        let project_config = get_project_config(&format!(
+            "[lint]\nenable = [\"{}\", \"{}\"]\ndisable = [\"{}\"]\n",
+            ARCHIVE_MISSING_SHA256, EOL_RUNTIME, EOL_RUNTIME
+        ));
+        let diagnostics = apply_config(
+            get_diagnostics(&[ARCHIVE_MISSING_SHA256, EOL_RUNTIME, INVALID_APP_ID]),
+            &project_config,
+            "/project/org.example.App.yaml",
+        );
+        assert_eq!(get_rule_ids(&diagnostics), vec![ARCHIVE_MISSING_SHA256]);
+    }
+
+    #[test]
+    fn apply_config_severity() {
+        let project_config =
+            get_project_config(&format!("[lint.severity]\n{} = \"error\"\n", EOL_RUNTIME));
+        let diagnostics = apply_config(
+            get_diagnostics(&[EOL_RUNTIME, BROAD_FILESYSTEM_ACCESS]),
+            &project_config,
+            "/project/org.example.App.yaml",
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn apply_config_per_file_ignores() {
+        let project_config = get_project_config(&format!(
+            "[lint.per-file-ignores]\n\"build/*\" = [\"{}\"]\n\"vendor/**\" = [\"*\"]\n",
+            EOL_RUNTIME
+        ));
+        let apply = |manifest_path| {
+            apply_config(
+                get_diagnostics(&[EOL_RUNTIME, INVALID_APP_ID]),
+                &project_config,
+                manifest_path,
+            )
+        };
+        assert_eq!(
+            get_rule_ids(&apply("/project/build/a.yaml")),
+            vec![INVALID_APP_ID]
+        );
+        // A `*` does not match the nested directories.
+        assert_eq!(
+            get_rule_ids(&apply("/project/build/a/b/c.yaml")),
+            vec![EOL_RUNTIME, INVALID_APP_ID]
+        );
+        assert!(apply("/project/vendor/a/b/c.yaml").is_empty());
+        assert_eq!(apply("/project/a.yaml").len(), 2);
+    }
+
+    fn get_located_diagnostic(rule_id: &str, line: usize) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new(rule_id, "".to_string(), "".to_string());
+        diagnostic.line = Some(line);
+        diagnostic.column = Some(1);
+        diagnostic
+    }
+
+    #[test]
+    fn suppression_comments() {
+        let manifest = format!(
+            "app-id: org.example.App # fpcli: ignore[{0}]\n# fpcli: ignore\nruntime: org.example.Platform\n\n# fpcli: ignore[{1}, {0}]\n\nruntime-version: '1.0'\nsdk: org.example.Sdk # fpcli: ignore[{1}]\n",
+            INVALID_APP_ID, EOL_RUNTIME
+        );
+        let diagnostics = apply_suppression_comments(
+            vec![
+                get_located_diagnostic(INVALID_APP_ID, 1),
+                get_located_diagnostic(EOL_RUNTIME, 1),
+                get_located_diagnostic(EOL_RUNTIME, 3),
+                get_located_diagnostic(INVALID_APP_ID, 3),
+                get_located_diagnostic(EOL_RUNTIME, 7),
+                get_located_diagnostic(INVALID_APP_ID, 8),
+                get_located_diagnostic(EOL_RUNTIME, 8),
+            ],
+            &manifest,
+        );
+        let remaining: Vec<(&str, Option<usize>)> = diagnostics
+            .iter()
+            .map(|d| (d.rule_id.as_str(), d.line))
+            .collect();
+        assert_eq!(
+            remaining,
+            vec![(EOL_RUNTIME, Some(1)), (INVALID_APP_ID, Some(8))]
+        );
+    }
+
+    #[test]
+    fn file_suppression_comments() {
+        let manifest = format!(
+            "app-id: org.example.App\nruntime: org.example.Platform\n# fpcli: ignore-file[{}]\n",
+            EOL_RUNTIME
+        );
+        let diagnostics = apply_suppression_comments(
+            vec![
+                get_located_diagnostic(EOL_RUNTIME, 1),
+                get_located_diagnostic(INVALID_APP_ID, 1),
+                Diagnostic::new(EOL_RUNTIME, "".to_string(), "".to_string()),
+            ],
+            &manifest,
+        );
+        assert_eq!(get_rule_ids(&diagnostics), vec![INVALID_APP_ID]);
+    }
+
+    #[test]
+    fn lint_application_ids() {
+        let lint_config = crate::config::LintConfig::default();
+        let mut application = FlatpakApplication {
+            id: "0rg.example.App".to_string(),
+            runtime: "org.example.Platform".to_string(),
+            runtime_version: "1.0".to_string(),
+            ..Default::default()
+        };
+        let diagnostics = lint_application(&application, &lint_config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule_id, INVALID_APP_ID);
+        assert_eq!(diagnostics[0].pointer, "/id");
+
+        application.id = "org.example.App".to_string();
+        assert!(lint_application(&application, &lint_config).is_empty());
+    }
+}
@@ -0,0 +1,684 @@
+//! Generates the sources of the dependencies of a project from its lockfiles, so
+//! the dependencies can be vendored for the offline builds.
+use std::collections::BTreeSet;
+use std::fs;
+use std::path;
+
+use flatpak_rs::archive::FlatpakArchiveType;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceType};
+
+pub const CARGO_LOCKFILE: &str = "Cargo.lock";
+pub const NPM_LOCKFILE: &str = "package-lock.json";
+pub const YARN_LOCKFILE: &str = "yarn.lock";
+pub const PIP_REQUIREMENTS: &str = "requirements.txt";
+pub const LOCKFILES: [&str; 4] = [
+    CARGO_LOCKFILE,
+    NPM_LOCKFILE,
+    YARN_LOCKFILE,
+    PIP_REQUIREMENTS,
+];
+
+pub const CRATES_IO_REGISTRY: &str = "registry+https://github.com/rust-lang/crates.io-index";
+pub const CRATES_DOWNLOAD_URL: &str = "https://static.crates.io/crates";
+/// The directory of the vendored crates, relative to the build directory. The
+/// cargo home of the cargo template is in the build directory.
+pub const CARGO_VENDOR_DIR: &str = "cargo/vendor";
+/// The directory of the npm tarballs, added to the npm cache by the node template.
+pub const NODE_TARBALLS_DIR: &str = "flatpak-node/tarballs";
+pub const PYPI_DOWNLOAD_URL: &str = "https://files.pythonhosted.org/packages/source";
+
+/// The sources generated from a lockfile.
+#[derive(Debug, Default)]
+pub struct GeneratedSources {
+    pub sources: Vec<FlatpakSource>,
+    /// The dependencies that could not be converted to sources.
+    pub warnings: Vec<String>,
+}
+
+/// Gets the name of the manifest to write the sources generated from a lockfile to,
+/// following the names used by flatpak-builder-tools.
+pub fn get_sources_file_name(lockfile_name: &str) -> &'static str {
+    match lockfile_name {
+        CARGO_LOCKFILE => "cargo-sources.json",
+        NPM_LOCKFILE | YARN_LOCKFILE => "node-sources.json",
+        PIP_REQUIREMENTS => "python3-sources.json",
+        _ => "generated-sources.json",
+    }
+}
+
+/// Finds the lockfiles of the project in `dir`.
+pub fn find_lockfiles(dir: &path::Path) -> Vec<path::PathBuf> {
+    LOCKFILES
+        .iter()
+        .map(|l| dir.join(l))
+        .filter(|l| l.is_file())
+        .collect()
+}
+
+/// Generates the sources of the dependencies listed in a lockfile. The kind of
+/// lockfile is found from its file name.
+pub fn generate_sources(lockfile_path: &path::Path) -> Result<GeneratedSources, String> {
+    let content = match fs::read_to_string(lockfile_path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(format!(
+                "Could not read file {}: {}.",
+                lockfile_path.display(),
+                e
+            ))
+        }
+    };
+    let file_name = lockfile_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match file_name.as_str() {
+        CARGO_LOCKFILE => generate_cargo_sources(&content),
+        NPM_LOCKFILE => generate_npm_sources(&content),
+        YARN_LOCKFILE => generate_yarn_sources(&content),
+        n if n.ends_with(".txt") => generate_pip_sources(&content),
+        _ => Err(format!(
+            "{} is not a known lockfile. The lockfiles are {}.",
+            lockfile_path.display(),
+            LOCKFILES.join(", ")
+        )),
+    }
+}
+
+/// Generates an archive source for every crate from crates.io, extracted to the
+/// vendor directory. Cargo needs a checksum file in every vendored crate, and a
+/// configuration replacing crates.io with the vendor directory, which are created
+/// by a shell source since flatpak-builder cannot write files from a manifest.
+pub fn generate_cargo_sources(content: &str) -> Result<GeneratedSources, String> {
+    let lockfile: toml::Value = match toml::from_str(content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Could not parse {}: {}.", CARGO_LOCKFILE, e)),
+    };
+    let packages = match lockfile.get("package").and_then(|p| p.as_array()) {
+        Some(p) => p.to_vec(),
+        None => vec![],
+    };
+
+    let mut generated_sources = GeneratedSources::default();
+    let mut commands: Vec<String> = vec![];
+    for package in packages {
+        let name = package.get("name").and_then(|n| n.as_str()).unwrap_or("");
+        let version = package
+            .get("version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let source = match package.get("source").and_then(|s| s.as_str()) {
+            Some(s) => s,
+            // The packages of the workspace.
+            None => continue,
+        };
+        if source != CRATES_IO_REGISTRY {
+            generated_sources.warnings.push(format!(
+                "Skipping crate {} {} from {}, only the crates from crates.io are supported.",
+                name, version, source
+            ));
+            continue;
+        }
+        let checksum = match package.get("checksum").and_then(|c| c.as_str()) {
+            Some(c) => c,
+            None => {
+                generated_sources.warnings.push(format!(
+                    "Skipping crate {} {} without a checksum.",
+                    name, version
+                ));
+                continue;
+            }
+        };
+
+        let crate_dir = format!("{}/{}-{}", CARGO_VENDOR_DIR, name, version);
+        generated_sources.sources.push(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Archive),
+            archive_type: Some(FlatpakArchiveType::TarGzip),
+            url: Some(format!(
+                "{}/{}/{}-{}.crate",
+                CRATES_DOWNLOAD_URL, name, name, version
+            )),
+            sha256: Some(checksum.to_string()),
+            dest: Some(crate_dir.to_string()),
+            ..Default::default()
+        });
+        commands.push(format!(
+            "echo '{{\"package\": \"{}\", \"files\": {{}}}}' > {}/.cargo-checksum.json",
+            checksum, crate_dir
+        ));
+    }
+    if generated_sources.sources.is_empty() {
+        return Ok(generated_sources);
+    }
+
+    commands.push(format!(
+        "printf '[source.vendored-sources]\\ndirectory = \"{}\"\\n\\n[source.crates-io]\\nreplace-with = \"vendored-sources\"\\n' > cargo/config",
+        CARGO_VENDOR_DIR
+    ));
+    generated_sources.sources.push(FlatpakSource {
+        r#type: Some(FlatpakSourceType::Shell),
+        commands: Some(commands),
+        ..Default::default()
+    });
+    Ok(generated_sources)
+}
+
+/// Generates a file source for the tarball of every package of a
+/// package-lock.json file. Both the `packages` of the lockfiles of version 2 and 3
+/// and the nested `dependencies` of the lockfiles of version 1 are supported.
+pub fn generate_npm_sources(content: &str) -> Result<GeneratedSources, String> {
+    let lockfile: serde_json::Value = match serde_json::from_str(content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Could not parse {}: {}.", NPM_LOCKFILE, e)),
+    };
+
+    let mut packages: Vec<(String, &serde_json::Value)> = vec![];
+    if let Some(lock_packages) = lockfile.get("packages").and_then(|p| p.as_object()) {
+        for (package_path, package) in lock_packages {
+            // The root package has an empty path.
+            if let Some((_, name)) = package_path.rsplit_once("node_modules/") {
+                packages.push((name.to_string(), package));
+            }
+        }
+    } else if let Some(dependencies) = lockfile.get("dependencies") {
+        get_npm_dependencies(dependencies, &mut packages);
+    }
+
+    let mut generated_sources = GeneratedSources::default();
+    let mut urls: BTreeSet<String> = BTreeSet::new();
+    for (name, package) in packages {
+        // The linked and the bundled packages are not downloaded.
+        let url = match package.get("resolved").and_then(|r| r.as_str()) {
+            Some(u) if u.starts_with("https://") || u.starts_with("http://") => u,
+            _ => continue,
+        };
+        if !urls.insert(url.to_string()) {
+            continue;
+        }
+        let version = package
+            .get("version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let integrity = package.get("integrity").and_then(|i| i.as_str());
+        match get_node_source(&name, version, url, integrity) {
+            Ok(s) => generated_sources.sources.push(s),
+            Err(e) => generated_sources.warnings.push(e),
+        };
+    }
+    Ok(generated_sources)
+}
+
+fn get_npm_dependencies<'a>(
+    dependencies: &'a serde_json::Value,
+    packages: &mut Vec<(String, &'a serde_json::Value)>,
+) {
+    let dependencies = match dependencies.as_object() {
+        Some(d) => d,
+        None => return,
+    };
+    for (name, package) in dependencies {
+        packages.push((name.to_string(), package));
+        if let Some(child_dependencies) = package.get("dependencies") {
+            get_npm_dependencies(child_dependencies, packages);
+        }
+    }
+}
+
+/// Generates a file source for the tarball of every package of a yarn.lock file.
+/// Only the lockfiles of yarn 1 are supported, the lockfiles of the later versions
+/// do not have the checksums of the tarballs.
+pub fn generate_yarn_sources(content: &str) -> Result<GeneratedSources, String> {
+    if content.contains("__metadata:") {
+        return Err(format!(
+            "Only the {} files of yarn 1 are supported.",
+            YARN_LOCKFILE
+        ));
+    }
+
+    let mut generated_sources = GeneratedSources::default();
+    let mut urls: BTreeSet<String> = BTreeSet::new();
+    let mut name = String::new();
+    let mut version = String::new();
+    let mut resolved = String::new();
+    let mut integrity: Option<String> = None;
+    // A blank line is added to process the last entry.
+    for line in content.lines().chain([""]) {
+        if line.trim().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') && !line.trim().is_empty() {
+            // The first pattern of the entry, like "@babel/core@^7.0.0". The name
+            // of a scoped package starts with @.
+            let pattern = line.split(',').next().unwrap_or("").trim_end_matches(':');
+            let pattern = pattern.trim().trim_matches('"');
+            name = match pattern.char_indices().skip(1).find(|(_, c)| *c == '@') {
+                Some((i, _)) => pattern[..i].to_string(),
+                None => pattern.to_string(),
+            };
+            continue;
+        }
+        if let Some((key, value)) = line.trim().split_once(' ') {
+            let value = value.trim().trim_matches('"').to_string();
+            match key {
+                "version" => version = value,
+                "resolved" => resolved = value,
+                "integrity" => integrity = Some(value),
+                _ => {}
+            };
+            continue;
+        }
+        if !line.trim().is_empty() || resolved.is_empty() {
+            continue;
+        }
+        if name.is_empty() {
+            generated_sources
+                .warnings
+                .push(format!("Skipping package {} without a name.", resolved));
+            resolved = String::new();
+            integrity = None;
+            continue;
+        }
+
+        // The sha1 of the tarball is in the fragment of the URL.
+        let (url, sha1) = match resolved.split_once('#') {
+            Some((u, s)) => (u.to_string(), Some(s.to_string())),
+            None => (resolved.to_string(), None),
+        };
+        if urls.insert(url.to_string()) {
+            let source = match (&integrity, sha1) {
+                (Some(i), _) => get_node_source(&name, &version, &url, Some(i)),
+                (None, Some(s)) => Ok(FlatpakSource {
+                    sha1: Some(s),
+                    ..get_node_file_source(&name, &version, &url)
+                }),
+                (None, None) => Err(format!(
+                    "Skipping package {} {} without a checksum.",
+                    name, version
+                )),
+            };
+            match source {
+                Ok(s) => generated_sources.sources.push(s),
+                Err(e) => generated_sources.warnings.push(e),
+            };
+        }
+        resolved = String::new();
+        integrity = None;
+    }
+    Ok(generated_sources)
+}
+
+/// Gets the source of an npm tarball, with the checksum of its subresource
+/// integrity, like `sha512-<base64>`.
+fn get_node_source(
+    name: &str,
+    version: &str,
+    url: &str,
+    integrity: Option<&str>,
+) -> Result<FlatpakSource, String> {
+    let integrity = match integrity {
+        Some(i) => i,
+        None => {
+            return Err(format!(
+                "Skipping package {} {} without an integrity checksum.",
+                name, version
+            ))
+        }
+    };
+    let (algorithm, checksum) = match integrity.split_once('-') {
+        Some((a, c)) => (a, decode_base64(c).map(|c| to_hex(&c))),
+        None => ("", None),
+    };
+    let mut source = get_node_file_source(name, version, url);
+    match (algorithm, checksum) {
+        ("sha512", Some(c)) => source.sha512 = Some(c),
+        ("sha256", Some(c)) => source.sha256 = Some(c),
+        ("sha1", Some(c)) => source.sha1 = Some(c),
+        _ => {
+            return Err(format!(
+                "Skipping package {} {} with the unsupported integrity {}.",
+                name, version, integrity
+            ))
+        }
+    };
+    Ok(source)
+}
+
+fn get_node_file_source(name: &str, version: &str, url: &str) -> FlatpakSource {
+    FlatpakSource {
+        r#type: Some(FlatpakSourceType::File),
+        url: Some(url.to_string()),
+        dest: Some(NODE_TARBALLS_DIR.to_string()),
+        dest_filename: Some(format!("{}-{}.tgz", name.replace('/', "-"), version)),
+        ..Default::default()
+    }
+}
+
+/// Generates a file source for the requirements of a requirements.txt file
+/// pinned with `--hash`. The requirements pinned to a version are downloaded as
+/// source archives from PyPI, so their checksum can only be known when they have a
+/// single hash. pip-compile lists the hashes of all the wheels and of the source
+/// archive, which cannot be told apart, so these requirements are skipped. The
+/// requirements can also use a direct URL, like `name @ https://...`.
+pub fn generate_pip_sources(content: &str) -> Result<GeneratedSources, String> {
+    let mut generated_sources = GeneratedSources::default();
+    // The requirements can continue on the next lines.
+    let content = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    for line in content.lines() {
+        // The fragments of the URLs, like #egg=name, are not comments.
+        let line = line.split(" #").next().unwrap_or(line).trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('-') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let requirement = words.next().unwrap_or("");
+        let mut hashes: Vec<String> = vec![];
+        let mut url: Option<String> = None;
+        for word in words {
+            if let Some(hash) = word.strip_prefix("--hash=sha256:") {
+                hashes.push(hash.to_string());
+            } else if word.starts_with("https://") || word.starts_with("http://") {
+                url = Some(word.split('#').next().unwrap_or(word).to_string());
+            }
+        }
+        let requirement = requirement.split(';').next().unwrap_or(requirement);
+
+        let url = match url {
+            Some(u) => u,
+            None => match requirement.split_once("==") {
+                Some((name, version)) => {
+                    let name = name.split('[').next().unwrap_or(name).trim();
+                    format!(
+                        "{}/{}/{}/{}-{}.tar.gz",
+                        PYPI_DOWNLOAD_URL,
+                        name.chars().next().unwrap_or('_'),
+                        name,
+                        get_sdist_name(name),
+                        version.trim()
+                    )
+                }
+                None => {
+                    generated_sources.warnings.push(format!(
+                        "Skipping requirement {}, which is not pinned to a version.",
+                        requirement
+                    ));
+                    continue;
+                }
+            },
+        };
+        if hashes.len() != 1 {
+            generated_sources.warnings.push(format!(
+                "Skipping requirement {}, which needs exactly one --hash=sha256 for its source archive, not {}. Use a direct URL for the requirements with several hashes.",
+                requirement,
+                hashes.len()
+            ));
+            continue;
+        }
+        generated_sources.sources.push(FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some(url),
+            sha256: Some(hashes[0].to_string()),
+            ..Default::default()
+        });
+    }
+    Ok(generated_sources)
+}
+
+/// Gets the name of a project in the file name of its source archive, which is
+/// normalized with underscores since PEP 625.
+fn get_sdist_name(name: &str) -> String {
+    let mut sdist_name = String::new();
+    for c in name.to_lowercase().chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !sdist_name.ends_with('_') {
+                sdist_name.push('_');
+            }
+            continue;
+        }
+        sdist_name.push(c);
+    }
+    sdist_name
+}
+
+/// Decodes standard base64, with or without padding.
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    let mut buffer: u32 = 0;
+    let mut bit_count = 0;
+    for c in encoded.trim_end_matches('=').chars() {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' => 62,
+            '/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((buffer >> bit_count) as u8);
+            buffer &= (1 << bit_count) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA512_INTEGRITY: &str = "sha512-XRcglhh3p2lHAu4gFg75i5owZ3/uttIZh11iKj+W2fqc4Iu8OvwIHkDSftaw4gURo0WA2fT2oguwTa4HChLwJw==";
+    const SHA512: &str = "5d1720961877a7694702ee20160ef98b9a30677feeb6d219875d622a3f96d9fa9ce08bbc3afc081e40d27ed6b0e20511a34580d9f4f6a20bb04dae070a12f027";
+    const SHA1: &str = "16c385a6cbd7c6ad06cd6a7195aafae4932fcf3d";
+    const SHA256: &str = "8b917c4b6163bc82ef4aff025c6f5f4d54205232c4595f39b7b43008256a6cb7";
+
+    #[test]
+    fn cargo_sources() {
+        let content = format!(
+            r#"version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["serde"]
+
+[[package]]
+name = "serde"
+version = "1.0.200"
+source = "{}"
+checksum = "{}"
+
+[[package]]
+name = "forked"
+version = "0.1.0"
+source = "git+https://example.org/forked.git#abc"
+"#,
+            CRATES_IO_REGISTRY, SHA256
+        );
+        let generated_sources = generate_cargo_sources(&content).unwrap();
+        assert_eq!(generated_sources.sources.len(), 2);
+        let source = &generated_sources.sources[0];
+        assert_eq!(
+            source.url.as_deref(),
+            Some("https://static.crates.io/crates/serde/serde-1.0.200.crate")
+        );
+        assert_eq!(source.sha256.as_deref(), Some(SHA256));
+        assert_eq!(source.dest.as_deref(), Some("cargo/vendor/serde-1.0.200"));
+
+        let commands = generated_sources.sources[1].commands.as_ref().unwrap();
+        assert!(commands[0].contains(SHA256));
+        assert!(commands[0].ends_with("> cargo/vendor/serde-1.0.200/.cargo-checksum.json"));
+        assert!(commands[1].ends_with("> cargo/config"));
+        assert_eq!(generated_sources.warnings.len(), 1);
+        assert!(generated_sources.warnings[0].starts_with("Skipping crate forked 0.1.0"));
+
+        assert!(generate_cargo_sources("[[package]").is_err());
+    }
+
+    #[test]
+    fn npm_sources_from_a_v3_lockfile() {
+        let content = format!(
+            r#"{{
+  "name": "app",
+  "lockfileVersion": 3,
+  "packages": {{
+    "": {{"name": "app", "version": "1.0.0"}},
+    "node_modules/left-pad": {{
+      "version": "1.3.0",
+      "resolved": "https://registry.npmjs.org/left-pad/-/left-pad-1.3.0.tgz",
+      "integrity": "{0}"
+    }},
+    "node_modules/@scope/pkg/node_modules/left-pad": {{
+      "version": "1.3.0",
+      "resolved": "https://registry.npmjs.org/left-pad/-/left-pad-1.3.0.tgz",
+      "integrity": "{0}"
+    }},
+    "node_modules/@scope/pkg": {{
+      "version": "2.0.0",
+      "resolved": "https://registry.npmjs.org/@scope/pkg/-/pkg-2.0.0.tgz"
+    }},
+    "node_modules/linked": {{"resolved": "../linked", "link": true}}
+  }}
+}}"#,
+            SHA512_INTEGRITY
+        );
+        let generated_sources = generate_npm_sources(&content).unwrap();
+        assert_eq!(generated_sources.sources.len(), 1);
+        let source = &generated_sources.sources[0];
+        assert_eq!(source.sha512.as_deref(), Some(SHA512));
+        assert_eq!(source.dest.as_deref(), Some(NODE_TARBALLS_DIR));
+        assert_eq!(source.dest_filename.as_deref(), Some("left-pad-1.3.0.tgz"));
+        assert_eq!(
+            generated_sources.warnings,
+            vec!["Skipping package @scope/pkg 2.0.0 without an integrity checksum."]
+        );
+    }
+
+    #[test]
+    fn npm_sources_from_a_v1_lockfile() {
+        let content = format!(
+            r#"{{
+  "name": "app",
+  "lockfileVersion": 1,
+  "dependencies": {{
+    "@scope/pkg": {{
+      "version": "2.0.0",
+      "resolved": "https://registry.npmjs.org/@scope/pkg/-/pkg-2.0.0.tgz",
+      "integrity": "sha1-FsOFpsvXxq0GzWpxlar65JMvzz0=",
+      "dependencies": {{
+        "left-pad": {{
+          "version": "1.3.0",
+          "resolved": "https://registry.npmjs.org/left-pad/-/left-pad-1.3.0.tgz",
+          "integrity": "{}"
+        }}
+      }}
+    }}
+  }}
+}}"#,
+            SHA512_INTEGRITY
+        );
+        let generated_sources = generate_npm_sources(&content).unwrap();
+        assert_eq!(generated_sources.sources.len(), 2);
+        assert_eq!(generated_sources.sources[0].sha1.as_deref(), Some(SHA1));
+        assert_eq!(
+            generated_sources.sources[0].dest_filename.as_deref(),
+            Some("@scope-pkg-2.0.0.tgz")
+        );
+        assert_eq!(generated_sources.sources[1].sha512.as_deref(), Some(SHA512));
+        assert!(generated_sources.warnings.is_empty());
+    }
+
+    #[test]
+    fn yarn_sources() {
+        let content = format!(
+            r#"# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@scope/pkg@^2.0.0", "@scope/pkg@^2.1.0":
+  version "2.1.0"
+  resolved "https://registry.yarnpkg.com/@scope/pkg/-/pkg-2.1.0.tgz#{}"
+
+left-pad@^1.3.0:
+  version "1.3.0"
+  resolved "https://registry.yarnpkg.com/left-pad/-/left-pad-1.3.0.tgz"
+  integrity {}
+
+"":
+  version "1.0.0"
+  resolved "https://registry.yarnpkg.com/empty/-/empty-1.0.0.tgz"
+
+"émoji@^1.0.0":
+  version "1.0.0"
+  resolved "https://registry.yarnpkg.com/émoji/-/émoji-1.0.0.tgz"
+"#,
+            SHA1, SHA512_INTEGRITY
+        );
+        let generated_sources = generate_yarn_sources(&content).unwrap();
+        assert_eq!(generated_sources.sources.len(), 2);
+        let source = &generated_sources.sources[0];
+        assert_eq!(
+            source.url.as_deref(),
+            Some("https://registry.yarnpkg.com/@scope/pkg/-/pkg-2.1.0.tgz")
+        );
+        assert_eq!(source.sha1.as_deref(), Some(SHA1));
+        assert_eq!(
+            source.dest_filename.as_deref(),
+            Some("@scope-pkg-2.1.0.tgz")
+        );
+        assert_eq!(generated_sources.sources[1].sha512.as_deref(), Some(SHA512));
+        assert_eq!(
+            generated_sources.warnings,
+            vec![
+                "Skipping package https://registry.yarnpkg.com/empty/-/empty-1.0.0.tgz without a name.",
+                "Skipping package émoji 1.0.0 without a checksum.",
+            ]
+        );
+
+        assert!(generate_yarn_sources("__metadata:\n  version: 6\n").is_err());
+    }
+
+    #[test]
+    fn pip_sources() {
+        let content = format!(
+            r#"# pip-compile --generate-hashes
+Flask-Login==0.6.3 \
+    --hash=sha256:{0}
+zope.interface==6.0 --hash=sha256:{0}
+requests==2.31.0 \
+    --hash=sha256:{0} \
+    --hash=sha256:{0}
+direct @ https://example.org/direct-1.0.tar.gz#egg=direct --hash=sha256:{0}
+unpinned>=1.0
+"#,
+            SHA256
+        );
+        let generated_sources = generate_pip_sources(&content).unwrap();
+        let urls: Vec<&str> = generated_sources
+            .sources
+            .iter()
+            .map(|s| s.url.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://files.pythonhosted.org/packages/source/F/Flask-Login/flask_login-0.6.3.tar.gz",
+                "https://files.pythonhosted.org/packages/source/z/zope.interface/zope_interface-6.0.tar.gz",
+                "https://example.org/direct-1.0.tar.gz",
+            ]
+        );
+        assert!(generated_sources
+            .sources
+            .iter()
+            .all(|s| s.sha256.as_deref() == Some(SHA256)));
+        assert_eq!(generated_sources.warnings.len(), 2);
+        assert!(generated_sources.warnings[0].starts_with("Skipping requirement requests==2.31.0"));
+        assert!(generated_sources.warnings[1].starts_with("Skipping requirement unpinned>=1.0"));
+    }
+}
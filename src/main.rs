@@ -2,7 +2,6 @@
 //! For a Flatpak library for Rust, see [flatpak-rs](https://crates.io/crates/flatpak-rs)
 //! To get the list of available commands, run `fpcli -h`.
 use std::env;
-use std::ffi::OsString;
 use std::fs;
 use std::path;
 
@@ -10,10 +9,29 @@ use clap::{AppSettings, Parser, Subcommand};
 use flatpak_rs::application::FlatpakApplication;
 use flatpak_rs::format::FlatpakManifestFormat;
 use flatpak_rs::manifest_type::FlatpakManifestType;
-use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+use flatpak_rs::module::{FlatpakBuildOptions, FlatpakModule, FlatpakModuleItem};
 use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
-
+use serde::Serialize;
+
+mod bootstrap;
+mod cache;
+mod config;
+mod detect;
+mod edit;
+mod fetch;
+mod git;
+mod install;
+mod json;
+mod lint;
+mod lockfile;
+mod manifest;
+mod query;
+mod resolve;
+mod split;
+mod tree;
 mod utils;
+mod wizard;
+mod yaml;
 
 /// A CLI app for Flatpak manifests.
 #[derive(Parser)]
@@ -27,14 +45,23 @@ struct Fpcli {
 
 #[derive(Subcommand)]
 enum SubCommand {
-    /// Formats a Flatpak manifest.
+    /// Formats a Flatpak manifest, and reports the problems found in the manifest.
+    /// Exits with an error if any problem has the error severity. YAML manifests
+    /// keep their comments and indentation, and are not formatted if their comments
+    /// cannot be kept.
     #[clap(setting(AppSettings::ArgRequiredElseHelp))]
     Lint {
-        /// The path of the manifest to lint.
-        path: String,
-        /// Only check the manifest for formatting issues.
+        /// The paths of the manifests to lint.
+        #[clap(required = true)]
+        paths: Vec<String>,
+        /// Only check the manifests for formatting issues, and print a diff of
+        /// the changes that would be made. Exits with an error if any manifest
+        /// is not formatted correctly.
         #[clap(long, short)]
         check: bool,
+        /// The output format of the problems found, either text, json or sarif.
+        #[clap(long, default_value = "text")]
+        output_format: String,
     },
     /// Install all the modules in a manifest. The manifest has to be an application
     /// manifest or a module manifest.
@@ -48,14 +75,51 @@ enum SubCommand {
         #[clap(long, short)]
         bare: bool,
 
-        /// Only print the install instructions to stdout.
+        /// Only print the install commands to stdout, as shell command lines
+        /// with their working directory and environment. Nothing is created.
         #[clap(long, short)]
         print: bool,
+
+        /// The SDK used to build the modules. Defaults to the SDK of the
+        /// application manifest.
+        #[clap(long)]
+        sdk: Option<String>,
+
+        /// The runtime used to build the modules. Defaults to the runtime of the
+        /// application manifest.
+        #[clap(long)]
+        runtime: Option<String>,
+
+        /// The version of the SDK and the runtime. Defaults to the runtime version
+        /// of the application manifest.
+        #[clap(long)]
+        runtime_version: Option<String>,
+
+        /// The install prefix of the modules, when installing on the host.
+        /// This can be overridden by the build options of the manifest.
+        #[clap(long, default_value = crate::install::DEFAULT_HOST_PREFIX)]
+        prefix: String,
+
+        /// The directory where the build sandbox, the build directories and the
+        /// logs are kept.
+        #[clap(long, default_value = crate::install::DEFAULT_STATE_DIR)]
+        state_dir: String,
     },
     /// List all the Flatpak manifests in a specific directory.
     Ls {
         /// The path of the directory to traverse.
+        #[clap(default_value = ".")]
         path: String,
+        /// Exclude the paths matching this glob pattern. The pattern uses the
+        /// .gitignore syntax, relative to the traversed directory.
+        #[clap(long, short)]
+        exclude: Vec<String>,
+        /// Do not use the rules from the .gitignore files.
+        #[clap(long)]
+        no_ignore: bool,
+        /// The output format, either text or json.
+        #[clap(long, short, default_value = "text")]
+        format: String,
     },
     /// Get all the urls contained in a manifest.
     #[clap(name = "get-urls")]
@@ -74,15 +138,23 @@ enum SubCommand {
         /// The path of the manifest parse
         path: String,
     },
-    /// Converts a manifest. The manifest must be a valid
-    /// Flatpak manifest.
+    /// Converts a manifest. The manifest must be a valid Flatpak application,
+    /// module or source manifest.
     #[clap(setting(AppSettings::ArgRequiredElseHelp))]
     Convert {
         /// The path of the manifest to convert.
         path: String,
-        /// The format to convert the manifest to.
+        /// The format to convert the manifest to, either json or yaml.
         #[clap(name = "format")]
         format_name: String,
+        /// Write the converted manifest to this path instead of stdout. The path
+        /// must not exist.
+        #[clap(long, short, conflicts_with = "in-place")]
+        output: Option<String>,
+        /// Replace the manifest with the converted manifest, changing the
+        /// extension of the file to match the new format.
+        #[clap(long, short)]
+        in_place: bool,
     },
     /// Parse a Flatpak manifest.
     #[clap(setting(AppSettings::ArgRequiredElseHelp))]
@@ -102,6 +174,65 @@ enum SubCommand {
         /// Inline the module instead of importing by path.
         #[clap(long, short)]
         inline: bool,
+        /// Add the module before the module with this name.
+        #[clap(long, conflicts_with = "after")]
+        before: Option<String>,
+        /// Add the module after the module with this name.
+        #[clap(long)]
+        after: Option<String>,
+        /// Add the module to the modules of the inline module with this name.
+        #[clap(long)]
+        parent: Option<String>,
+    },
+    /// Remove a module from a Flatpak manifest. The module can be an inline module
+    /// or a module imported by path, at any depth. Fails if several modules match.
+    #[clap(setting(AppSettings::ArgRequiredElseHelp))]
+    RemoveModule {
+        /// The path of the manifest to remove the module from.
+        manifest_path: String,
+        /// The name of the module to remove, or the path of an imported module.
+        module_name: String,
+    },
+    /// Move a module of a Flatpak manifest. The module can be an inline module
+    /// or a module imported by path, at any depth. Fails if several modules match.
+    #[clap(setting(AppSettings::ArgRequiredElseHelp))]
+    MoveModule {
+        /// The path of the manifest containing the module.
+        manifest_path: String,
+        /// The name of the module to move, or the path of an imported module.
+        module_name: String,
+        /// Move the module before the module with this name.
+        #[clap(long, conflicts_with = "after", required_unless_present_any = &["after", "into"])]
+        before: Option<String>,
+        /// Move the module after the module with this name.
+        #[clap(long)]
+        after: Option<String>,
+        /// Move the module to the modules of the inline module with this name.
+        /// With --before or --after, the other module must be in this module.
+        #[clap(long)]
+        into: Option<String>,
+    },
+    /// Print the value of a field of a manifest, with a path like
+    /// modules[name=glib].sources[0].tag. The strings are printed as is, and the
+    /// other values as JSON.
+    #[clap(setting(AppSettings::ArgRequiredElseHelp))]
+    Get {
+        /// The path of the manifest to query.
+        path: String,
+        /// The path of the field in the manifest.
+        query: String,
+    },
+    /// Set the value of a field of a manifest, with a path like
+    /// modules[name=glib].sources[0].tag. The value is parsed as JSON, unless the
+    /// field is a string.
+    #[clap(setting(AppSettings::ArgRequiredElseHelp))]
+    Set {
+        /// The path of the manifest to edit.
+        path: String,
+        /// The path of the field in the manifest.
+        query: String,
+        /// The new value of the field.
+        value: String,
     },
     /// Resolve all the imported manifests in a manifest file.
     #[clap(setting(AppSettings::ArgRequiredElseHelp))]
@@ -111,6 +242,29 @@ enum SubCommand {
         /// Only check that the manifests can be resolved.
         #[clap(long, short)]
         check: bool,
+        /// Write the resolved manifest to this path instead of replacing the
+        /// manifest, or to stdout with -. The local paths of the sources are
+        /// rebased on the directory of the output file.
+        #[clap(long, short, conflicts_with = "check")]
+        output: Option<String>,
+    },
+    /// Extract the inline modules of a manifest to their own module manifests,
+    /// imported by path. This is the inverse of resolve.
+    #[clap(setting(AppSettings::ArgRequiredElseHelp))]
+    Split {
+        /// The path of the manifest to split.
+        path: String,
+        /// The directory to write the module manifests to. Defaults to the
+        /// directory of the manifest.
+        #[clap(long, short)]
+        dir: Option<String>,
+        /// Do not extract the modules deeper than this. The modules of the
+        /// manifest have a depth of 0.
+        #[clap(long)]
+        max_depth: Option<i64>,
+        /// Only extract the modules with at least this number of lines.
+        #[clap(long, default_value = "0")]
+        min_lines: usize,
     },
     /// Converts a URL to its reverse DNS equivalent.
     #[clap(setting(AppSettings::ArgRequiredElseHelp))]
@@ -135,6 +289,9 @@ enum SubCommand {
         /// Do not print modules deeper than this
         #[clap(long, short)]
         max_depth: Option<i64>,
+        /// The output format, either text, json, dot or mermaid.
+        #[clap(long, short, default_value = "text")]
+        format: String,
     },
     /// Creates a new manifest from the available information.
     Bootstrap {
@@ -143,40 +300,137 @@ enum SubCommand {
         #[clap(long, short)]
         manifest_type: Option<String>,
 
-        /// A build system, in the case of an application or a module.
+        /// A build system, in the case of an application or a module. One of
+        /// autotools, cmake, cmake-ninja, meson, qmake, simple, cargo, python, node
+        /// or go.
         build_system: Option<String>,
 
         /// A url to bootstrap from.
         #[clap(long, short)]
         url: Option<String>,
+
+        /// The branch to build, for a git URL. Defaults to the default branch
+        /// of the local clone given with --from, or of the remote.
+        #[clap(long, short)]
+        branch: Option<String>,
+
+        /// The tag to build, for a git URL.
+        #[clap(long, short, conflicts_with = "branch")]
+        tag: Option<String>,
+
+        /// The commit to build, for a git URL.
+        #[clap(long, short)]
+        commit: Option<String>,
+
+        /// A local source tree to bootstrap from. The build system, the name and
+        /// the runtime of the project are detected from its files, and the app ID
+        /// from its git remote. The sources of the dependencies are generated from
+        /// the lockfiles of the source tree, or from the given lockfile for a source
+        /// manifest. For an application or a module manifest, they are written to a
+        /// source manifest next to the manifest, like cargo-sources.json. The
+        /// requirements of a requirements.txt file need a direct URL, or a version
+        /// pinned with a single --hash for its source archive.
+        #[clap(long)]
+        from: Option<String>,
+
+        /// Ask for the app ID, the runtime, the permissions and the first module
+        /// of the application.
+        #[clap(long, short)]
+        interactive: bool,
+
+        /// The path of the manifest to write, or - to print it. Defaults to the
+        /// ID of the application or the name of the module, with a yaml extension.
+        #[clap(long, short)]
+        output: Option<String>,
+    },
+    /// Download the sources of a manifest into the download cache, and verify
+    /// their checksums.
+    #[clap(setting(AppSettings::ArgRequiredElseHelp))]
+    Fetch {
+        /// The path of the manifest to fetch the sources of.
+        path: String,
+    },
+    /// Inspect and prune the download cache.
+    Cache {
+        #[clap(subcommand)]
+        command: CacheSubCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheSubCommand {
+    /// List the files in the download cache.
+    Ls,
+    /// Remove the interrupted downloads and the corrupted files from the download
+    /// cache. When paths are given, the files not used by the manifests at these
+    /// paths are also removed.
+    Gc {
+        /// The paths of the manifests, or of the directories containing the manifests,
+        /// for which the cached files are kept.
+        paths: Vec<String>,
     },
+    /// Remove all the files from the download cache.
+    Clear,
 }
 
 fn main() -> std::process::ExitCode {
     let args = Fpcli::parse();
 
     match &args.command {
-        SubCommand::Ls { path } => {
-            // FIXME right now we don't use the path parameter.
-            for file_path in crate::utils::get_all_paths(path::Path::new(".")).unwrap() {
-                if !file_path.is_file() {
-                    continue;
+        SubCommand::Ls {
+            path,
+            exclude,
+            no_ignore,
+            format,
+        } => {
+            if format != LS_FORMAT_TEXT && format != LS_FORMAT_JSON {
+                eprintln!("Invalid output format {}.", format);
+                return std::process::ExitCode::FAILURE;
+            }
+
+            let dir_path = path::Path::new(path);
+            if !dir_path.is_dir() {
+                eprintln!("{} is not a directory.", path);
+                return std::process::ExitCode::FAILURE;
+            }
+
+            let mut ignore_rules = crate::utils::IgnoreRules::default();
+            if let Err(e) = ignore_rules.add_patterns(dir_path, exclude) {
+                eprintln!("{}", e);
+                return std::process::ExitCode::FAILURE;
+            }
+
+            let file_paths = match crate::utils::get_all_paths(dir_path, &ignore_rules, !no_ignore)
+            {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("Could not traverse directory {}: {}.", path, e);
+                    return std::process::ExitCode::FAILURE;
                 }
+            };
 
+            let mut manifests: Vec<ManifestEntry> = vec![];
+            for file_path in file_paths {
                 let file_path = match file_path.to_str() {
                     Some(f) => f,
                     None => continue,
                 };
 
-                if file_path.contains(".git/") {
-                    continue;
+                if let Some(manifest_type) = crate::manifest::get_manifest_type(file_path) {
+                    manifests.push(ManifestEntry {
+                        path: file_path.to_string(),
+                        r#type: manifest_type.to_string(),
+                    });
                 }
+            }
 
-                if let Ok(flatpak_application) =
-                    FlatpakApplication::load_from_file(file_path.to_string())
-                {
-                    println!("Flatpak application at {}.", &file_path);
-                }
+            if format == LS_FORMAT_JSON {
+                println!("{}", serde_json::to_string_pretty(&manifests).unwrap());
+                return std::process::ExitCode::SUCCESS;
+            }
+
+            for manifest in manifests {
+                println!("Flatpak {} at {}.", manifest.r#type, manifest.path);
             }
         }
         SubCommand::GetUrls {
@@ -228,180 +482,425 @@ fn main() -> std::process::ExitCode {
                 eprintln!("{} is not a file.", path);
                 return std::process::ExitCode::FAILURE;
             }
-            if FlatpakApplication::load_from_file(path.to_string()).is_ok() {
-                println!("application");
+            // TODO should we differentiate with 1 source VS multiple sources?
+            if let Some(manifest_type) = crate::manifest::get_manifest_type(path) {
+                println!("{}", manifest_type.to_string());
                 return std::process::ExitCode::SUCCESS;
+            }
+            eprintln!("{} is not a Flatpak manifest.", path);
+            return std::process::ExitCode::SUCCESS;
+        }
+        SubCommand::Convert {
+            path,
+            format_name,
+            output,
+            in_place,
+        } => {
+            let (format, format_extension) = match get_format_from_name(format_name) {
+                Some(f) => f,
+                None => {
+                    eprintln!(
+                        "Invalid format {}. The valid formats are: {}.",
+                        format_name,
+                        VALID_FORMAT_NAMES.join(", ")
+                    );
+                    return std::process::ExitCode::FAILURE;
+                }
             };
-            if FlatpakModule::load_from_file(path.to_string()).is_ok() {
-                println!("module");
-                return std::process::ExitCode::SUCCESS;
+
+            let manifest_dump = match crate::manifest::dump_manifest(path, Some(format)) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
             };
-            if FlatpakSource::load_from_file(path.to_string()).is_ok() {
-                // TODO should we differentiate with 1 source VS multiple sources?
-                println!("source");
+
+            let output_path = if *in_place {
+                let manifest_path = path::Path::new(path);
+                let current_format = manifest_path
+                    .extension()
+                    .and_then(|e| get_format_from_name(&e.to_string_lossy()));
+                match current_format {
+                    // The extension already matches the format, e.g. .yml for yaml.
+                    Some((_, extension)) if extension == format_extension => {
+                        manifest_path.to_path_buf()
+                    }
+                    _ => manifest_path.with_extension(format_extension),
+                }
+            } else if let Some(output) = output {
+                path::PathBuf::from(output)
+            } else {
+                println!("{}", manifest_dump);
                 return std::process::ExitCode::SUCCESS;
             };
-            eprintln!("{} is not a Flatpak manifest.", path);
-            return std::process::ExitCode::SUCCESS;
+
+            // Only an in-place conversion that keeps the extension replaces a file.
+            let is_renamed = *in_place && output_path != path::Path::new(path);
+            if (is_renamed || output.is_some()) && output_path.exists() {
+                eprintln!("{} already exists.", output_path.display());
+                return std::process::ExitCode::FAILURE;
+            }
+            if let Err(e) = fs::write(&output_path, manifest_dump) {
+                eprintln!("Could not write file {}: {}.", output_path.display(), e);
+                return std::process::ExitCode::FAILURE;
+            }
+            if is_renamed {
+                if let Err(e) = fs::remove_file(path) {
+                    eprintln!("Could not remove file {}: {}.", path, e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+            eprintln!("Converted {} to {}.", path, output_path.display());
         }
-        SubCommand::Convert { path, format_name } => {
-            // TODO we should also try to parse the file as a module manifest or as a source manifest!
-            let mut flatpak_application = match FlatpakApplication::load_from_file(path.to_string())
-            {
+        SubCommand::Install {
+            path,
+            bare,
+            print,
+            sdk,
+            runtime,
+            runtime_version,
+            prefix,
+            state_dir,
+        } => {
+            let mut module_items: Vec<FlatpakModuleItem> = vec![];
+            let mut app_build_options: Option<FlatpakBuildOptions> = None;
+            let mut sandbox_config = crate::install::SandboxConfig {
+                app_id: DEFAULT_INSTALL_APP_ID.to_string(),
+                sdk: "".to_string(),
+                runtime: "".to_string(),
+                runtime_version: "".to_string(),
+            };
+
+            if let Ok(mut flatpak_app) = FlatpakApplication::load_from_file(path.to_string()) {
+                if let Err(errors) = crate::resolve::resolve_application(path, &mut flatpak_app) {
+                    for error in errors {
+                        eprintln!("{}", error);
+                    }
+                    return std::process::ExitCode::FAILURE;
+                }
+                sandbox_config = crate::install::SandboxConfig::from_application(&flatpak_app);
+                app_build_options = flatpak_app.build_options;
+                module_items = flatpak_app.modules;
+            } else if let Ok(mut flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
+                if let Err(errors) = crate::resolve::resolve_module(path, &mut flatpak_module) {
+                    for error in errors {
+                        eprintln!("{}", error);
+                    }
+                    return std::process::ExitCode::FAILURE;
+                }
+                module_items = vec![FlatpakModuleItem::Description(flatpak_module)];
+            } else {
+                eprintln!(
+                    "{} is not a Flatpak application manifest or a Flatpak module manifest.",
+                    path
+                );
+                return std::process::ExitCode::FAILURE;
+            }
+
+            if module_items.is_empty() {
+                eprintln!("No module to install in {}.", path);
+                return std::process::ExitCode::FAILURE;
+            }
+
+            let modules = match crate::install::get_build_order(&module_items) {
                 Ok(m) => m,
                 Err(e) => {
-                    eprintln!("Could not parse manifest file at {}: {}.", path, e);
+                    eprintln!("{}", e);
                     return std::process::ExitCode::FAILURE;
                 }
             };
 
-            // This is not optimal. Maybe we need a standalone function for that.
-            let format = match FlatpakManifestFormat::from_path(format_name) {
-                Some(f) => f,
-                None => panic!("Invalid destination format {}.", format_name),
+            // Printing the commands must not leave anything behind.
+            let state_dir = match crate::install::get_state_dir(state_dir, !*print) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
             };
+            let app_dir = crate::install::get_app_dir(&state_dir);
 
-            flatpak_application.format = format;
-
-            let application_dump = match flatpak_application.dump() {
+            let cache_dir = match crate::cache::get_downloads_cache_dir() {
                 Ok(d) => d,
                 Err(e) => {
-                    eprintln!("Could not dump manifest: {}.", e);
+                    eprintln!("{}", e);
                     return std::process::ExitCode::FAILURE;
                 }
             };
-            println!("{}", application_dump);
-        }
-        SubCommand::Install { path, bare, print } => {
-            let mut modules: Vec<FlatpakModule> = vec![];
+            let base_dir = match get_absolute_manifest_dir(path) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let fetch_config = crate::fetch::FetchConfig {
+                base_dir,
+                tmp_dir: crate::install::get_tmp_dir(&state_dir),
+                cache_dir,
+                sandbox_app_dir: if *bare { None } else { Some(app_dir.clone()) },
+            };
 
-            if let Ok(mut flatpak_app) = FlatpakApplication::load_from_file(path.to_string()) {
-                resolve_application(path, &mut flatpak_app);
+            if *bare {
+                for module in modules {
+                    eprintln!("Installing module {}", module.name);
+                    let build_options = crate::install::get_module_build_options(
+                        app_build_options.as_ref(),
+                        &module,
+                    );
 
-                for module in flatpak_app.get_all_modules_recursively() {
-                    let module_description = match module {
-                        FlatpakModuleItem::Path(_) => {
-                            panic!("There should be no module defined as path after resolving.")
+                    if *print {
+                        let build_dir =
+                            match crate::install::get_module_build_dir(&state_dir, &module) {
+                                Ok(d) => d,
+                                Err(e) => {
+                                    eprintln!("{}", e);
+                                    return std::process::ExitCode::FAILURE;
+                                }
+                            };
+                        let commands = match crate::install::get_host_commands(
+                            &module,
+                            &build_options,
+                            &build_dir,
+                            prefix,
+                        ) {
+                            Ok(c) => c,
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                return std::process::ExitCode::FAILURE;
+                            }
+                        };
+                        for command in commands {
+                            println!("{}", crate::install::command_to_shell_line(&command));
                         }
-                        FlatpakModuleItem::Description(d) => d,
-                    };
-                    modules.push(module_description.clone());
+                        continue;
+                    }
+
+                    if let Err(e) = crate::install::install_module_on_host(
+                        &module,
+                        &build_options,
+                        &state_dir,
+                        prefix,
+                        &fetch_config,
+                    ) {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
                 }
+                return std::process::ExitCode::SUCCESS;
             }
 
-            if let Ok(flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
-                let mut resolved_modules =
-                    resolve_modules(path, &vec![FlatpakModuleItem::Description(flatpak_module)]);
-
-                for module in resolved_modules {
-                    let module_description = match module {
-                        FlatpakModuleItem::Path(_) => {
-                            panic!("There should be no module defined as path after resolving.")
+            if let Some(sdk) = sdk {
+                sandbox_config.sdk = sdk.to_string();
+            }
+            if let Some(runtime) = runtime {
+                sandbox_config.runtime = runtime.to_string();
+            }
+            if let Some(runtime_version) = runtime_version {
+                sandbox_config.runtime_version = runtime_version.to_string();
+            }
+            if *print {
+                for module in modules {
+                    eprintln!("Installing module {}", module.name);
+                    let build_options = crate::install::get_module_build_options(
+                        app_build_options.as_ref(),
+                        &module,
+                    );
+                    let build_dir = match crate::install::get_module_build_dir(&state_dir, &module)
+                    {
+                        Ok(d) => d,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return std::process::ExitCode::FAILURE;
                         }
-                        FlatpakModuleItem::Description(d) => d,
                     };
-                    modules.push(module_description.to_owned());
-                    for inner_module in module_description.get_all_modules_recursively() {
-                        let inner_module_description = match inner_module {
-                            FlatpakModuleItem::Path(_) => {
-                                panic!("There should be no module defined as path after resolving.")
-                            }
-                            FlatpakModuleItem::Description(d) => d,
-                        };
-                        modules.push(inner_module_description.to_owned());
+                    let commands = match crate::install::get_sandbox_commands(
+                        &module,
+                        &build_options,
+                        &build_dir,
+                        &app_dir,
+                    ) {
+                        Ok(c) => c,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return std::process::ExitCode::FAILURE;
+                        }
+                    };
+                    for command in commands {
+                        println!("{}", crate::install::command_to_shell_line(&command));
                     }
                 }
+                return std::process::ExitCode::SUCCESS;
+            }
+
+            // The sandbox is only needed when the modules are actually installed.
+            if sandbox_config.sdk.is_empty()
+                || sandbox_config.runtime.is_empty()
+                || sandbox_config.runtime_version.is_empty()
+            {
+                eprintln!(
+                    "The SDK, runtime and runtime version are required to install the modules in a sandbox. Use --sdk, --runtime and --runtime-version."
+                );
+                return std::process::ExitCode::FAILURE;
             }
 
-            if !bare || !print {
-                panic!("Only --print and --bare is supported at the moment.");
+            if let Err(e) = crate::install::init_sandbox(&sandbox_config, &app_dir) {
+                eprintln!("{}", e);
+                return std::process::ExitCode::FAILURE;
             }
 
             for module in modules {
-                let mut args: Vec<OsString> = vec![];
-
                 eprintln!("Installing module {}", module.name);
-                for command in module.get_commands(args, true, "", "", Some(""), 0) {
-                    print!("{}", command.get_program().to_str().unwrap());
-                    for arg in command.get_args() {
-                        print!(" {}", arg.to_str().unwrap());
-                    }
-                    println!("");
+                let build_options =
+                    crate::install::get_module_build_options(app_build_options.as_ref(), &module);
+                if let Err(e) = crate::install::install_module_in_sandbox(
+                    &module,
+                    &build_options,
+                    &state_dir,
+                    &fetch_config,
+                ) {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
                 }
             }
+            return std::process::ExitCode::SUCCESS;
         }
-        SubCommand::Lint { path, check } => {
-            if let Ok(flatpak_application) = FlatpakApplication::load_from_file(path.to_string()) {
-                let initial_content = match fs::read_to_string(path) {
+        SubCommand::Lint {
+            paths,
+            check,
+            output_format,
+        } => {
+            if output_format != LINT_OUTPUT_FORMAT_TEXT
+                && output_format != LINT_OUTPUT_FORMAT_JSON
+                && output_format != LINT_OUTPUT_FORMAT_SARIF
+            {
+                eprintln!("Invalid output format {}.", output_format);
+                return std::process::ExitCode::FAILURE;
+            }
+
+            let mut has_errors = false;
+            let mut all_diagnostics: Vec<crate::lint::Diagnostic> = vec![];
+            for path in paths {
+                let (initial_content, formatted_content) =
+                    match crate::manifest::get_formatted_manifest(path) {
+                        Ok(c) => c,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            has_errors = true;
+                            continue;
+                        }
+                    };
+
+                let project_config = match crate::config::get_project_config(path) {
                     Ok(c) => c,
                     Err(e) => {
-                        eprintln!("Could not read file {}: {}!", &path, e);
-                        return std::process::ExitCode::FAILURE;
+                        eprintln!("{}", e);
+                        has_errors = true;
+                        continue;
                     }
                 };
+                let lint_config = match &project_config {
+                    Some(c) => c.config.lint.clone(),
+                    None => crate::config::LintConfig::default(),
+                };
 
-                let application_dump = match flatpak_application.dump() {
+                let mut diagnostics = match get_lint_diagnostics(path, &lint_config) {
                     Ok(d) => d,
                     Err(e) => {
-                        eprintln!("Could not dump manifest: {}.", e);
-                        return std::process::ExitCode::FAILURE;
+                        eprintln!("{}", e);
+                        has_errors = true;
+                        continue;
                     }
                 };
-
-                if *check {
-                    if application_dump == initial_content {
-                        println!("The file is formatted correctly.");
-                        return std::process::ExitCode::FAILURE;
-                    } else {
-                        panic!("There are formatting issues with the file.");
-                    }
+                let is_formatted = initial_content == formatted_content;
+                if *check && !is_formatted {
+                    diagnostics.push(crate::lint::Diagnostic::new(
+                        crate::lint::FORMATTING,
+                        "The manifest is not formatted correctly.".to_string(),
+                        "".to_string(),
+                    ));
+                }
+                crate::lint::locate_diagnostics(&mut diagnostics, path, &initial_content);
+                if let Some(FlatpakManifestFormat::YAML) = FlatpakManifestFormat::from_path(path) {
+                    diagnostics =
+                        crate::lint::apply_suppression_comments(diagnostics, &initial_content);
+                }
+                if let Some(project_config) = &project_config {
+                    diagnostics = crate::lint::apply_config(diagnostics, project_config, path);
                 }
 
-                if let Err(e) = fs::write(path::Path::new(&path), application_dump) {
-                    panic!("could not write file {}: {}.", path, e);
-                };
-
-                return std::process::ExitCode::SUCCESS;
-            }
-
-            if let Ok(flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
-                let initial_content = match fs::read_to_string(path) {
-                    Ok(c) => c,
-                    Err(e) => {
-                        eprintln!("Could not read file {}: {}!", &path, e);
-                        return std::process::ExitCode::FAILURE;
+                for diagnostic in &diagnostics {
+                    if diagnostic.severity == crate::lint::Severity::Error {
+                        has_errors = true;
                     }
-                };
-
-                let module_dump = match flatpak_module.dump() {
-                    Ok(d) => d,
-                    Err(e) => {
-                        eprintln!("Could not dump manifest: {}.", e);
-                        return std::process::ExitCode::FAILURE;
+                    if output_format != LINT_OUTPUT_FORMAT_TEXT {
+                        continue;
                     }
-                };
-
-                if *check {
-                    if module_dump == initial_content {
-                        println!("The file is formatted correctly.");
+                    let location = match (diagnostic.line, diagnostic.column) {
+                        (Some(line), Some(column)) => format!("{}:{}:{}", path, line, column),
+                        _ => path.to_string(),
+                    };
+                    let pointer = match diagnostic.pointer.as_str() {
+                        "" => "".to_string(),
+                        p => format!(" ({})", p),
+                    };
+                    let mut output = format!(
+                        "{}: {} [{}] {}{}\n",
+                        location,
+                        diagnostic.severity,
+                        diagnostic.rule_id,
+                        diagnostic.message,
+                        pointer
+                    );
+                    if diagnostic.rule_id == crate::lint::FORMATTING {
+                        output += &crate::utils::get_unified_diff(
+                            path,
+                            &initial_content,
+                            &formatted_content,
+                        );
+                    }
+                    if let Err(e) = crate::utils::write_stdout(&output) {
+                        eprintln!("{}", e);
                         return std::process::ExitCode::FAILURE;
-                    } else {
-                        panic!("There are formatting issues with the file.");
                     }
                 }
+                all_diagnostics.append(&mut diagnostics);
 
-                if let Err(e) = fs::write(path::Path::new(&path), module_dump) {
-                    panic!("could not write file {}: {}.", path, e);
+                if *check || is_formatted {
+                    continue;
+                }
+                if let Err(e) = fs::write(path::Path::new(&path), formatted_content) {
+                    eprintln!("Could not write file {}: {}.", path, e);
+                    has_errors = true;
+                    continue;
                 };
-
-                return std::process::ExitCode::SUCCESS;
+                eprintln!("Formatted {}.", path);
             }
 
-            if let Ok(flatpak_sources) = FlatpakSource::load_from_file(path.to_string()) {
-                panic!("Lint Flatpak source manifests is not yet supported :(");
+            let output = if output_format == LINT_OUTPUT_FORMAT_JSON {
+                serde_json::to_string_pretty(&all_diagnostics)
+            } else if output_format == LINT_OUTPUT_FORMAT_SARIF {
+                serde_json::to_string_pretty(&crate::lint::get_sarif_log(&all_diagnostics))
+            } else {
+                Ok("".to_string())
+            };
+            let output = match output {
+                Ok(o) if o.is_empty() => o,
+                Ok(o) => o + "\n",
+                Err(e) => {
+                    eprintln!("Could not serialize the diagnostics: {}.", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            if let Err(e) = crate::utils::write_stdout(&output) {
+                eprintln!("{}", e);
+                return std::process::ExitCode::FAILURE;
             }
 
-            panic!("Could not parse Flatpak manifest at {}.", path);
+            if has_errors {
+                return std::process::ExitCode::FAILURE;
+            }
         }
         SubCommand::Parse { path } => {
             match FlatpakApplication::load_from_file(path.to_string()) {
@@ -446,289 +945,1019 @@ fn main() -> std::process::ExitCode {
             manifest_path,
             module_path,
             inline,
+            before,
+            after,
+            parent,
         } => {
-            if *inline {
-                panic!("The inline option is not implemented yet!");
-            }
-            match FlatpakApplication::load_from_file(manifest_path.to_string()) {
-                Ok(mut app) => {
-                    println!("Parsed Flatpak application manifest for {}.", app.get_id());
-
-                    app.modules
-                        .push(FlatpakModuleItem::Path(manifest_path.to_string()));
-
-                    let app_dump = app.dump().unwrap();
-                    if let Err(e) = fs::write(path::Path::new(&manifest_path), app_dump) {
-                        eprintln!("could not write file {}: {}.", &manifest_path, e);
-                        return std::process::ExitCode::FAILURE;
-                    }
-
-                    return std::process::ExitCode::SUCCESS;
-                }
-                Err(e) => {
-                    eprintln!(
-                        "Could not parse Flatpak application from {}: {}.",
-                        manifest_path, e
-                    );
-                }
-            };
-
-            match FlatpakModule::load_from_file(manifest_path.to_string()) {
-                Ok(mut module) => {
-                    module
-                        .modules
-                        .push(FlatpakModuleItem::Path(manifest_path.to_string()));
-
-                    println!("Parsed Flatpak module manifest for {}.", module.name);
-                    return std::process::ExitCode::SUCCESS;
-                }
+            let mut flatpak_module = match FlatpakModule::load_from_file(module_path.to_string()) {
+                Ok(m) => m,
                 Err(e) => {
                     eprintln!(
                         "Could not parse Flatpak module from {}: {}.",
-                        manifest_path, e
+                        module_path, e
                     );
-                }
-            };
-
-            eprintln!("Could not parse Flatpak manifest at {}", manifest_path);
-            return std::process::ExitCode::FAILURE;
-        }
-        SubCommand::Resolve { path, check } => {
-            // TODO we should also try to parse the file as a module manifest here.
-            let mut flatpak_application = match FlatpakApplication::load_from_file(path.to_string())
-            {
-                Ok(m) => m,
-                Err(e) => {
-                    eprintln!("Could not parse manifest file at {}: {}.", path, e);
                     return std::process::ExitCode::FAILURE;
                 }
             };
+            let module_name = flatpak_module.name.to_string();
 
-            resolve_application(path, &mut flatpak_application);
+            // The paths in the module are relative to the module manifest, and have to
+            // be relative to the target manifest.
+            let manifest_dir = get_manifest_dir(manifest_path);
+            let relative_dir =
+                match crate::resolve::get_relative_dir(manifest_dir, get_manifest_dir(module_path))
+                {
+                    Ok(d) => d,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+            // The inline module is copied from its manifest, so it keeps its comments.
+            let mut module_content: Option<String> = None;
+            let module_item = if *inline {
+                let initial_module = flatpak_module.clone();
+                crate::resolve::rebase_module(&relative_dir, &mut flatpak_module);
+                module_content = match crate::edit::get_module_content(
+                    module_path,
+                    &initial_module,
+                    &flatpak_module,
+                ) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        eprintln!(
+                            "Could not preserve the formatting of module {}: {}",
+                            module_name, e
+                        );
+                        None
+                    }
+                };
+                FlatpakModuleItem::Description(flatpak_module)
+            } else {
+                let module_file_name = path::Path::new(module_path).file_name().unwrap();
+                FlatpakModuleItem::Path(
+                    relative_dir
+                        .join(module_file_name)
+                        .to_string_lossy()
+                        .to_string(),
+                )
+            };
+            let position = crate::edit::ModulePosition {
+                parent: parent.clone(),
+                before: before.clone(),
+                after: after.clone(),
+            };
 
-            if *check {
-                return std::process::ExitCode::SUCCESS;
+            let mut module_pointer: Option<String> = None;
+            let mut content =
+                match crate::manifest::get_edited_manifest_modules(manifest_path, |module_items| {
+                    let initial_module_items = module_items.clone();
+                    let index = crate::edit::insert_module(
+                        manifest_dir,
+                        module_items,
+                        module_item,
+                        &position,
+                    )?;
+                    module_pointer = crate::edit::get_edited_modules_pointer(
+                        &initial_module_items,
+                        module_items,
+                        "/modules",
+                    )
+                    .map(|p| crate::yaml::get_child_pointer(&p, &index.to_string()));
+                    Ok(())
+                }) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+            if let (Some(module_content), Some(module_pointer), Some(FlatpakManifestFormat::YAML)) = (
+                module_content,
+                module_pointer,
+                FlatpakManifestFormat::from_path(manifest_path),
+            ) {
+                match crate::yaml::replace_item_content(&content, &module_pointer, &module_content)
+                {
+                    Ok(c) => content = c,
+                    Err(e) => eprintln!(
+                        "Could not preserve the formatting of module {}: {}",
+                        module_name, e
+                    ),
+                };
             }
-
-            let application_dump = flatpak_application.dump().unwrap();
-            if let Err(e) = fs::write(path::Path::new(&path), application_dump) {
-                eprintln!("could not write file {}: {}.", &path, e);
+            if let Err(e) = fs::write(path::Path::new(manifest_path), content) {
+                eprintln!("Could not write file {}: {}.", manifest_path, e);
                 return std::process::ExitCode::FAILURE;
-            };
-
-            return std::process::ExitCode::SUCCESS;
+            }
+            eprintln!("Added module {} to {}.", module_name, manifest_path);
         }
-        SubCommand::Tree {
+        SubCommand::RemoveModule {
+            manifest_path,
+            module_name,
+        } => {
+            let manifest_dir = get_manifest_dir(manifest_path);
+            if let Err(e) = crate::manifest::edit_manifest_modules(manifest_path, |module_items| {
+                crate::edit::remove_module(manifest_dir, module_items, module_name).map(|_| ())
+            }) {
+                eprintln!("{}", e);
+                return std::process::ExitCode::FAILURE;
+            }
+            eprintln!("Removed module {} from {}.", module_name, manifest_path);
+        }
+        SubCommand::MoveModule {
+            manifest_path,
+            module_name,
+            before,
+            after,
+            into,
+        } => {
+            let manifest_dir = get_manifest_dir(manifest_path);
+            let position = crate::edit::ModulePosition {
+                parent: into.clone(),
+                before: before.clone(),
+                after: after.clone(),
+            };
+            if let Err(e) = crate::manifest::edit_manifest_modules(manifest_path, |module_items| {
+                crate::edit::move_module(manifest_dir, module_items, module_name, &position)
+            }) {
+                eprintln!("{}", e);
+                return std::process::ExitCode::FAILURE;
+            }
+            eprintln!("Moved module {} in {}.", module_name, manifest_path);
+        }
+        SubCommand::Get { path, query } => {
+            let manifest_value = match crate::query::get_manifest_value(path) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let pointer = match crate::query::get_pointer(&manifest_value, query, false) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let value = match manifest_value.pointer(&pointer) {
+                Some(v) => v,
+                None => {
+                    eprintln!("Could not find {} in {}.", query, path);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            println!("{}", crate::query::format_value(value));
+        }
+        SubCommand::Set { path, query, value } => {
+            let mut manifest_value = match crate::query::get_manifest_value(path) {
+                Ok(v) => v,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let pointer = match crate::query::get_pointer(&manifest_value, query, true) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let new_value = crate::query::parse_value(value, manifest_value.pointer(&pointer));
+
+            if let Err(e) = crate::query::set_value(&mut manifest_value, &pointer, new_value) {
+                eprintln!("Could not set {} in {}: {}", query, path, e);
+                return std::process::ExitCode::FAILURE;
+            }
+
+            if let Err(e) = crate::query::set_manifest_value(path, &manifest_value, &pointer) {
+                eprintln!("Could not set {} in {}: {}", query, path, e);
+                return std::process::ExitCode::FAILURE;
+            }
+        }
+        SubCommand::Resolve {
             path,
-            resolve,
-            max_depth,
+            check,
+            output,
         } => {
-            // TODO we should also try to parse the file as a module manifest here.
-            let mut flatpak_application = match FlatpakApplication::load_from_file(path.to_string())
+            let output_path = match output {
+                Some(o) if o == STDOUT_PATH => None,
+                Some(o) => Some(o.to_string()),
+                None => Some(path.to_string()),
+            };
+            let relative_dir = match &output_path {
+                Some(o) => match crate::resolve::get_relative_dir(
+                    get_manifest_dir(o),
+                    get_manifest_dir(path),
+                ) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                },
+                None => path::PathBuf::new(),
+            };
+
+            let manifest_content = if let Ok(mut flatpak_application) =
+                FlatpakApplication::load_from_file(path.to_string())
             {
-                Ok(m) => m,
+                let initial_application = flatpak_application.clone();
+                if let Err(errors) =
+                    crate::resolve::resolve_application(path, &mut flatpak_application)
+                {
+                    for error in errors {
+                        eprintln!("{}", error);
+                    }
+                    return std::process::ExitCode::FAILURE;
+                }
+                eprintln!("Resolved modules for {}.", flatpak_application.get_id());
+                if *check {
+                    return std::process::ExitCode::SUCCESS;
+                }
+
+                crate::resolve::rebase_modules(&relative_dir, &mut flatpak_application.modules);
+                let application_dump = flatpak_application.dump().unwrap();
+                crate::manifest::get_edited_manifest(
+                    path,
+                    &initial_application,
+                    &flatpak_application,
+                    application_dump,
+                )
+            } else {
+                let mut flatpak_module = match FlatpakModule::load_from_file(path.to_string()) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        eprintln!("Could not parse manifest file at {}: {}.", path, e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+                let initial_module = flatpak_module.clone();
+                if let Err(errors) = crate::resolve::resolve_module(path, &mut flatpak_module) {
+                    for error in errors {
+                        eprintln!("{}", error);
+                    }
+                    return std::process::ExitCode::FAILURE;
+                }
+                eprintln!("Resolved modules for {}.", flatpak_module.name);
+                if *check {
+                    return std::process::ExitCode::SUCCESS;
+                }
+
+                crate::resolve::rebase_module(&relative_dir, &mut flatpak_module);
+                let module_dump = flatpak_module.dump().unwrap();
+                crate::manifest::get_edited_manifest(
+                    path,
+                    &initial_module,
+                    &flatpak_module,
+                    module_dump,
+                )
+            };
+            let manifest_content = match manifest_content {
+                Ok(c) => c,
                 Err(e) => {
-                    eprintln!("Could not parse manifest file at {}: {}.", path, e);
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+
+            let output_path = match output_path {
+                Some(o) => o,
+                None => {
+                    print!("{}", manifest_content);
+                    if !manifest_content.ends_with('\n') {
+                        println!();
+                    }
+                    return std::process::ExitCode::SUCCESS;
+                }
+            };
+            if let Err(e) = fs::write(path::Path::new(&output_path), manifest_content) {
+                eprintln!("Could not write file {}: {}.", output_path, e);
+                return std::process::ExitCode::FAILURE;
+            }
+        }
+        SubCommand::Split {
+            path,
+            dir,
+            max_depth,
+            min_lines,
+        } => {
+            let manifest_dir = get_manifest_dir(path);
+            let extension = match path::Path::new(path).extension() {
+                Some(e) => e.to_string_lossy().to_string(),
+                None => "".to_string(),
+            };
+            let format = match get_format_from_name(&extension) {
+                Some((f, _)) => f,
+                None => {
+                    eprintln!("Could not get the format of {}.", path);
                     return std::process::ExitCode::FAILURE;
                 }
             };
 
-            if *resolve {
-                resolve_application(path, &mut flatpak_application);
+            let output_dir = match dir {
+                Some(d) => {
+                    if let Err(e) = fs::create_dir_all(d) {
+                        eprintln!("Could not create directory {}: {}.", d, e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                    match crate::resolve::get_relative_dir(manifest_dir, path::Path::new(d)) {
+                        Ok(d) => d,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return std::process::ExitCode::FAILURE;
+                        }
+                    }
+                }
+                None => path::PathBuf::new(),
+            };
+            let split_config = crate::split::SplitConfig {
+                output_dir,
+                format,
+                extension,
+                max_depth: *max_depth,
+                min_lines: *min_lines,
+            };
+
+            let module_files = if let Ok(mut flatpak_application) =
+                FlatpakApplication::load_from_file(path.to_string())
+            {
+                let initial_application = flatpak_application.clone();
+                let module_files = match crate::split::split_modules(
+                    path,
+                    &mut flatpak_application.modules,
+                    &split_config,
+                ) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+                if let Err(e) = write_module_files(&module_files) {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+                let application_dump = flatpak_application.dump().unwrap();
+                if let Err(e) = crate::manifest::write_manifest(
+                    path,
+                    &initial_application,
+                    &flatpak_application,
+                    application_dump,
+                ) {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                };
+                module_files
+            } else {
+                let mut flatpak_module = match FlatpakModule::load_from_file(path.to_string()) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        eprintln!("Could not parse manifest file at {}: {}.", path, e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+                let initial_module = flatpak_module.clone();
+                let module_files = match crate::split::split_modules(
+                    path,
+                    &mut flatpak_module.modules,
+                    &split_config,
+                ) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+                if let Err(e) = write_module_files(&module_files) {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+                let module_dump = flatpak_module.dump().unwrap();
+                if let Err(e) = crate::manifest::write_manifest(
+                    path,
+                    &initial_module,
+                    &flatpak_module,
+                    module_dump,
+                ) {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                };
+                module_files
+            };
+
+            if module_files.is_empty() {
+                eprintln!("No module to extract from {}.", path);
+            }
+            for module_file in module_files {
+                eprintln!(
+                    "Extracted module {} to {}.",
+                    module_file.module_name,
+                    module_file.path.display()
+                );
+            }
+        }
+        SubCommand::Tree {
+            path,
+            resolve,
+            max_depth,
+            format,
+        } => {
+            if !crate::tree::TREE_FORMATS.contains(&format.as_str()) {
+                eprintln!("Invalid output format {}.", format);
+                return std::process::ExitCode::FAILURE;
             }
 
-            // TODO add a maximum depth option.
+            let module_tree =
+                match crate::tree::get_module_tree(path, *resolve, max_depth.unwrap_or(1000)) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
 
-            println!("{}", flatpak_application.get_id());
-            print_modules(&flatpak_application.modules, 0, max_depth.unwrap_or(1000));
+            let tree_output = match format.as_str() {
+                crate::tree::TREE_FORMAT_JSON => {
+                    serde_json::to_string_pretty(&module_tree).unwrap()
+                }
+                crate::tree::TREE_FORMAT_DOT => crate::tree::format_dot(&module_tree),
+                crate::tree::TREE_FORMAT_MERMAID => crate::tree::format_mermaid(&module_tree),
+                _ => crate::tree::format_text(&module_tree),
+            };
+            println!("{}", tree_output);
         }
         SubCommand::Bootstrap {
             manifest_type,
             build_system,
             url,
+            branch,
+            tag,
+            commit,
+            from,
+            interactive,
+            output,
         } => {
-            if let Some(manifest_type) = manifest_type {
-                let manifest_type = match FlatpakManifestType::from_string(&manifest_type) {
+            let git_ref = crate::git::GitRef {
+                branch: branch.clone(),
+                tag: tag.clone(),
+                commit: commit.clone(),
+            };
+            let manifest_type = match manifest_type {
+                Some(manifest_type) => match FlatpakManifestType::from_string(manifest_type) {
                     Ok(t) => t,
-                    Err(e) => {
+                    Err(_) => {
                         eprintln!("Invalid manifest type {:?}.", manifest_type);
                         return std::process::ExitCode::FAILURE;
                     }
+                },
+                None => FlatpakManifestType::Application,
+            };
+
+            if let FlatpakManifestType::Source = manifest_type {
+                if *interactive {
+                    eprintln!("Only application manifests can be bootstrapped interactively.");
+                    return std::process::ExitCode::FAILURE;
+                }
+                let (mut sources, file_name) = match crate::bootstrap::get_bootstrapped_sources(
+                    url.clone(),
+                    &git_ref,
+                    from.as_deref(),
+                ) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
                 };
+                let output_path = output.clone().unwrap_or(file_name);
+                if let Some(from) = from {
+                    for source in sources.iter_mut() {
+                        if source.get_type() != Some(FlatpakSourceType::Dir) {
+                            continue;
+                        }
+                        *source = match crate::bootstrap::get_local_source(&output_path, from) {
+                            Ok(s) => s,
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                return std::process::ExitCode::FAILURE;
+                            }
+                        };
+                    }
+                }
+
+                let format = FlatpakManifestFormat::from_path(&output_path)
+                    .unwrap_or(FlatpakManifestFormat::JSON);
+                let manifest_dump = if sources.len() == 1 {
+                    format.dump(&sources[0])
+                } else {
+                    format.dump(&sources)
+                };
+                let manifest_dump = match manifest_dump {
+                    Ok(d) => d,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+                if let Err(e) = crate::bootstrap::write_new_manifest(&output_path, manifest_dump) {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+                return std::process::ExitCode::SUCCESS;
+            }
+
+            let project = match from {
+                Some(from) => match crate::detect::detect_project(path::Path::new(from)) {
+                    Ok(p) => Some(p),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                },
+                None => None,
+            };
+            if let Some(project) = &project {
+                eprintln!(
+                    "Detected project {} using {}, with the {} runtime.",
+                    project.name,
+                    project
+                        .build_system
+                        .as_deref()
+                        .unwrap_or("an unknown build system"),
+                    project.runtime.name
+                );
+            }
+            let app_id = match project.as_ref().and_then(|p| p.app_id.clone()) {
+                Some(i) => i,
+                None => crate::bootstrap::DEFAULT_APP_ID.to_string(),
+            };
+            let runtime = match &project {
+                Some(p) => p.runtime,
+                None => &crate::bootstrap::GNOME_RUNTIME,
+            };
+            let mut application_settings =
+                crate::bootstrap::ApplicationSettings::new(&app_id, runtime);
+            let mut module_settings = crate::bootstrap::ModuleSettings {
+                name: match (&project, url) {
+                    (Some(p), None) => Some(p.name.to_string()),
+                    _ => None,
+                },
+                build_system: build_system
+                    .clone()
+                    .or_else(|| project.as_ref().and_then(|p| p.build_system.clone())),
+                url: url.clone(),
+                git_ref,
+            };
+
+            if *interactive {
+                if !matches!(manifest_type, FlatpakManifestType::Application) {
+                    eprintln!("Only application manifests can be bootstrapped interactively.");
+                    return std::process::ExitCode::FAILURE;
+                }
+                if let Err(e) =
+                    crate::wizard::ask_settings(&mut application_settings, &mut module_settings)
+                {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
 
-                match manifest_type {
+            if let Some(url) = &module_settings.url {
+                module_settings.git_ref = crate::git::get_git_ref(
+                    &module_settings.git_ref,
+                    url,
+                    from.as_deref().map(path::Path::new),
+                );
+            }
+            let mut default_module = match crate::bootstrap::get_default_module(
+                module_settings.url.clone(),
+                &module_settings.git_ref,
+            ) {
+                Ok(m) => m,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            if let Some(name) = &module_settings.name {
+                default_module.name = name.to_string();
+            }
+            if let Some(build_system) = &module_settings.build_system {
+                if let Err(e) =
+                    crate::bootstrap::apply_build_system(&mut default_module, build_system)
+                {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+
+            let output_path = match output {
+                Some(o) => o.to_string(),
+                None => match manifest_type {
                     FlatpakManifestType::Application => {
-                        let mut flatpak_application = FlatpakApplication::default();
-                        flatpak_application.format = FlatpakManifestFormat::YAML;
-                        flatpak_application.id = "org.example.appName".to_string();
-                        flatpak_application.runtime = "org.gnome.Platform".to_string();
-                        flatpak_application.runtime_version = "41".to_string();
-                        flatpak_application.sdk = "org.gnome.Sdk".to_string();
-
-                        flatpak_application
-                            .finish_args
-                            .push("--filesystem=home".to_string());
-                        flatpak_application
-                            .finish_args
-                            .push("--socket=x11".to_string());
-                        flatpak_application
-                            .finish_args
-                            .push("--socket=wayland".to_string());
-
-                        let mut default_module = get_default_module(url.to_owned());
-                        flatpak_application
-                            .modules
-                            .push(FlatpakModuleItem::Description(default_module));
-
-                        println!("{}", flatpak_application.dump().unwrap());
-                    }
-                    FlatpakManifestType::Module => {
-                        let mut default_module = get_default_module(url.to_owned());
-                        println!("{}", default_module.dump().unwrap());
-                    }
-                    FlatpakManifestType::Source => {
-                        // let mut default_source = get_default_source(url.to_owned());
-                        // println!("{}", default_source.dump().unwrap());
-                        eprintln!("Bootstrapping a source manifest is not supported yet.");
+                        format!("{}.yaml", application_settings.app_id)
+                    }
+                    _ => format!("{}.yaml", default_module.name.replace('/', "-")),
+                },
+            };
+
+            // The sources of the dependencies listed in the lockfiles of the source
+            // tree are written to a source manifest next to the manifest.
+            let mut dependency_sources: Option<(String, String)> = None;
+            if let (Some(from), None) = (from, &module_settings.url) {
+                match crate::bootstrap::get_local_source(&output_path, from) {
+                    Ok(source) => {
+                        default_module.sources = vec![FlatpakSourceItem::Description(source)]
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                };
+                if !crate::lockfile::find_lockfiles(path::Path::new(from)).is_empty() {
+                    let (sources, file_name) = match crate::bootstrap::get_bootstrapped_sources(
+                        None,
+                        &crate::git::GitRef::default(),
+                        Some(from),
+                    ) {
+                        Ok(s) => s,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return std::process::ExitCode::FAILURE;
+                        }
+                    };
+                    let sources_path = path::Path::new(&output_path)
+                        .with_file_name(&file_name)
+                        .to_string_lossy()
+                        .to_string();
+                    if path::Path::new(&sources_path).exists() {
+                        eprintln!("{} already exists.", sources_path);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                    let sources_dump = match FlatpakManifestFormat::JSON.dump(&sources) {
+                        Ok(d) => d,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return std::process::ExitCode::FAILURE;
+                        }
+                    };
+                    default_module
+                        .sources
+                        .push(FlatpakSourceItem::Path(file_name));
+                    dependency_sources = Some((sources_path, sources_dump));
+                }
+            }
+            let format = FlatpakManifestFormat::from_path(&output_path)
+                .unwrap_or(FlatpakManifestFormat::YAML);
+
+            let manifest_dump = match manifest_type {
+                FlatpakManifestType::Application => {
+                    let mut flatpak_application = crate::bootstrap::get_application(
+                        &application_settings,
+                        default_module,
+                        module_settings.build_system.as_deref(),
+                    );
+                    flatpak_application.format = format;
+                    flatpak_application.dump()
+                }
+                FlatpakManifestType::Module => {
+                    default_module.format = format;
+                    default_module.dump()
+                }
+                FlatpakManifestType::Source => unreachable!(),
+            };
+            let manifest_dump = match manifest_dump {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            if let Err(e) = crate::bootstrap::write_new_manifest(&output_path, manifest_dump) {
+                eprintln!("{}", e);
+                return std::process::ExitCode::FAILURE;
+            }
+            if let Some((sources_path, sources_dump)) = dependency_sources {
+                if let Err(e) = crate::bootstrap::write_new_manifest(&sources_path, sources_dump) {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+        }
+        SubCommand::Fetch { path } => {
+            let sources = match get_manifest_sources(path) {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+
+            let cache_dir = match crate::cache::get_downloads_cache_dir() {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let base_dir = match get_absolute_manifest_dir(path) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let fetch_config = crate::fetch::FetchConfig {
+                base_dir,
+                tmp_dir: env::temp_dir(),
+                cache_dir: cache_dir.clone(),
+                sandbox_app_dir: None,
+            };
+
+            let mut has_errors = false;
+            for source in sources {
+                let source_type = source.get_type();
+                if source_type != Some(FlatpakSourceType::Archive)
+                    && source_type != Some(FlatpakSourceType::File)
+                {
+                    continue;
+                }
+                let url = match &source.url {
+                    Some(u) => u,
+                    None => continue,
+                };
+
+                let is_cached = match crate::cache::get_cache_key(&source) {
+                    Ok(Some(k)) => crate::cache::get_cached_file(&cache_dir, &k).is_some(),
+                    _ => false,
+                };
+                match crate::fetch::get_source_file(&source, &fetch_config) {
+                    Ok(_) => {
+                        if is_cached {
+                            println!("{} is already cached.", url);
+                        } else {
+                            println!("Downloaded {}.", url);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Could not fetch {}: {}", url, e);
+                        has_errors = true;
                     }
                 };
             }
+
+            if has_errors {
+                return std::process::ExitCode::FAILURE;
+            }
+        }
+        SubCommand::Cache { command } => {
+            let cache_dir = match crate::cache::get_downloads_cache_dir() {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            let entries = match crate::cache::get_entries(&cache_dir) {
+                Ok(e) => e,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+
+            match command {
+                CacheSubCommand::Ls => {
+                    let mut total_size: u64 = 0;
+                    for entry in &entries {
+                        let partial_marker = if entry.is_partial { " (partial)" } else { "" };
+                        println!(
+                            "{}  {}{}",
+                            entry.key,
+                            crate::cache::format_size(entry.size),
+                            partial_marker
+                        );
+                        total_size += entry.size;
+                    }
+                    println!(
+                        "{} file(s) in {}, {} in total.",
+                        entries.len(),
+                        cache_dir.display(),
+                        crate::cache::format_size(total_size)
+                    );
+                }
+                CacheSubCommand::Gc { paths } => {
+                    let mut used_keys: Option<Vec<String>> = None;
+                    if !paths.is_empty() {
+                        match get_used_cache_keys(paths) {
+                            Ok(k) => used_keys = Some(k),
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                return std::process::ExitCode::FAILURE;
+                            }
+                        };
+                    }
+
+                    let mut freed_size: u64 = 0;
+                    for entry in entries {
+                        let reason = if entry.is_partial {
+                            "interrupted download"
+                        } else if crate::cache::verify_cache_entry(&entry.key, &entry.path).is_err()
+                        {
+                            "corrupted"
+                        } else if !used_keys.as_ref().map_or(true, |k| k.contains(&entry.key)) {
+                            "unused"
+                        } else {
+                            continue;
+                        };
+
+                        if let Err(e) = fs::remove_file(&entry.path) {
+                            eprintln!("Could not remove {}: {}.", entry.path.display(), e);
+                            return std::process::ExitCode::FAILURE;
+                        }
+                        println!("Removed {} ({}).", entry.key, reason);
+                        freed_size += entry.size;
+                    }
+                    println!("Freed {}.", crate::cache::format_size(freed_size));
+                }
+                CacheSubCommand::Clear => {
+                    let mut freed_size: u64 = 0;
+                    for entry in &entries {
+                        if let Err(e) = fs::remove_file(&entry.path) {
+                            eprintln!("Could not remove {}: {}.", entry.path.display(), e);
+                            return std::process::ExitCode::FAILURE;
+                        }
+                        freed_size += entry.size;
+                    }
+                    println!(
+                        "Removed {} file(s), freed {}.",
+                        entries.len(),
+                        crate::cache::format_size(freed_size)
+                    );
+                }
+            };
         }
     };
     std::process::ExitCode::SUCCESS
 }
 
-pub fn resolve_application(path: &str, application: &mut FlatpakApplication) {
-    let mut new_base_path = match path::Path::new(path).parent() {
-        Some(b) => b.to_str().unwrap(),
-        None => "",
-    };
-    application.modules = resolve_modules(new_base_path, &application.modules);
-    println!("Resolved modules for {}.", application.get_id());
-    // FIXME we should actually also resolve the imported sources here.
+pub const LS_FORMAT_TEXT: &str = "text";
+pub const LS_FORMAT_JSON: &str = "json";
+
+pub const VALID_FORMAT_NAMES: [&str; 2] = ["json", "yaml"];
+
+pub const LINT_OUTPUT_FORMAT_TEXT: &str = "text";
+pub const LINT_OUTPUT_FORMAT_JSON: &str = "json";
+pub const LINT_OUTPUT_FORMAT_SARIF: &str = "sarif";
+
+#[derive(Serialize)]
+/// A manifest found while traversing a directory.
+pub struct ManifestEntry {
+    pub path: String,
+    pub r#type: String,
 }
 
-pub fn print_modules(module_items: &Vec<FlatpakModuleItem>, depth: i64, max_depth: i64) {
-    if depth > max_depth {
-        return;
+pub fn write_module_files(module_files: &[crate::split::ModuleFile]) -> Result<(), String> {
+    for module_file in module_files {
+        if let Err(e) = fs::write(&module_file.path, &module_file.content) {
+            return Err(format!(
+                "Could not write file {}: {}.",
+                module_file.path.display(),
+                e
+            ));
+        }
     }
+    Ok(())
+}
 
-    let mut indent = "".to_string();
-    for n in 0..depth {
-        indent = format!("{}{}", indent, "  ");
+/// Gets a manifest format, and the file extension to use, from its name.
+pub fn get_format_from_name(format_name: &str) -> Option<(FlatpakManifestFormat, &'static str)> {
+    match format_name.to_lowercase().as_str() {
+        "json" => Some((FlatpakManifestFormat::JSON, "json")),
+        "yaml" | "yml" => Some((FlatpakManifestFormat::YAML, "yaml")),
+        _ => None,
     }
+}
 
-    for module in module_items {
-        match module {
-            FlatpakModuleItem::Description(m) => {
-                println!("{}↪ {}", indent, m.name);
-                print_modules(&m.modules, depth + 1, max_depth);
-            }
-            FlatpakModuleItem::Path(p) => {
-                println!("{}↪ {}", indent, p);
-            }
-        }
+/// Gets the problems found by the lint rules in a manifest.
+pub fn get_lint_diagnostics(
+    path: &str,
+    lint_config: &crate::config::LintConfig,
+) -> Result<Vec<crate::lint::Diagnostic>, String> {
+    if let Ok(flatpak_application) = FlatpakApplication::load_from_file(path.to_string()) {
+        return Ok(crate::lint::lint_application(
+            &flatpak_application,
+            lint_config,
+        ));
+    }
+    if let Ok(flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
+        return Ok(crate::lint::lint_module(&flatpak_module));
+    }
+    if let Ok((flatpak_sources, is_single_source)) = crate::manifest::load_source_manifest(path) {
+        return Ok(crate::lint::lint_sources(
+            &flatpak_sources,
+            is_single_source,
+        ));
     }
+    Err(format!("Could not parse Flatpak manifest at {}.", path))
 }
 
-pub fn resolve_modules(
-    base_path: &str,
-    module_items: &Vec<FlatpakModuleItem>,
-) -> Vec<FlatpakModuleItem> {
-    let mut response: Vec<FlatpakModuleItem> = vec![];
-    for module_item in module_items {
-        match module_item {
-            FlatpakModuleItem::Path(p) => {
-                let mut new_base_path = match path::Path::new(&p).parent() {
-                    Some(b) => b.to_str().unwrap(),
-                    None => "",
-                };
-                let full_file_path = format!("{}/{}", base_path, p);
-                let mut module = FlatpakModule::load_from_file(full_file_path).unwrap();
-                module.modules = resolve_modules(new_base_path, &module.modules);
-                response.push(FlatpakModuleItem::Description(module));
-            }
-            FlatpakModuleItem::Description(m) => {
-                let mut module = m.clone();
-                module.modules = resolve_modules(base_path, &module.modules);
-                response.push(FlatpakModuleItem::Description(module));
-            }
-        };
+/// Gets the directory of a manifest, relative to which the paths in the manifest
+/// are resolved.
+pub fn get_manifest_dir(path: &str) -> &path::Path {
+    match path::Path::new(path).parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => path::Path::new("."),
     }
-    response
 }
 
-pub const DEFAULT_GIT_BRANCH: &str = "master";
+/// Gets the absolute path of the directory of a manifest, relative to which its
+/// local sources are resolved.
+pub fn get_absolute_manifest_dir(path: &str) -> Result<path::PathBuf, String> {
+    match fs::canonicalize(get_manifest_dir(path)) {
+        Ok(d) => Ok(d),
+        Err(e) => Err(format!(
+            "Could not get the directory of manifest {}: {}.",
+            path, e
+        )),
+    }
+}
 
-pub fn get_default_source(url: Option<String>) -> FlatpakSource {
-    let mut default_source = FlatpakSource::default();
+/// Gets all the sources of a manifest, including the sources of the imported
+/// modules and the imported sources.
+pub fn get_manifest_sources(path: &str) -> Result<Vec<FlatpakSource>, String> {
+    let base_dir = get_manifest_dir(path);
 
-    if let Some(url) = url {
-        if url.ends_with(".git") {
-            default_source.r#type = Some(FlatpakSourceType::Git);
-            default_source.branch = Some(DEFAULT_GIT_BRANCH.to_string());
+    let module_items =
+        if let Ok(mut flatpak_app) = FlatpakApplication::load_from_file(path.to_string()) {
+            if let Err(errors) = crate::resolve::resolve_application(path, &mut flatpak_app) {
+                return Err(errors.join("\n"));
+            }
+            flatpak_app.modules
+        } else if let Ok(mut flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
+            if let Err(errors) = crate::resolve::resolve_module(path, &mut flatpak_module) {
+                return Err(errors.join("\n"));
+            }
+            vec![FlatpakModuleItem::Description(flatpak_module)]
+        } else if let Ok(flatpak_sources) = FlatpakSource::load_from_file(path.to_string()) {
+            return Ok(flatpak_sources);
         } else {
-            default_source.r#type = Some(FlatpakSourceType::Archive);
+            return Err(format!("{} is not a Flatpak manifest.", path));
+        };
+
+    let mut sources: Vec<FlatpakSource> = vec![];
+    for module_item in &module_items {
+        if let FlatpakModuleItem::Description(module) = module_item {
+            sources.append(&mut crate::fetch::get_module_sources(module, base_dir)?);
+            for child_module_item in module.get_all_modules_recursively() {
+                if let FlatpakModuleItem::Description(child_module) = child_module_item {
+                    sources.append(&mut crate::fetch::get_module_sources(
+                        child_module,
+                        base_dir,
+                    )?);
+                }
+            }
         }
-        default_source.url = Some(url.clone());
-    } else {
-        default_source.r#type = Some(FlatpakSourceType::Dir);
-        default_source.path = Some("./".to_string());
     }
-    default_source
+    Ok(sources)
 }
 
-pub fn get_default_module(url: Option<String>) -> FlatpakModule {
-    let mut default_module = FlatpakModule::default();
-    let default_source = get_default_source(url);
-    default_module
-        .sources
-        .push(FlatpakSourceItem::Description(default_source.clone()));
-
-    if default_source.url.is_none() {
-        return default_module;
-    }
-
-    if default_source.get_type() == Some(FlatpakSourceType::Git) {
-        if let Some(project_name) =
-            get_project_name_from_git_url(default_source.url.as_ref().unwrap().to_string())
-        {
-            default_module.name = format!("{}.{}", project_name, DEFAULT_GIT_BRANCH);
-        } else {
-            default_module.name = format!("project-name.{}", DEFAULT_GIT_BRANCH);
+/// Gets the cache keys of all the sources used by the manifests at `paths`.
+/// The paths can be manifests or directories containing manifests.
+pub fn get_used_cache_keys(paths: &[String]) -> Result<Vec<String>, String> {
+    let mut manifest_paths: Vec<String> = vec![];
+    for path in paths {
+        let dir_path = path::Path::new(path);
+        if !dir_path.is_dir() {
+            manifest_paths.push(path.to_string());
+            continue;
         }
-    } else if default_source.get_type() == Some(FlatpakSourceType::Archive) {
-        if let Some(project_name) =
-            flatpak_rs::archive::get_project_name_from_url(default_source.url.as_ref().unwrap())
-        {
-            default_module.name = format!("{}.archive", project_name);
-        } else {
-            default_module.name = format!("project-name.{}", DEFAULT_GIT_BRANCH);
+        let ignore_rules = crate::utils::IgnoreRules::default();
+        for file_path in crate::utils::get_all_paths(dir_path, &ignore_rules, true)? {
+            let file_path = file_path.to_string_lossy().to_string();
+            if crate::manifest::get_manifest_type(&file_path).is_some() {
+                manifest_paths.push(file_path);
+            }
         }
     }
-    default_module
-}
 
-///```
-///let project_name = crate::main::get_project_name_from_git_url(
-///  "https://github.com/louib/flatpak-rs.git"
-///);
-///assert!(project_name.is_some());
-///assert_eq!(project_name.unwrap(), "flatpak-rs");
-///
-///let project_name = crate::main::get_project_name_from_git_url(
-///  "git@github.com:louib/flatpak-rs.git"
-///);
-///assert!(project_name.is_some());
-///assert_eq!(project_name.unwrap(), "flatpak-rs");
-///```
-pub fn get_project_name_from_git_url(url: String) -> Option<String> {
-    if !url.ends_with(".git") {
-        return None;
+    let mut used_keys: Vec<String> = vec![];
+    for manifest_path in manifest_paths {
+        for source in get_manifest_sources(&manifest_path)? {
+            if let Some(key) = crate::cache::get_cache_key(&source)? {
+                used_keys.push(key);
+            }
+        }
     }
+    Ok(used_keys)
+}
 
-    let url = url.replace(".git", "");
-    let project_name = url.split("/").last().unwrap();
+/// The output path used to write to stdout.
+pub const STDOUT_PATH: &str = "-";
 
-    Some(project_name.to_string())
-}
+/// The application ID of the build sandbox, when installing the modules of a
+/// module manifest.
+pub const DEFAULT_INSTALL_APP_ID: &str = "org.flatpak.fpcli.Install";
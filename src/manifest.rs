@@ -0,0 +1,194 @@
+//! Loads, dumps and writes the manifests of any type: the application manifests,
+//! the module manifests and the source manifests. YAML manifests keep their
+//! comments when they are formatted or edited.
+use std::fs;
+use std::path;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::format::FlatpakManifestFormat;
+use flatpak_rs::manifest_type::FlatpakManifestType;
+use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+use flatpak_rs::source::FlatpakSource;
+use serde::Serialize;
+
+/// Gets the type of the Flatpak manifest at `path`, or `None` if the file
+/// is not a Flatpak manifest.
+pub fn get_manifest_type(path: &str) -> Option<FlatpakManifestType> {
+    if FlatpakApplication::load_from_file(path.to_string()).is_ok() {
+        return Some(FlatpakManifestType::Application);
+    }
+    if FlatpakModule::load_from_file(path.to_string()).is_ok() {
+        return Some(FlatpakManifestType::Module);
+    }
+    if FlatpakSource::load_from_file(path.to_string()).is_ok() {
+        return Some(FlatpakManifestType::Source);
+    }
+    None
+}
+
+/// Gets the current content of a manifest, and its content once formatted.
+pub fn get_formatted_manifest(path: &str) -> Result<(String, String), String> {
+    let mut formatted_content = dump_manifest(path, None)?;
+
+    let initial_content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read file {}: {}!", path, e)),
+    };
+
+    // The comments are kept when formatting YAML manifests, and the manifest
+    // is not formatted if they cannot be kept.
+    if let Some(FlatpakManifestFormat::YAML) = FlatpakManifestFormat::from_path(path) {
+        if crate::yaml::has_anchors(&initial_content) {
+            eprintln!(
+                "Not formatting {}, since its anchors and aliases would be lost.",
+                path
+            );
+            return Ok((initial_content.clone(), initial_content));
+        }
+        formatted_content =
+            match crate::yaml::reattach_comments(&initial_content, &formatted_content) {
+                Ok(c) => c,
+                Err(e) => {
+                    return Err(format!(
+                        "Could not format {} without losing its comments: {}",
+                        path, e
+                    ))
+                }
+            };
+    }
+
+    Ok((initial_content, formatted_content))
+}
+
+/// Edits the modules of an application or module manifest, and writes the manifest.
+pub fn edit_manifest_modules<F>(path: &str, edit: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Vec<FlatpakModuleItem>) -> Result<(), String>,
+{
+    let content = get_edited_manifest_modules(path, edit)?;
+    if let Err(e) = fs::write(path::Path::new(path), content) {
+        return Err(format!("Could not write file {}: {}.", path, e));
+    }
+    Ok(())
+}
+
+/// Gets the content of a manifest once its modules are edited.
+pub fn get_edited_manifest_modules<F>(path: &str, edit: F) -> Result<String, String>
+where
+    F: FnOnce(&mut Vec<FlatpakModuleItem>) -> Result<(), String>,
+{
+    if let Ok(mut flatpak_application) = FlatpakApplication::load_from_file(path.to_string()) {
+        let initial_application = flatpak_application.clone();
+        edit(&mut flatpak_application.modules)?;
+        let application_dump = flatpak_application.dump()?;
+        return get_edited_manifest(
+            path,
+            &initial_application,
+            &flatpak_application,
+            application_dump,
+        );
+    }
+    if let Ok(mut flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
+        let initial_module = flatpak_module.clone();
+        edit(&mut flatpak_module.modules)?;
+        let module_dump = flatpak_module.dump()?;
+        return get_edited_manifest(path, &initial_module, &flatpak_module, module_dump);
+    }
+    Err(format!(
+        "{} is not a Flatpak application manifest or a Flatpak module manifest.",
+        path
+    ))
+}
+
+/// Writes a manifest after it was edited.
+pub fn write_manifest<T: Serialize>(
+    path: &str,
+    initial_manifest: &T,
+    manifest: &T,
+    manifest_dump: String,
+) -> Result<(), String> {
+    let content = get_edited_manifest(path, initial_manifest, manifest, manifest_dump)?;
+    if let Err(e) = fs::write(path::Path::new(path), content) {
+        return Err(format!("Could not write file {}: {}.", path, e));
+    }
+    Ok(())
+}
+
+/// Gets the content of a manifest after it was edited. YAML manifests are edited in
+/// place, so only the nodes that changed are rewritten, and the comments are kept.
+pub fn get_edited_manifest<T: Serialize>(
+    path: &str,
+    initial_manifest: &T,
+    manifest: &T,
+    manifest_dump: String,
+) -> Result<String, String> {
+    let mut content = manifest_dump;
+    if let Some(FlatpakManifestFormat::YAML) = FlatpakManifestFormat::from_path(path) {
+        let initial_content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read file {}: {}!", path, e)),
+        };
+        let initial_value = serde_yaml::to_value(initial_manifest).map_err(|e| e.to_string())?;
+        let value = serde_yaml::to_value(manifest).map_err(|e| e.to_string())?;
+        content = match crate::yaml::update_content(&initial_content, &initial_value, &value) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(format!(
+                    "Could not edit {} without losing its comments: {}",
+                    path, e
+                ))
+            }
+        };
+    }
+    Ok(content)
+}
+
+/// Gets the content of a manifest once formatted, in the given format or else in
+/// the format of the manifest.
+pub fn dump_manifest(path: &str, format: Option<FlatpakManifestFormat>) -> Result<String, String> {
+    let manifest_dump =
+        if let Ok(mut flatpak_application) = FlatpakApplication::load_from_file(path.to_string()) {
+            if let Some(format) = format {
+                flatpak_application.format = format;
+            }
+            flatpak_application.dump()
+        } else if let Ok(mut flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
+            if let Some(format) = format {
+                flatpak_module.format = format;
+            }
+            flatpak_module.dump()
+        } else if let Ok((flatpak_sources, is_single_source)) = load_source_manifest(path) {
+            // The format is known, since the manifest could be loaded.
+            let format = format.unwrap_or_else(|| FlatpakManifestFormat::from_path(path).unwrap());
+            if is_single_source {
+                format.dump(&flatpak_sources[0])
+            } else {
+                format.dump(&flatpak_sources)
+            }
+        } else {
+            return Err(format!("Could not parse Flatpak manifest at {}.", path));
+        };
+
+    match manifest_dump {
+        Ok(d) => Ok(d),
+        Err(e) => Err(format!("Could not dump manifest {}: {}.", path, e)),
+    }
+}
+
+/// Loads a source manifest, and returns its sources and whether the manifest
+/// contains a single source instead of an array of sources.
+pub fn load_source_manifest(path: &str) -> Result<(Vec<FlatpakSource>, bool), String> {
+    let format = match FlatpakManifestFormat::from_path(path) {
+        Some(f) => f,
+        None => return Err(format!("{} is not a Flatpak source manifest.", path)),
+    };
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read file {}: {}!", path, e)),
+    };
+
+    if let Ok(flatpak_source) = FlatpakSource::parse(format.clone(), &content) {
+        return Ok((vec![flatpak_source], true));
+    }
+    Ok((FlatpakSource::parse_many(format, &content)?, false))
+}
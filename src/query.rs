@@ -0,0 +1,313 @@
+//! Queries the fields of a manifest with paths like `runtime-version`,
+//! `modules[name=glib].sources[0].tag` or `finish-args`. The paths are evaluated on
+//! the manifest serialized as JSON, and converted to JSON pointers. A field is set
+//! on the JSON value, which is then validated as a manifest of the same type.
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::format::FlatpakManifestFormat;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::FlatpakSource;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryComponent {
+    /// A field of an object.
+    Key(String),
+    /// An item of an array, by index.
+    Index(usize),
+    /// The first item of an array with the given value for a field, like
+    /// `[name=glib]`.
+    Filter(String, String),
+}
+
+/// Parses a query path into its components.
+pub fn parse_query(query: &str) -> Result<Vec<QueryComponent>, String> {
+    let mut components: Vec<QueryComponent> = vec![];
+    let mut chars = query.chars().peekable();
+    let mut expects_key = true;
+
+    while chars.peek().is_some() {
+        if expects_key {
+            let mut key = String::new();
+            while let Some(c) = chars.peek() {
+                if *c == '.' || *c == '[' {
+                    break;
+                }
+                key.push(*c);
+                chars.next();
+            }
+            // The query can start with a selector, for the manifests that are arrays.
+            if key.is_empty() && !(components.is_empty() && chars.peek() == Some(&'[')) {
+                return Err(format!("Empty field name in query {}.", query));
+            }
+            if !key.is_empty() {
+                components.push(QueryComponent::Key(key));
+            }
+            expects_key = false;
+            continue;
+        }
+
+        match chars.next() {
+            Some('.') => expects_key = true,
+            Some('[') => {
+                let mut selector = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => selector.push(c),
+                        None => return Err(format!("Missing ] in query {}.", query)),
+                    };
+                }
+                components.push(parse_selector(&selector, query)?);
+            }
+            _ => return Err(format!("Invalid query {}.", query)),
+        };
+    }
+    if expects_key {
+        return Err(format!("Empty field name in query {}.", query));
+    }
+    Ok(components)
+}
+
+fn parse_selector(selector: &str, query: &str) -> Result<QueryComponent, String> {
+    if let Ok(index) = selector.trim().parse::<usize>() {
+        return Ok(QueryComponent::Index(index));
+    }
+    match selector.split_once('=') {
+        Some((field, value)) => {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            Ok(QueryComponent::Filter(
+                field.trim().to_string(),
+                value.to_string(),
+            ))
+        }
+        None => Err(format!(
+            "Invalid selector [{}] in query {}.",
+            selector, query
+        )),
+    }
+}
+
+/// Gets the JSON pointer of the value at the query path. When `allow_missing` is
+/// set, the last field of the path does not have to exist, so it can be added.
+pub fn get_pointer(value: &Value, query: &str, allow_missing: bool) -> Result<String, String> {
+    let components = parse_query(query)?;
+    let mut pointer_components: Vec<String> = vec![];
+    let mut current_value = Some(value);
+
+    for (index, component) in components.iter().enumerate() {
+        let is_last = index == components.len() - 1;
+        let value = match current_value {
+            Some(v) => v,
+            None => return Err(format!("Could not find {} in the manifest.", query)),
+        };
+        let path = get_path_name(&components[..index]);
+
+        current_value = match component {
+            QueryComponent::Key(key) => {
+                let object = match value.as_object() {
+                    Some(o) => o,
+                    None => return Err(format!("Expected an object at {}.", path)),
+                };
+                pointer_components.push(key.to_string());
+                match object.get(key) {
+                    Some(v) => Some(v),
+                    None if is_last && allow_missing => None,
+                    None => return Err(format!("Could not find {} in {}.", key, path)),
+                }
+            }
+            QueryComponent::Index(item_index) => {
+                let array = match value.as_array() {
+                    Some(a) => a,
+                    None => return Err(format!("Expected an array at {}.", path)),
+                };
+                match array.get(*item_index) {
+                    Some(v) => {
+                        pointer_components.push(item_index.to_string());
+                        Some(v)
+                    }
+                    None => return Err(format!("No item at index {} in {}.", item_index, path)),
+                }
+            }
+            QueryComponent::Filter(field, field_value) => {
+                let array = match value.as_array() {
+                    Some(a) => a,
+                    None => return Err(format!("Expected an array at {}.", path)),
+                };
+                let item_index = array.iter().position(|item| match item.get(field) {
+                    Some(Value::String(s)) => s == field_value,
+                    Some(Value::Number(n)) => n.to_string() == *field_value,
+                    Some(Value::Bool(b)) => b.to_string() == *field_value,
+                    _ => false,
+                });
+                match item_index {
+                    Some(i) => {
+                        pointer_components.push(i.to_string());
+                        Some(&array[i])
+                    }
+                    None => {
+                        return Err(format!(
+                            "No item with {}={} in {}.",
+                            field, field_value, path
+                        ))
+                    }
+                }
+            }
+        };
+    }
+    Ok(crate::yaml::to_pointer(&pointer_components))
+}
+
+/// Gets the query path of some components, to use in the error messages.
+fn get_path_name(components: &[QueryComponent]) -> String {
+    if components.is_empty() {
+        return "the manifest".to_string();
+    }
+    let mut path_name = String::new();
+    for component in components {
+        match component {
+            QueryComponent::Key(key) if path_name.is_empty() => path_name.push_str(key),
+            QueryComponent::Key(key) => path_name.push_str(&format!(".{}", key)),
+            QueryComponent::Index(index) => path_name.push_str(&format!("[{}]", index)),
+            QueryComponent::Filter(field, value) => {
+                path_name.push_str(&format!("[{}={}]", field, value))
+            }
+        };
+    }
+    path_name
+}
+
+/// Parses the value to set at a query path. The value is parsed as JSON, except
+/// when replacing a string, so that `46` stays a string for `runtime-version`.
+pub fn parse_value(value: &str, current_value: Option<&Value>) -> Value {
+    if let Some(Value::String(_)) = current_value {
+        return Value::String(value.to_string());
+    }
+    match serde_json::from_str::<Value>(value) {
+        Ok(v) => v,
+        Err(_) => Value::String(value.to_string()),
+    }
+}
+
+/// Formats a queried value. The strings are printed as is, and the other values
+/// as JSON.
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        Value::Array(_) | Value::Object(_) => serde_json::to_string_pretty(value).unwrap(),
+        v => v.to_string(),
+    }
+}
+
+/// Loads a manifest as a JSON value, to query its fields.
+pub fn get_manifest_value(path: &str) -> Result<serde_json::Value, String> {
+    let manifest_value =
+        if let Ok(flatpak_application) = FlatpakApplication::load_from_file(path.to_string()) {
+            serde_json::to_value(&flatpak_application)
+        } else if let Ok(flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
+            serde_json::to_value(&flatpak_module)
+        } else if let Ok((flatpak_sources, is_single_source)) =
+            crate::manifest::load_source_manifest(path)
+        {
+            if is_single_source {
+                serde_json::to_value(&flatpak_sources[0])
+            } else {
+                serde_json::to_value(&flatpak_sources)
+            }
+        } else {
+            return Err(format!("Could not parse Flatpak manifest at {}.", path));
+        };
+    manifest_value.map_err(|e| e.to_string())
+}
+
+/// Sets the value at a JSON pointer, adding the last field if it is missing from
+/// its object.
+pub fn set_value(
+    manifest_value: &mut serde_json::Value,
+    pointer: &str,
+    new_value: serde_json::Value,
+) -> Result<(), String> {
+    if let Some(v) = manifest_value.pointer_mut(pointer) {
+        *v = new_value;
+        return Ok(());
+    }
+    let mut components = crate::yaml::get_pointer_components(pointer);
+    let key = match components.pop() {
+        Some(k) => k,
+        None => return Err("The manifest cannot be replaced.".to_string()),
+    };
+    let parent_pointer = crate::yaml::to_pointer(&components);
+    match manifest_value
+        .pointer_mut(&parent_pointer)
+        .and_then(|p| p.as_object_mut())
+    {
+        Some(parent) => {
+            parent.insert(key, new_value);
+            Ok(())
+        }
+        None => Err(format!("There is no object at {}.", parent_pointer)),
+    }
+}
+
+/// Checks that the field at a JSON pointer is in a parsed manifest. The fields that
+/// are not part of the manifest schema are dropped when the manifest is parsed.
+fn check_field_is_set<T: Serialize>(manifest: &T, pointer: &str) -> Result<(), String> {
+    let manifest_value = serde_json::to_value(manifest).map_err(|e| e.to_string())?;
+    match manifest_value.pointer(pointer) {
+        Some(_) => Ok(()),
+        None => Err("the field is not supported by the manifest.".to_string()),
+    }
+}
+
+/// Replaces the content of a manifest with a JSON value, once the value is
+/// validated as a manifest of the same type, with the field at `pointer` set.
+pub fn set_manifest_value(
+    path: &str,
+    manifest_value: &serde_json::Value,
+    pointer: &str,
+) -> Result<(), String> {
+    let manifest_content = manifest_value.to_string();
+    if let Ok(flatpak_application) = FlatpakApplication::load_from_file(path.to_string()) {
+        let mut new_application =
+            FlatpakApplication::parse(FlatpakManifestFormat::JSON, &manifest_content)?;
+        check_field_is_set(&new_application, pointer)?;
+        new_application.format = flatpak_application.format.clone();
+        let application_dump = new_application.dump()?;
+        return crate::manifest::write_manifest(
+            path,
+            &flatpak_application,
+            &new_application,
+            application_dump,
+        );
+    }
+    if let Ok(flatpak_module) = FlatpakModule::load_from_file(path.to_string()) {
+        let mut new_module = FlatpakModule::parse(FlatpakManifestFormat::JSON, &manifest_content)?;
+        check_field_is_set(&new_module, pointer)?;
+        new_module.format = flatpak_module.format.clone();
+        let module_dump = new_module.dump()?;
+        return crate::manifest::write_manifest(path, &flatpak_module, &new_module, module_dump);
+    }
+
+    let (flatpak_sources, is_single_source) = crate::manifest::load_source_manifest(path)?;
+    // The format is known, since the manifest could be loaded.
+    let format = FlatpakManifestFormat::from_path(path).unwrap();
+    if is_single_source {
+        let new_source = FlatpakSource::parse(FlatpakManifestFormat::JSON, &manifest_content)?;
+        check_field_is_set(&new_source, pointer)?;
+        let source_dump = format.dump(&new_source)?;
+        return crate::manifest::write_manifest(
+            path,
+            &flatpak_sources[0],
+            &new_source,
+            source_dump,
+        );
+    }
+    let new_sources = FlatpakSource::parse_many(FlatpakManifestFormat::JSON, &manifest_content)?;
+    check_field_is_set(&new_sources, pointer)?;
+    let sources_dump = format.dump(&new_sources)?;
+    crate::manifest::write_manifest(path, &flatpak_sources, &new_sources, sources_dump)
+}
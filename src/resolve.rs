@@ -0,0 +1,360 @@
+//! Replaces the module and source manifests imported by a manifest with their
+//! content. The paths in an imported manifest are relative to that manifest, so
+//! they are rebased on the directory of the manifest being resolved.
+use std::fs;
+use std::path;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem};
+
+/// The manifests currently being imported, from the first manifest to the last
+/// imported manifest, with their canonical paths to detect the include cycles.
+pub struct IncludeChain {
+    manifests: Vec<(String, path::PathBuf)>,
+}
+impl IncludeChain {
+    pub fn new(path: &str) -> IncludeChain {
+        let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| path::PathBuf::from(path));
+        IncludeChain {
+            manifests: vec![(path.to_string(), canonical_path)],
+        }
+    }
+
+    /// Starts importing the manifest at `path`. Fails if the manifest cannot be
+    /// found, or if it is already being imported.
+    pub fn enter(&mut self, path: &str) -> Result<(), String> {
+        let canonical_path = match fs::canonicalize(path) {
+            Ok(p) => p,
+            Err(e) => return Err(self.get_error(&format!("Could not import {}: {}", path, e))),
+        };
+        if let Some(position) = self
+            .manifests
+            .iter()
+            .position(|(_, p)| *p == canonical_path)
+        {
+            let mut cycle: Vec<&str> = self.manifests[position..]
+                .iter()
+                .map(|(p, _)| p.as_str())
+                .collect();
+            cycle.push(path);
+            return Err(format!("Include cycle detected: {}.", cycle.join(" → ")));
+        }
+        self.manifests.push((path.to_string(), canonical_path));
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.manifests.pop();
+    }
+
+    /// Adds the manifests being imported to an error.
+    pub fn get_error(&self, message: &str) -> String {
+        let included_from: Vec<&str> = self.manifests.iter().map(|(p, _)| p.as_str()).collect();
+        format!("{} (included from {})", message, included_from.join(" → "))
+    }
+}
+
+struct Resolver {
+    /// The directory of the manifest being resolved.
+    base_dir: path::PathBuf,
+    include_chain: IncludeChain,
+    errors: Vec<String>,
+}
+impl Resolver {
+    fn new(path: &str) -> Resolver {
+        let base_dir = match path::Path::new(path).parent() {
+            Some(d) => d.to_path_buf(),
+            None => path::PathBuf::new(),
+        };
+        Resolver {
+            base_dir,
+            include_chain: IncludeChain::new(path),
+            errors: vec![],
+        }
+    }
+
+    fn add_error(&mut self, message: String) {
+        self.errors.push(self.include_chain.get_error(&message));
+    }
+
+    /// Starts resolving the manifest at `relative_path`. Returns the path to
+    /// display for the manifest and its full path, or `None` if the manifest cannot
+    /// be imported.
+    fn enter_manifest(&mut self, relative_path: &path::Path) -> Option<(String, path::PathBuf)> {
+        let full_path = join_paths(&self.base_dir, relative_path);
+        let display_path = full_path.to_string_lossy().to_string();
+        if let Err(e) = self.include_chain.enter(&display_path) {
+            self.errors.push(e);
+            return None;
+        }
+        Some((display_path, full_path))
+    }
+
+    fn leave_manifest(&mut self) {
+        self.include_chain.leave();
+    }
+
+    fn resolve_modules(
+        &mut self,
+        relative_dir: &path::Path,
+        module_items: &[FlatpakModuleItem],
+    ) -> Vec<FlatpakModuleItem> {
+        let mut response: Vec<FlatpakModuleItem> = vec![];
+        for module_item in module_items {
+            match module_item {
+                FlatpakModuleItem::Path(p) => match self.import_module(relative_dir, p) {
+                    Some(module) => response.push(FlatpakModuleItem::Description(module)),
+                    None => response.push(module_item.clone()),
+                },
+                FlatpakModuleItem::Description(m) => {
+                    let mut module = m.clone();
+                    self.resolve_module(relative_dir, &mut module);
+                    response.push(FlatpakModuleItem::Description(module));
+                }
+            };
+        }
+        response
+    }
+
+    fn resolve_module(&mut self, relative_dir: &path::Path, module: &mut FlatpakModule) {
+        module.sources = self.resolve_sources(relative_dir, &module.sources);
+        module.modules = self.resolve_modules(relative_dir, &module.modules);
+    }
+
+    fn import_module(
+        &mut self,
+        relative_dir: &path::Path,
+        module_path: &str,
+    ) -> Option<FlatpakModule> {
+        let relative_path = join_paths(relative_dir, path::Path::new(module_path));
+        let (display_path, full_path) = self.enter_manifest(&relative_path)?;
+
+        let module = match FlatpakModule::load_from_file(full_path.to_string_lossy().to_string()) {
+            Ok(mut module) => {
+                let module_dir = get_parent_dir(&relative_path);
+                self.resolve_module(&module_dir, &mut module);
+                Some(module)
+            }
+            Err(e) => {
+                self.leave_manifest();
+                self.add_error(format!(
+                    "Could not load module manifest {}: {}",
+                    display_path, e
+                ));
+                return None;
+            }
+        };
+        self.leave_manifest();
+        module
+    }
+
+    fn resolve_sources(
+        &mut self,
+        relative_dir: &path::Path,
+        source_items: &[FlatpakSourceItem],
+    ) -> Vec<FlatpakSourceItem> {
+        let mut response: Vec<FlatpakSourceItem> = vec![];
+        for source_item in source_items {
+            match source_item {
+                FlatpakSourceItem::Path(p) => match self.import_sources(relative_dir, p) {
+                    Some(sources) => {
+                        for source in sources {
+                            response.push(FlatpakSourceItem::Description(source));
+                        }
+                    }
+                    None => response.push(source_item.clone()),
+                },
+                FlatpakSourceItem::Description(d) => {
+                    response.push(FlatpakSourceItem::Description(rebase_source(
+                        relative_dir,
+                        d,
+                    )));
+                }
+            };
+        }
+        response
+    }
+
+    /// Loads a source manifest, which can contain a single source or a list of
+    /// sources.
+    fn import_sources(
+        &mut self,
+        relative_dir: &path::Path,
+        sources_path: &str,
+    ) -> Option<Vec<FlatpakSource>> {
+        let relative_path = join_paths(relative_dir, path::Path::new(sources_path));
+        let (display_path, full_path) = self.enter_manifest(&relative_path)?;
+        self.leave_manifest();
+
+        match FlatpakSource::load_from_file(full_path.to_string_lossy().to_string()) {
+            Ok(sources) => {
+                let sources_dir = get_parent_dir(&relative_path);
+                Some(
+                    sources
+                        .iter()
+                        .map(|s| rebase_source(&sources_dir, s))
+                        .collect(),
+                )
+            }
+            Err(e) => {
+                self.add_error(format!(
+                    "Could not load source manifest {}: {}",
+                    display_path, e
+                ));
+                None
+            }
+        }
+    }
+}
+
+/// Joins two paths, removing the `.` components and the `..` components that can
+/// be removed.
+pub fn join_paths(base_path: &path::Path, relative_path: &path::Path) -> path::PathBuf {
+    if relative_path.is_absolute() {
+        return relative_path.to_path_buf();
+    }
+    let mut joined_path = base_path.to_path_buf();
+    for component in relative_path.components() {
+        match component {
+            path::Component::CurDir => {}
+            path::Component::ParentDir => match joined_path.components().next_back() {
+                Some(path::Component::Normal(_)) => {
+                    joined_path.pop();
+                }
+                _ => joined_path.push(".."),
+            },
+            c => joined_path.push(c),
+        };
+    }
+    joined_path
+}
+
+fn get_parent_dir(path: &path::Path) -> path::PathBuf {
+    match path.parent() {
+        Some(p) => p.to_path_buf(),
+        None => path::PathBuf::new(),
+    }
+}
+
+/// Makes the local paths of a source imported from `relative_dir` relative to the
+/// manifest being resolved.
+fn rebase_source(relative_dir: &path::Path, source: &FlatpakSource) -> FlatpakSource {
+    let mut source = source.clone();
+    if relative_dir.as_os_str().is_empty() {
+        return source;
+    }
+    let rebase_path = |p: &String| {
+        let rebased_path = join_paths(relative_dir, path::Path::new(p));
+        // A path that goes back to the directory of the manifest, like `..` from `parts`.
+        if rebased_path.as_os_str().is_empty() {
+            return ".".to_string();
+        }
+        rebased_path.to_string_lossy().to_string()
+    };
+    source.path = source.path.as_ref().map(rebase_path);
+    source.paths = source
+        .paths
+        .as_ref()
+        .map(|paths| paths.iter().map(rebase_path).collect());
+    source
+}
+
+/// Resolves all the imported manifests of an application manifest. All the imports
+/// that could not be resolved are reported, and are left unchanged.
+pub fn resolve_application(
+    path: &str,
+    application: &mut FlatpakApplication,
+) -> Result<(), Vec<String>> {
+    let mut resolver = Resolver::new(path);
+    application.modules = resolver.resolve_modules(path::Path::new(""), &application.modules);
+    if !resolver.errors.is_empty() {
+        return Err(resolver.errors);
+    }
+    Ok(())
+}
+
+/// Resolves all the imported manifests of a module manifest.
+pub fn resolve_module(path: &str, module: &mut FlatpakModule) -> Result<(), Vec<String>> {
+    let mut resolver = Resolver::new(path);
+    resolver.resolve_module(path::Path::new(""), module);
+    if !resolver.errors.is_empty() {
+        return Err(resolver.errors);
+    }
+    Ok(())
+}
+
+/// Gets the path of `to_dir` relative to `from_dir`. Both directories must exist.
+pub fn get_relative_dir(
+    from_dir: &path::Path,
+    to_dir: &path::Path,
+) -> Result<path::PathBuf, String> {
+    let from_dir = match fs::canonicalize(from_dir) {
+        Ok(d) => d,
+        Err(e) => {
+            return Err(format!(
+                "Could not find directory {}: {}.",
+                from_dir.display(),
+                e
+            ))
+        }
+    };
+    let to_dir = match fs::canonicalize(to_dir) {
+        Ok(d) => d,
+        Err(e) => {
+            return Err(format!(
+                "Could not find directory {}: {}.",
+                to_dir.display(),
+                e
+            ))
+        }
+    };
+
+    let from_components: Vec<path::Component> = from_dir.components().collect();
+    let to_components: Vec<path::Component> = to_dir.components().collect();
+    let common_length = from_components
+        .iter()
+        .zip(to_components.iter())
+        .take_while(|(f, t)| f == t)
+        .count();
+
+    let mut relative_dir = path::PathBuf::new();
+    for _ in common_length..from_components.len() {
+        relative_dir.push("..");
+    }
+    for component in &to_components[common_length..] {
+        relative_dir.push(component);
+    }
+    Ok(relative_dir)
+}
+
+/// Makes the local paths of resolved modules relative to another directory, when
+/// the resolved manifest is written to another directory.
+pub fn rebase_modules(relative_dir: &path::Path, module_items: &mut [FlatpakModuleItem]) {
+    for module_item in module_items {
+        match module_item {
+            FlatpakModuleItem::Path(p) => {
+                *p = join_paths(relative_dir, path::Path::new(p))
+                    .to_string_lossy()
+                    .to_string();
+            }
+            FlatpakModuleItem::Description(module) => rebase_module(relative_dir, module),
+        };
+    }
+}
+
+pub fn rebase_module(relative_dir: &path::Path, module: &mut FlatpakModule) {
+    for source_item in &mut module.sources {
+        match source_item {
+            FlatpakSourceItem::Path(p) => {
+                *p = join_paths(relative_dir, path::Path::new(p))
+                    .to_string_lossy()
+                    .to_string();
+            }
+            FlatpakSourceItem::Description(source) => {
+                *source = rebase_source(relative_dir, source);
+            }
+        };
+    }
+    rebase_modules(relative_dir, &mut module.modules);
+}
@@ -0,0 +1,203 @@
+//! Extracts the inline modules of a manifest to their own module manifests, which
+//! are then imported by path. This is the inverse of resolving a manifest.
+use std::collections::BTreeSet;
+use std::fs;
+use std::path;
+
+use flatpak_rs::format::FlatpakManifestFormat;
+use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+
+pub struct SplitConfig {
+    /// The directory to write the module manifests to.
+    pub output_dir: path::PathBuf,
+    pub format: FlatpakManifestFormat,
+    /// The extension of the module manifests.
+    pub extension: String,
+    /// Do not extract the modules deeper than this. The modules of the manifest
+    /// have a depth of 0.
+    pub max_depth: Option<i64>,
+    /// Do not extract the modules with fewer lines than this once dumped.
+    pub min_lines: usize,
+}
+
+/// A module manifest to write.
+pub struct ModuleFile {
+    pub module_name: String,
+    pub path: path::PathBuf,
+    pub content: String,
+}
+
+struct Splitter<'a> {
+    config: &'a SplitConfig,
+    /// The directory of the manifest being split.
+    manifest_dir: path::PathBuf,
+    /// The manifest being split, when it is a YAML manifest. The extracted modules
+    /// keep their comments.
+    document: Option<crate::yaml::YamlDocument>,
+    files: Vec<ModuleFile>,
+    file_names: BTreeSet<String>,
+}
+impl<'a> Splitter<'a> {
+    /// Splits the modules of a manifest located in `container_dir`. `pointer` is
+    /// the JSON pointer of the modules in the manifest being split, and
+    /// `initial_module_items` are the modules as they are in the manifest, before
+    /// their local paths were rebased.
+    fn split_modules(
+        &mut self,
+        container_dir: &path::Path,
+        pointer: &str,
+        module_items: &mut [FlatpakModuleItem],
+        initial_module_items: &[FlatpakModuleItem],
+        depth: i64,
+    ) -> Result<(), String> {
+        if let Some(max_depth) = self.config.max_depth {
+            if depth > max_depth {
+                return Ok(());
+            }
+        }
+
+        for (index, module_item) in module_items.iter_mut().enumerate() {
+            let module_pointer = crate::yaml::get_child_pointer(pointer, &index.to_string());
+            let modules_pointer = crate::yaml::get_child_pointer(&module_pointer, "modules");
+            let (module, initial_module) = match (&mut *module_item, &initial_module_items[index]) {
+                (FlatpakModuleItem::Description(m), FlatpakModuleItem::Description(i)) => (m, i),
+                _ => continue,
+            };
+
+            let module_dump = self.dump_module(module)?;
+            if module_dump.lines().count() < self.config.min_lines {
+                self.split_modules(
+                    container_dir,
+                    &modules_pointer,
+                    &mut module.modules,
+                    &initial_module.modules,
+                    depth + 1,
+                )?;
+                continue;
+            }
+
+            // The local paths of the module are relative to the manifest that
+            // contains it, and have to be relative to the new module manifest.
+            let relative_dir = crate::resolve::get_relative_dir(
+                &self.get_full_path(&self.config.output_dir),
+                &self.get_full_path(container_dir),
+            )?;
+            crate::resolve::rebase_module(&relative_dir, module);
+            let output_dir = self.config.output_dir.clone();
+            self.split_modules(
+                &output_dir,
+                &modules_pointer,
+                &mut module.modules,
+                &initial_module.modules,
+                depth + 1,
+            )?;
+
+            let file_name = format!(
+                "{}.{}",
+                module.name.replace('/', "-"),
+                self.config.extension
+            );
+            if !self.file_names.insert(file_name.to_string()) {
+                return Err(format!(
+                    "Multiple modules would be extracted to {}. Use --max-depth or --min-lines to only extract some of them.",
+                    file_name
+                ));
+            }
+            let module_path = self.get_full_path(&self.config.output_dir.join(&file_name));
+            if module_path.exists() {
+                return Err(format!("{} already exists.", module_path.display()));
+            }
+
+            let module_reference = crate::resolve::get_relative_dir(
+                &self.get_full_path(container_dir),
+                &self.get_full_path(&self.config.output_dir),
+            )?
+            .join(&file_name);
+            self.files.push(ModuleFile {
+                module_name: module.name.to_string(),
+                path: module_path,
+                content: self.get_module_content(&module_pointer, initial_module, module)?,
+            });
+            *module_item = FlatpakModuleItem::Path(module_reference.to_string_lossy().to_string());
+        }
+        Ok(())
+    }
+
+    fn dump_module(&self, module: &FlatpakModule) -> Result<String, String> {
+        let mut module = module.clone();
+        module.format = self.config.format.clone();
+        module.dump()
+    }
+
+    /// Gets the content of an extracted module. For YAML manifests, the lines of the
+    /// module are copied from the manifest, so the comments are kept.
+    fn get_module_content(
+        &self,
+        pointer: &str,
+        initial_module: &FlatpakModule,
+        module: &FlatpakModule,
+    ) -> Result<String, String> {
+        let module_dump = self.dump_module(module)?;
+        let node_content = match self
+            .document
+            .as_ref()
+            .and_then(|d| d.get_node_content(pointer))
+        {
+            Some(c) => c,
+            None => return Ok(module_dump),
+        };
+        let initial_value = serde_yaml::to_value(initial_module).map_err(|e| e.to_string())?;
+        let value = serde_yaml::to_value(module).map_err(|e| e.to_string())?;
+        match crate::yaml::update_content(&node_content, &initial_value, &value) {
+            Ok(c) => Ok(c),
+            Err(e) => {
+                eprintln!(
+                    "Could not preserve the formatting of module {}: {}",
+                    module.name, e
+                );
+                Ok(module_dump)
+            }
+        }
+    }
+
+    /// Gets a path relative to the directory of the manifest as a path relative to
+    /// the current directory.
+    fn get_full_path(&self, relative_path: &path::Path) -> path::PathBuf {
+        crate::resolve::join_paths(&self.manifest_dir, relative_path)
+    }
+}
+
+/// Extracts the inline modules of the manifest at `path`. The paths in the
+/// configuration are relative to the directory of the manifest. Returns the module
+/// manifests to write.
+pub fn split_modules(
+    path: &str,
+    module_items: &mut [FlatpakModuleItem],
+    config: &SplitConfig,
+) -> Result<Vec<ModuleFile>, String> {
+    let mut document: Option<crate::yaml::YamlDocument> = None;
+    if let Some(FlatpakManifestFormat::YAML) = FlatpakManifestFormat::from_path(path) {
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read file {}: {}!", path, e)),
+        };
+        document = crate::yaml::YamlDocument::parse(&content).ok();
+    }
+
+    let mut splitter = Splitter {
+        config,
+        manifest_dir: crate::get_manifest_dir(path).to_path_buf(),
+        document,
+        files: vec![],
+        file_names: BTreeSet::new(),
+    };
+    let initial_module_items = module_items.to_vec();
+    splitter.split_modules(
+        path::Path::new(""),
+        "/modules",
+        module_items,
+        &initial_module_items,
+        0,
+    )?;
+    Ok(splitter.files)
+}
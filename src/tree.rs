@@ -0,0 +1,295 @@
+//! Builds the tree of the modules of a manifest, with the metadata of every module,
+//! and prints it as text, JSON, DOT or Mermaid.
+use std::path;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem};
+use serde::Serialize;
+
+pub const TREE_FORMAT_TEXT: &str = "text";
+pub const TREE_FORMAT_JSON: &str = "json";
+pub const TREE_FORMAT_DOT: &str = "dot";
+pub const TREE_FORMAT_MERMAID: &str = "mermaid";
+pub const TREE_FORMATS: [&str; 4] = [
+    TREE_FORMAT_TEXT,
+    TREE_FORMAT_JSON,
+    TREE_FORMAT_DOT,
+    TREE_FORMAT_MERMAID,
+];
+
+/// flatpak-builder uses autotools when a module has no build system.
+pub const DEFAULT_BUILD_SYSTEM: &str = "autotools";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ModuleNode {
+    /// The name of the module, or the ID of the application at the root of the tree.
+    pub name: String,
+    /// The manifest the module is defined in.
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buildsystem: Option<String>,
+    pub source_count: usize,
+    pub source_types: Vec<String>,
+    /// The path of the imported module manifest, when the imports are not resolved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import_path: Option<String>,
+    pub modules: Vec<ModuleNode>,
+}
+
+struct TreeBuilder {
+    resolve: bool,
+    max_depth: i64,
+    include_chain: crate::resolve::IncludeChain,
+}
+impl TreeBuilder {
+    fn get_module_nodes(
+        &mut self,
+        file: &str,
+        module_items: &[FlatpakModuleItem],
+        depth: i64,
+    ) -> Result<Vec<ModuleNode>, String> {
+        let mut module_nodes: Vec<ModuleNode> = vec![];
+        if depth > self.max_depth {
+            return Ok(module_nodes);
+        }
+
+        for module_item in module_items {
+            match module_item {
+                FlatpakModuleItem::Description(module) => {
+                    module_nodes.push(self.get_module_node(file, module, depth)?);
+                }
+                FlatpakModuleItem::Path(p) if self.resolve => {
+                    let module_path = crate::resolve::join_paths(get_dir(file), path::Path::new(p));
+                    let module_path = module_path.to_string_lossy().to_string();
+                    self.include_chain.enter(&module_path)?;
+                    let module = match FlatpakModule::load_from_file(module_path.to_string()) {
+                        Ok(m) => m,
+                        Err(e) => {
+                            self.include_chain.leave();
+                            return Err(self.include_chain.get_error(&format!(
+                                "Could not load module manifest {}: {}",
+                                module_path, e
+                            )));
+                        }
+                    };
+                    let module_node = self.get_module_node(&module_path, &module, depth);
+                    self.include_chain.leave();
+                    module_nodes.push(module_node?);
+                }
+                FlatpakModuleItem::Path(p) => module_nodes.push(ModuleNode {
+                    name: p.to_string(),
+                    file: file.to_string(),
+                    buildsystem: None,
+                    source_count: 0,
+                    source_types: vec![],
+                    import_path: Some(p.to_string()),
+                    modules: vec![],
+                }),
+            };
+        }
+        Ok(module_nodes)
+    }
+
+    fn get_module_node(
+        &mut self,
+        file: &str,
+        module: &FlatpakModule,
+        depth: i64,
+    ) -> Result<ModuleNode, String> {
+        // The imported source manifests are always loaded, since they are part of
+        // the module.
+        let mut sources: Vec<FlatpakSource> = vec![];
+        for source_item in &module.sources {
+            match source_item {
+                FlatpakSourceItem::Description(d) => sources.push(d.clone()),
+                FlatpakSourceItem::Path(p) => {
+                    let source_path = crate::resolve::join_paths(get_dir(file), path::Path::new(p));
+                    sources.append(&mut FlatpakSource::load_from_file(
+                        source_path.to_string_lossy().to_string(),
+                    )?);
+                }
+            };
+        }
+        let mut source_types: Vec<String> = vec![];
+        for source in &sources {
+            if let Some(source_type) = &source.r#type {
+                if !source_types.contains(&source_type.to_string()) {
+                    source_types.push(source_type.to_string());
+                }
+            }
+        }
+
+        let buildsystem = match &module.buildsystem {
+            Some(b) => b.to_string(),
+            None => DEFAULT_BUILD_SYSTEM.to_string(),
+        };
+        Ok(ModuleNode {
+            name: module.name.to_string(),
+            file: file.to_string(),
+            buildsystem: Some(buildsystem),
+            source_count: sources.len(),
+            source_types,
+            import_path: None,
+            modules: self.get_module_nodes(file, &module.modules, depth + 1)?,
+        })
+    }
+}
+
+/// Gets the directory of a manifest, which is empty for the manifests in the
+/// current directory.
+fn get_dir(path: &str) -> &path::Path {
+    path::Path::new(path)
+        .parent()
+        .unwrap_or_else(|| path::Path::new(""))
+}
+
+/// Gets the tree of the modules of an application or module manifest. The imported
+/// module manifests are only loaded when `resolve` is set.
+pub fn get_module_tree(path: &str, resolve: bool, max_depth: i64) -> Result<ModuleNode, String> {
+    let mut tree_builder = TreeBuilder {
+        resolve,
+        max_depth,
+        include_chain: crate::resolve::IncludeChain::new(path),
+    };
+
+    if let Ok(flatpak_application) = FlatpakApplication::load_from_file(path.to_string()) {
+        return Ok(ModuleNode {
+            name: flatpak_application.get_id(),
+            file: path.to_string(),
+            buildsystem: None,
+            source_count: 0,
+            source_types: vec![],
+            import_path: None,
+            modules: tree_builder.get_module_nodes(path, &flatpak_application.modules, 0)?,
+        });
+    }
+    match FlatpakModule::load_from_file(path.to_string()) {
+        Ok(flatpak_module) => tree_builder.get_module_node(path, &flatpak_module, -1),
+        Err(_) => Err(format!(
+            "{} is not a Flatpak application manifest or a Flatpak module manifest.",
+            path
+        )),
+    }
+}
+
+/// Gets the columns printed after the name of a module.
+fn get_module_columns(module_node: &ModuleNode) -> Vec<String> {
+    let buildsystem = match &module_node.buildsystem {
+        Some(b) => b.to_string(),
+        None => return vec![],
+    };
+    let source_count = match module_node.source_count {
+        1 => "1 source".to_string(),
+        c => format!("{} sources", c),
+    };
+    let source_count = if module_node.source_types.is_empty() {
+        source_count
+    } else {
+        format!("{} ({})", source_count, module_node.source_types.join(", "))
+    };
+    vec![buildsystem, source_count, module_node.file.to_string()]
+}
+
+/// Gets the lines of the tree as text, with the columns of every module.
+fn get_text_rows(module_node: &ModuleNode, depth: usize, rows: &mut Vec<Vec<String>>) {
+    for child_node in &module_node.modules {
+        let mut row = vec![format!("{}↪ {}", "  ".repeat(depth), child_node.name)];
+        row.append(&mut get_module_columns(child_node));
+        rows.push(row);
+        get_text_rows(child_node, depth + 1, rows);
+    }
+}
+
+pub fn format_text(module_tree: &ModuleNode) -> String {
+    let mut rows: Vec<Vec<String>> = vec![];
+    get_text_rows(module_tree, 0, &mut rows);
+
+    let mut column_widths: Vec<usize> = vec![];
+    for row in &rows {
+        for (index, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match column_widths.get_mut(index) {
+                Some(w) => *w = (*w).max(width),
+                None => column_widths.push(width),
+            };
+        }
+    }
+
+    let mut lines: Vec<String> = vec![module_tree.name.to_string()];
+    for row in rows {
+        let mut line = String::new();
+        for (index, cell) in row.iter().enumerate() {
+            if index == row.len() - 1 {
+                line.push_str(cell);
+                break;
+            }
+            let padding = column_widths[index] - cell.chars().count();
+            line.push_str(&format!("{}{}  ", cell, " ".repeat(padding)));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// Gets the nodes and the edges of the tree, with an ID for every node, since the
+/// module names are not unique.
+fn get_graph(
+    module_node: &ModuleNode,
+    nodes: &mut Vec<(String, String)>,
+    edges: &mut Vec<(String, String)>,
+) {
+    let node_id = format!("n{}", nodes.len());
+    let label = match &module_node.buildsystem {
+        Some(b) if module_node.import_path.is_none() => format!("{}\n{}", module_node.name, b),
+        _ => module_node.name.to_string(),
+    };
+    nodes.push((node_id.to_string(), label));
+    for child_node in &module_node.modules {
+        edges.push((node_id.to_string(), format!("n{}", nodes.len())));
+        get_graph(child_node, nodes, edges);
+    }
+}
+
+pub fn format_dot(module_tree: &ModuleNode) -> String {
+    let mut nodes: Vec<(String, String)> = vec![];
+    let mut edges: Vec<(String, String)> = vec![];
+    get_graph(module_tree, &mut nodes, &mut edges);
+
+    let mut lines: Vec<String> = vec!["digraph modules {".to_string()];
+    for (node_id, label) in nodes {
+        let label = label
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        lines.push(format!("  {} [label=\"{}\"];", node_id, label));
+    }
+    for (parent_id, child_id) in edges {
+        lines.push(format!("  {} -> {};", parent_id, child_id));
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+pub fn format_mermaid(module_tree: &ModuleNode) -> String {
+    let mut nodes: Vec<(String, String)> = vec![];
+    let mut edges: Vec<(String, String)> = vec![];
+    get_graph(module_tree, &mut nodes, &mut edges);
+
+    let mut lines: Vec<String> = vec!["graph TD".to_string()];
+    for (node_id, label) in nodes {
+        // Mermaid reads `#...;` as an entity code, and the labels as HTML.
+        let label = label
+            .replace('#', "#35;")
+            .replace('"', "#quot;")
+            .replace('<', "#lt;")
+            .replace('>', "#gt;")
+            .replace('\n', "<br/>");
+        lines.push(format!("  {}[\"{}\"]", node_id, label));
+    }
+    for (parent_id, child_id) in edges {
+        lines.push(format!("  {} --> {}", parent_id, child_id));
+    }
+    lines.join("\n")
+}
@@ -1,17 +1,194 @@
 use std::fs;
+use std::io::{Read, Write};
 use std::path;
 
-pub fn get_all_paths(dir: &path::Path) -> Result<Vec<path::PathBuf>, String> {
+use sha2::Digest;
+
+/// Directories that are never traversed.
+pub const ALWAYS_IGNORED_DIRS: [&str; 1] = [".git"];
+
+pub const GITIGNORE_FILE_NAME: &str = ".gitignore";
+
+#[derive(Clone, Debug)]
+/// A single ignore rule, using the same semantics as the patterns of a `.gitignore` file.
+pub struct IgnoreRule {
+    /// The directory the pattern is relative to.
+    pub base_dir: path::PathBuf,
+    pub pattern: glob::Pattern,
+    /// The rule re-includes the paths it matches.
+    pub negated: bool,
+    /// The rule only matches directories.
+    pub dir_only: bool,
+    /// The pattern contains a slash, so it is matched against the whole path
+    /// relative to the base directory instead of only the file name.
+    pub anchored: bool,
+}
+impl IgnoreRule {
+    pub fn parse(base_dir: &path::Path, line: &str) -> Result<Option<IgnoreRule>, String> {
+        let mut line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let negated = line.starts_with('!');
+        if negated {
+            line = &line[1..];
+        }
+
+        let dir_only = line.ends_with('/');
+        if dir_only {
+            line = &line[..line.len() - 1];
+        }
+
+        let anchored = line.contains('/');
+        let line = line.trim_start_matches('/');
+        if line.is_empty() {
+            return Ok(None);
+        }
+
+        let pattern = match glob::Pattern::new(line) {
+            Ok(p) => p,
+            Err(e) => return Err(format!("Invalid ignore pattern {}: {}.", line, e)),
+        };
+
+        Ok(Some(IgnoreRule {
+            base_dir: base_dir.to_path_buf(),
+            pattern,
+            negated,
+            dir_only,
+            anchored,
+        }))
+    }
+
+    pub fn matches(&self, path: &path::Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let relative_path = match path.strip_prefix(&self.base_dir) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+
+        let match_options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        if self.anchored {
+            return self.pattern.matches_path_with(relative_path, match_options);
+        }
+
+        match relative_path.file_name() {
+            Some(file_name) => self
+                .pattern
+                .matches_path_with(path::Path::new(file_name), match_options),
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// An ordered list of ignore rules. Like in a `.gitignore` file, the last rule
+/// matching a path decides if the path is ignored.
+pub struct IgnoreRules {
+    pub rules: Vec<IgnoreRule>,
+}
+impl IgnoreRules {
+    pub fn add_patterns(
+        &mut self,
+        base_dir: &path::Path,
+        patterns: &[String],
+    ) -> Result<(), String> {
+        for pattern in patterns {
+            if let Some(rule) = IgnoreRule::parse(base_dir, pattern)? {
+                self.rules.push(rule);
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads the rules from the `.gitignore` file in `dir`, if there is one.
+    pub fn add_gitignore(&mut self, dir: &path::Path) -> Result<(), String> {
+        let gitignore_path = dir.join(GITIGNORE_FILE_NAME);
+        if !gitignore_path.is_file() {
+            return Ok(());
+        }
+
+        let gitignore_content = match fs::read_to_string(&gitignore_path) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(format!(
+                    "Could not read file {}: {}!",
+                    gitignore_path.display(),
+                    e
+                ))
+            }
+        };
+        for line in gitignore_content.lines() {
+            if let Some(rule) = IgnoreRule::parse(dir, line)? {
+                self.rules.push(rule);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_ignored(&self, path: &path::Path, is_dir: bool) -> bool {
+        if is_dir {
+            if let Some(dir_name) = path.file_name() {
+                if ALWAYS_IGNORED_DIRS.iter().any(|d| *d == dir_name) {
+                    return true;
+                }
+            }
+        }
+
+        let mut is_ignored = false;
+        for rule in &self.rules {
+            if rule.matches(path, is_dir) {
+                is_ignored = !rule.negated;
+            }
+        }
+        is_ignored
+    }
+}
+
+/// Gets all the file paths under `dir`, skipping the paths matched by the ignore rules.
+/// If `use_gitignore` is set, the rules of the `.gitignore` files found while traversing
+/// the directories also apply to their respective sub-trees.
+pub fn get_all_paths(
+    dir: &path::Path,
+    ignore_rules: &IgnoreRules,
+    use_gitignore: bool,
+) -> Result<Vec<path::PathBuf>, String> {
     let mut all_paths: Vec<path::PathBuf> = vec![];
 
+    let mut ignore_rules = ignore_rules.clone();
+    if use_gitignore {
+        ignore_rules.add_gitignore(dir)?;
+    }
+
     let dir_entries = match fs::read_dir(dir) {
         Ok(entries) => entries,
         Err(err) => return Err(err.to_string()),
     };
+    let mut entry_paths: Vec<path::PathBuf> = vec![];
     for entry in dir_entries {
-        let entry_path = entry.unwrap().path();
-        if entry_path.is_dir() {
-            let mut dir_paths: Vec<path::PathBuf> = get_all_paths(&entry_path)?;
+        match entry {
+            Ok(e) => entry_paths.push(e.path()),
+            Err(err) => return Err(err.to_string()),
+        };
+    }
+    entry_paths.sort();
+
+    for entry_path in entry_paths {
+        let is_dir = entry_path.is_dir();
+        if ignore_rules.is_ignored(&entry_path, is_dir) {
+            continue;
+        }
+        if is_dir {
+            let mut dir_paths: Vec<path::PathBuf> =
+                get_all_paths(&entry_path, &ignore_rules, use_gitignore)?;
             all_paths.append(&mut dir_paths);
         } else {
             all_paths.push(entry_path);
@@ -20,3 +197,88 @@ pub fn get_all_paths(dir: &path::Path) -> Result<Vec<path::PathBuf>, String> {
 
     Ok(all_paths)
 }
+
+fn get_file_digest<D: Digest>(file_path: &path::Path) -> Result<String, String> {
+    let mut file = match fs::File::open(file_path) {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(format!(
+                "Could not open file {}: {}.",
+                file_path.display(),
+                e
+            ))
+        }
+    };
+
+    let mut hasher = D::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let bytes_read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                return Err(format!(
+                    "Could not read file {}: {}.",
+                    file_path.display(),
+                    e
+                ))
+            }
+        };
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    let digest = hasher.finalize();
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Gets the hex-encoded sha256 checksum of a file.
+pub fn get_file_sha256(file_path: &path::Path) -> Result<String, String> {
+    get_file_digest::<sha2::Sha256>(file_path)
+}
+
+/// Gets the hex-encoded sha512 checksum of a file.
+pub fn get_file_sha512(file_path: &path::Path) -> Result<String, String> {
+    get_file_digest::<sha2::Sha512>(file_path)
+}
+
+/// Gets a unified diff between two versions of a file, in the format used by `diff -u`.
+pub fn get_unified_diff(file_path: &str, old_content: &str, new_content: &str) -> String {
+    similar::TextDiff::from_lines(old_content, new_content)
+        .unified_diff()
+        .header(file_path, file_path)
+        .to_string()
+}
+
+/// Writes some output to stdout. Unlike `print!`, this does not panic when stdout
+/// is a closed pipe, like with `fpcli lint | head`: the output is dropped instead.
+pub fn write_stdout(content: &str) -> Result<(), String> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout
+        .write_all(content.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        Err(e) => Err(format!("Could not write to stdout: {}.", e)),
+    }
+}
+
+/// Gets an empty directory for a unit test, in the temporary directory.
+#[cfg(test)]
+pub fn get_test_dir(name: &str) -> path::PathBuf {
+    let test_dir = std::env::temp_dir()
+        .join(format!("fpcli-tests-{}", std::process::id()))
+        .join(name);
+    if test_dir.exists() {
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+    fs::create_dir_all(&test_dir).unwrap();
+    test_dir
+}
+
+/// Writes a file for a unit test, creating its parent directories.
+#[cfg(test)]
+pub fn write_test_file(file_path: &path::Path, content: &str) {
+    fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+    fs::write(file_path, content).unwrap();
+}
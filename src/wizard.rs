@@ -0,0 +1,301 @@
+//! Asks for the settings of a bootstrapped application manifest. The questions are
+//! printed to stderr, so the manifest can still be printed to stdout.
+use std::io::{self, BufRead, Write};
+
+use crate::bootstrap::{ApplicationSettings, ModuleSettings};
+
+/// The answer used to bootstrap from a local directory instead of a URL.
+pub const NO_URL_ANSWER: &str = "none";
+
+struct Wizard<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+impl<R: BufRead, W: Write> Wizard<R, W> {
+    /// Asks a question, and returns the default answer when the answer is empty.
+    fn ask(&mut self, question: &str, default: &str) -> Result<String, String> {
+        if default.is_empty() {
+            write!(self.output, "{}: ", question).map_err(|e| e.to_string())?;
+        } else {
+            write!(self.output, "{} [{}]: ", question, default).map_err(|e| e.to_string())?;
+        }
+        self.output.flush().map_err(|e| e.to_string())?;
+
+        let mut answer = String::new();
+        match self.input.read_line(&mut answer) {
+            Ok(0) => {
+                return Err("The input ended before all the questions were answered.".to_string())
+            }
+            Ok(_) => {}
+            Err(e) => return Err(format!("Could not read the answer: {}.", e)),
+        };
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(default.to_string());
+        }
+        Ok(answer.to_string())
+    }
+
+    fn say(&mut self, message: &str) -> Result<(), String> {
+        writeln!(self.output, "{}", message).map_err(|e| e.to_string())
+    }
+
+    /// Asks to pick one of the options, by number or by name.
+    fn choose(
+        &mut self,
+        question: &str,
+        options: &[&str],
+        default: &str,
+    ) -> Result<String, String> {
+        for (index, option) in options.iter().enumerate() {
+            self.say(&format!("  {}. {}", index + 1, option))?;
+        }
+        loop {
+            let answer = self.ask(question, default)?;
+            if options.contains(&answer.as_str()) {
+                return Ok(answer);
+            }
+            match answer.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= options.len() => return Ok(options[n - 1].to_string()),
+                _ => self.say(&format!("{} is not one of the options.", answer))?,
+            };
+        }
+    }
+
+    fn ask_app_id(&mut self, default: &str) -> Result<String, String> {
+        loop {
+            let app_id = self.ask("Application ID", default)?;
+            if crate::lint::is_valid_app_id(&app_id) {
+                return Ok(app_id);
+            }
+            self.say(&format!(
+                "{} is not a reverse DNS name, like org.example.App.",
+                app_id
+            ))?;
+        }
+    }
+
+    /// Asks for the permissions, as a checklist of the known permissions. The
+    /// permissions that are already selected but not known are kept.
+    fn ask_permissions(&mut self, selected: &[String]) -> Result<Vec<String>, String> {
+        let mut is_selected: Vec<bool> = crate::bootstrap::PERMISSIONS
+            .iter()
+            .map(|p| selected.contains(&p.0.to_string()))
+            .collect();
+        loop {
+            self.say("Permissions:")?;
+            for (index, (permission, description, _)) in
+                crate::bootstrap::PERMISSIONS.iter().enumerate()
+            {
+                let check_mark = if is_selected[index] { "x" } else { " " };
+                self.say(&format!(
+                    "  [{}] {:>2}. {} ({})",
+                    check_mark,
+                    index + 1,
+                    description,
+                    permission
+                ))?;
+            }
+            let answer = self.ask(
+                "Numbers of the permissions to toggle, or nothing to keep this selection",
+                "",
+            )?;
+            if answer.is_empty() {
+                break;
+            }
+            for number in answer.split(|c: char| c == ',' || c.is_whitespace()) {
+                if number.is_empty() {
+                    continue;
+                }
+                match number.parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= is_selected.len() => {
+                        is_selected[n - 1] = !is_selected[n - 1]
+                    }
+                    _ => self.say(&format!("{} is not one of the permissions.", number))?,
+                };
+            }
+        }
+
+        let mut finish_args: Vec<String> = crate::bootstrap::PERMISSIONS
+            .iter()
+            .zip(is_selected)
+            .filter(|(_, s)| *s)
+            .map(|(p, _)| p.0.to_string())
+            .collect();
+        for permission in selected {
+            if !crate::bootstrap::PERMISSIONS
+                .iter()
+                .any(|p| p.0 == permission)
+            {
+                finish_args.push(permission.to_string());
+            }
+        }
+        Ok(finish_args)
+    }
+
+    fn ask_settings(
+        &mut self,
+        application: &mut ApplicationSettings,
+        module: &mut ModuleSettings,
+    ) -> Result<(), String> {
+        application.app_id = self.ask_app_id(&application.app_id)?;
+
+        let runtime_names: Vec<&str> = crate::bootstrap::RUNTIMES.iter().map(|r| r.name).collect();
+        let default_runtime = match crate::bootstrap::RUNTIMES
+            .iter()
+            .find(|r| r.runtime == application.runtime)
+        {
+            Some(r) => r.name,
+            None => crate::bootstrap::GNOME_RUNTIME.name,
+        };
+        self.say("Runtimes:")?;
+        let runtime_name = self.choose("Runtime", &runtime_names, default_runtime)?;
+        let runtime = crate::bootstrap::RUNTIMES
+            .iter()
+            .find(|r| r.name == runtime_name)
+            .unwrap();
+        if runtime.runtime != application.runtime {
+            application.runtime = runtime.runtime.to_string();
+            application.sdk = runtime.sdk.to_string();
+            application.runtime_version = runtime.version.to_string();
+        }
+        application.runtime_version = self.ask("Runtime version", &application.runtime_version)?;
+        application.finish_args = self.ask_permissions(&application.finish_args)?;
+
+        self.say("First module:")?;
+        let url = self.ask(
+            &format!(
+                "URL of the sources, or {} for the local directory",
+                NO_URL_ANSWER
+            ),
+            module.url.as_deref().unwrap_or(NO_URL_ANSWER),
+        )?;
+        if url != NO_URL_ANSWER {
+            module.url = Some(url);
+        } else {
+            module.url = None;
+        }
+        let default_name = match &module.name {
+            Some(n) => n.to_string(),
+            None => crate::bootstrap::get_default_module(module.url.clone(), &module.git_ref)?.name,
+        };
+        module.name = Some(self.ask("Module name", &default_name)?);
+
+        self.say("Build systems:")?;
+        let build_system = self.choose(
+            "Build system",
+            &crate::bootstrap::BUILD_SYSTEMS,
+            module
+                .build_system
+                .as_deref()
+                .unwrap_or(crate::bootstrap::BUILD_SYSTEM_MESON),
+        )?;
+        module.build_system = Some(build_system);
+        Ok(())
+    }
+}
+
+/// Asks for the settings of an application manifest and of its first module,
+/// starting from the given settings.
+pub fn ask_settings(
+    application: &mut ApplicationSettings,
+    module: &mut ModuleSettings,
+) -> Result<(), String> {
+    let mut wizard = Wizard {
+        input: io::stdin().lock(),
+        output: io::stderr(),
+    };
+    wizard.ask_settings(application, module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers the questions of the wizard with the lines of `script`, and returns
+    /// what the wizard printed.
+    fn run_wizard(
+        script: &str,
+        application: &mut ApplicationSettings,
+        module: &mut ModuleSettings,
+    ) -> Result<String, String> {
+        let mut wizard = Wizard {
+            input: script.as_bytes(),
+            output: vec![],
+        };
+        wizard.ask_settings(application, module)?;
+        Ok(String::from_utf8(wizard.output).unwrap())
+    }
+
+    #[test]
+    fn keep_the_default_answers() {
+        let mut application =
+            ApplicationSettings::new("org.example.App", &crate::bootstrap::GNOME_RUNTIME);
+        let default_finish_args = application.finish_args.clone();
+        let mut module = ModuleSettings {
+            name: Some("app".to_string()),
+            ..Default::default()
+        };
+        let output = run_wizard("\n\n\n\n\n\n\n", &mut application, &mut module).unwrap();
+        assert!(output.contains("Application ID [org.example.App]: "));
+
+        assert_eq!(application.app_id, "org.example.App");
+        assert_eq!(application.runtime, "org.gnome.Platform");
+        assert_eq!(application.runtime_version, "49");
+        assert_eq!(application.finish_args, default_finish_args);
+        assert_eq!(module.name.as_deref(), Some("app"));
+        assert_eq!(module.url, None);
+        assert_eq!(module.build_system.as_deref(), Some("meson"));
+    }
+
+    #[test]
+    fn ask_again_after_invalid_answers() {
+        let mut application =
+            ApplicationSettings::new("org.example.App", &crate::bootstrap::GNOME_RUNTIME);
+        application.finish_args.push("--env=DEBUG=1".to_string());
+        let mut module = ModuleSettings::default();
+        let script = [
+            "not an id",
+            "org.example.Other",
+            "7",
+            "KDE",
+            "",
+            "1, 99",
+            "",
+            "https://example.org/project.git",
+            "",
+            "7",
+        ]
+        .join("\n");
+        let output = run_wizard(&script, &mut application, &mut module).unwrap();
+        assert!(output.contains("not an id is not a reverse DNS name, like org.example.App."));
+        assert!(output.contains("7 is not one of the options."));
+        assert!(output.contains("99 is not one of the permissions."));
+
+        assert_eq!(application.app_id, "org.example.Other");
+        assert_eq!(application.runtime, "org.kde.Platform");
+        assert_eq!(application.sdk, "org.kde.Sdk");
+        assert_eq!(application.runtime_version, "6.9");
+        assert!(!application.finish_args.contains(&"--share=ipc".to_string()));
+        assert!(application
+            .finish_args
+            .contains(&"--env=DEBUG=1".to_string()));
+        assert_eq!(
+            module.url.as_deref(),
+            Some("https://example.org/project.git")
+        );
+        assert_eq!(module.name.as_deref(), Some("project"));
+        assert_eq!(module.build_system.as_deref(), Some("cargo"));
+    }
+
+    #[test]
+    fn input_ends_before_the_last_question() {
+        let mut application =
+            ApplicationSettings::new("org.example.App", &crate::bootstrap::GNOME_RUNTIME);
+        let mut module = ModuleSettings::default();
+        assert_eq!(
+            run_wizard("org.example.App\n", &mut application, &mut module).unwrap_err(),
+            "The input ended before all the questions were answered."
+        );
+    }
+}
@@ -0,0 +1,1832 @@
+//! A minimal parser for the block style YAML used in the manifests. Unlike serde_yaml,
+//! it keeps the location of every node in the file, so the nodes can be found from
+//! their JSON pointer, and edited without touching the rest of the file. The
+//! comments, blank lines and key order of the untouched nodes are preserved.
+//!
+//! When formatting, the scalars and the single-line flow collections that did not
+//! change keep their text, so their quotes and their flow style are preserved.
+//! The manifests with anchors or aliases are neither formatted nor edited, since the
+//! aliases would be expanded and the anchors dropped.
+use std::collections::HashMap;
+
+use serde_yaml::Value;
+
+/// A line of the file with content, once the comment is removed.
+#[derive(Clone, Debug)]
+struct Line {
+    /// The index of the line in the file.
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum YamlNodeKind {
+    Mapping(Vec<YamlEntry>),
+    Sequence(Vec<YamlNode>),
+    Scalar,
+}
+
+#[derive(Clone, Debug)]
+pub struct YamlEntry {
+    pub key: String,
+    pub key_line: usize,
+    pub key_column: usize,
+    pub value: YamlNode,
+}
+
+#[derive(Clone, Debug)]
+pub struct YamlNode {
+    pub kind: YamlNodeKind,
+    /// The index of the first line of the node.
+    pub start_line: usize,
+    /// The index of the last line of the node.
+    pub end_line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct YamlDocument {
+    pub lines: Vec<String>,
+    pub root: Option<YamlNode>,
+    pub has_final_newline: bool,
+    /// Whether the document uses anchors or aliases, like `&base` and `*base`.
+    pub has_anchors: bool,
+}
+impl YamlDocument {
+    pub fn parse(content: &str) -> Result<YamlDocument, String> {
+        let raw_lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+
+        let mut lines: Vec<Line> = vec![];
+        let mut has_anchors = false;
+        let mut block_scalar_indent: Option<usize> = None;
+        for (line_number, raw_line) in raw_lines.iter().enumerate() {
+            // The lines of a block scalar are content, even when they look like comments.
+            if let Some(parent_indent) = block_scalar_indent {
+                let trimmed_line = raw_line.trim();
+                if trimmed_line.is_empty() {
+                    continue;
+                }
+                let indent = raw_line.len() - raw_line.trim_start().len();
+                if indent > parent_indent {
+                    lines.push(Line {
+                        number: line_number,
+                        indent,
+                        text: trimmed_line.to_string(),
+                    });
+                    continue;
+                }
+                block_scalar_indent = None;
+            }
+
+            let text = match find_comment(raw_line) {
+                Some(comment_index) => &raw_line[..comment_index],
+                None => raw_line,
+            };
+            let text = text.trim_end();
+            let trimmed_text = text.trim_start();
+            if trimmed_text.is_empty() || trimmed_text.starts_with('%') {
+                continue;
+            }
+            if trimmed_text == "---" || trimmed_text == "..." {
+                if lines.is_empty() {
+                    continue;
+                }
+                return Err(format!(
+                    "Multiple YAML documents are not supported (line {}).",
+                    line_number + 1
+                ));
+            }
+            if text.starts_with('\t') {
+                return Err(format!(
+                    "Tabs are not allowed for indentation (line {}).",
+                    line_number + 1
+                ));
+            }
+            let indent = text.len() - trimmed_text.len();
+            block_scalar_indent = get_block_scalar_indent(trimmed_text, indent);
+            has_anchors = has_anchors || has_anchor_or_alias(trimmed_text);
+            lines.push(Line {
+                number: line_number,
+                indent,
+                text: trimmed_text.to_string(),
+            });
+        }
+
+        let mut parser = Parser { lines, position: 0 };
+        let root = parser.parse_node(-1);
+        if let Some(line) = parser.lines.get(parser.position) {
+            return Err(format!("Unexpected content at line {}.", line.number + 1));
+        }
+
+        Ok(YamlDocument {
+            lines: raw_lines,
+            root,
+            has_final_newline: content.ends_with('\n'),
+            has_anchors,
+        })
+    }
+
+    pub fn dump(&self) -> String {
+        let mut content = self.lines.join("\n");
+        if self.has_final_newline {
+            content.push('\n');
+        }
+        content
+    }
+
+    /// Parses the document again, after its lines were edited.
+    fn reparse(&mut self) -> Result<(), String> {
+        *self = YamlDocument::parse(&self.dump())?;
+        Ok(())
+    }
+
+    /// Gets the node at the given JSON pointer.
+    pub fn get_node(&self, pointer: &str) -> Option<&YamlNode> {
+        let mut node = self.root.as_ref()?;
+        for component in get_pointer_components(pointer) {
+            node = match &node.kind {
+                YamlNodeKind::Mapping(entries) => {
+                    &entries.iter().find(|e| e.key == component)?.value
+                }
+                YamlNodeKind::Sequence(items) => items.get(component.parse::<usize>().ok()?)?,
+                YamlNodeKind::Scalar => return None,
+            };
+        }
+        Some(node)
+    }
+
+    /// Gets the lines of the node at the given JSON pointer as a document of its
+    /// own, with the comments inside the node.
+    pub fn get_node_content(&self, pointer: &str) -> Option<String> {
+        let node = self.get_node(pointer)?;
+        let mut content = String::new();
+        for line_number in node.start_line..=node.end_line {
+            let line = &self.lines[line_number];
+            // The first line can start with the dash of a sequence item.
+            let line = if line_number == node.start_line {
+                line.get(node.column..).unwrap_or("")
+            } else {
+                let indent = line.len() - line.trim_start().len();
+                &line[indent.min(node.column)..]
+            };
+            content.push_str(line);
+            content.push('\n');
+        }
+        Some(content)
+    }
+
+    /// Gets the line and column where the element at the given JSON pointer starts.
+    /// For the values of a mapping, this is the location of their key.
+    pub fn get_location(&self, pointer: &str) -> Option<(usize, usize)> {
+        let mut components = get_pointer_components(pointer);
+        let last_component = match components.pop() {
+            Some(c) => c,
+            None => {
+                let root = self.root.as_ref()?;
+                return Some((root.start_line, root.column));
+            }
+        };
+
+        let parent_pointer = to_pointer(&components);
+        let parent = self.get_node(&parent_pointer)?;
+        match &parent.kind {
+            YamlNodeKind::Mapping(entries) => {
+                let entry = entries.iter().find(|e| e.key == last_component)?;
+                Some((entry.key_line, entry.key_column))
+            }
+            YamlNodeKind::Sequence(items) => {
+                let item = items.get(last_component.parse::<usize>().ok()?)?;
+                Some((item.start_line, item.column))
+            }
+            YamlNodeKind::Scalar => None,
+        }
+    }
+
+    /// Gets the indentation style of the document, so the new nodes look like
+    /// the existing ones.
+    pub fn get_style(&self) -> YamlStyle {
+        let mut style = YamlStyle::default();
+        let mut found_mapping = false;
+        let mut found_sequence = false;
+        let mut nodes: Vec<&YamlNode> = self.root.iter().collect();
+        while let Some(node) = nodes.pop() {
+            match &node.kind {
+                YamlNodeKind::Mapping(entries) => {
+                    for entry in entries {
+                        let is_block = entry.value.start_line > entry.key_line;
+                        match &entry.value.kind {
+                            YamlNodeKind::Mapping(_) if is_block && !found_mapping => {
+                                style.mapping_indent = entry.value.column - entry.key_column;
+                                found_mapping = true;
+                            }
+                            YamlNodeKind::Sequence(_) if is_block && !found_sequence => {
+                                style.sequence_indent = entry.value.column - entry.key_column;
+                                found_sequence = true;
+                            }
+                            _ => {}
+                        };
+                        nodes.push(&entry.value);
+                    }
+                }
+                YamlNodeKind::Sequence(items) => nodes.extend(items),
+                YamlNodeKind::Scalar => {}
+            };
+        }
+        style
+    }
+
+    /// Gets the text before the column on a line, which contains the dash of the
+    /// sequence item for the first entry of a mapping in a sequence.
+    fn get_line_prefix(&self, line: usize, column: usize) -> String {
+        let line = &self.lines[line];
+        if line.len() < column {
+            return format!("{:1$}", line, column);
+        }
+        line[..column].to_string()
+    }
+
+    /// Gets the comment at the end of a line, with the whitespace before it.
+    fn get_trailing_comment(&self, line: usize) -> Option<String> {
+        let line = &self.lines[line];
+        let comment_index = find_comment(line)?;
+        if line[..comment_index].trim().is_empty() {
+            return None;
+        }
+        let content_end = line[..comment_index].trim_end().len();
+        Some(line[content_end..].to_string())
+    }
+
+    /// Gets the first line of the comments directly above `line`, at the same
+    /// column. These comments are attached to the node starting at `line`.
+    fn get_attached_comments_start(&self, line: usize, column: usize, min_line: usize) -> usize {
+        let mut start = line;
+        while start > min_line {
+            let previous_line = &self.lines[start - 1];
+            let trimmed_line = previous_line.trim_start();
+            if !trimmed_line.starts_with('#') || previous_line.len() - trimmed_line.len() != column
+            {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+
+    /// Gets the last of the blank lines following `line`, before `max_line`.
+    fn get_blank_lines_end(&self, line: usize, max_line: usize) -> usize {
+        let mut end = line;
+        while end + 1 < max_line && self.lines[end + 1].trim().is_empty() {
+            end += 1;
+        }
+        end
+    }
+
+    fn replace_lines(
+        &mut self,
+        start: usize,
+        end: usize,
+        new_lines: Vec<String>,
+    ) -> Result<(), String> {
+        self.lines.splice(start..end, new_lines);
+        self.reparse()
+    }
+
+    /// Replaces the element at the given JSON pointer, or adds it if the parent
+    /// mapping does not have it.
+    pub fn replace(&mut self, pointer: &str, value: &Value) -> Result<(), String> {
+        let style = self.get_style();
+        let mut components = get_pointer_components(pointer);
+        let last_component = match components.pop() {
+            Some(c) => c,
+            None => {
+                let line_count = self.lines.len();
+                return self.replace_lines(0, line_count, render_root(value, &style));
+            }
+        };
+        let parent_pointer = to_pointer(&components);
+        let parent = match self.get_node(&parent_pointer) {
+            Some(p) => p,
+            None => return Err(format!("There is no element at {}.", parent_pointer)),
+        };
+
+        let (start, end, mut new_lines) = match &parent.kind {
+            YamlNodeKind::Mapping(entries) => {
+                let entry = match entries.iter().find(|e| e.key == last_component) {
+                    Some(e) => e,
+                    None => {
+                        let last_key = entries.last().map(|e| e.key.to_string());
+                        return self.insert_entry(
+                            &parent_pointer,
+                            last_key.as_deref(),
+                            &last_component,
+                            value,
+                        );
+                    }
+                };
+                let mut new_lines = render_entry(&last_component, value, entry.key_column, &style);
+                new_lines[0] = format!(
+                    "{}{}",
+                    self.get_line_prefix(entry.key_line, entry.key_column),
+                    &new_lines[0][entry.key_column..]
+                );
+                if entry.value.start_line == entry.key_line {
+                    if let Some(new_line) = self.render_quoted_string(&entry.value, value) {
+                        new_lines = vec![new_line];
+                    }
+                }
+                (entry.key_line, entry.value.end_line, new_lines)
+            }
+            YamlNodeKind::Sequence(items) => {
+                let item = match last_component
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| items.get(i))
+                {
+                    Some(i) => i,
+                    None => return Err(format!("There is no element at {}.", pointer)),
+                };
+                let new_lines = match self.render_quoted_string(item, value) {
+                    Some(new_line) => vec![new_line],
+                    None => render_item(value, parent.column, &style),
+                };
+                (item.start_line, item.end_line, new_lines)
+            }
+            YamlNodeKind::Scalar => {
+                return Err(format!(
+                    "Cannot edit {} since {} is not a block mapping or sequence.",
+                    pointer, parent_pointer
+                ))
+            }
+        };
+
+        if let Some(comment) = self.get_trailing_comment(start) {
+            new_lines[0].push_str(&comment);
+        }
+        self.replace_lines(start, end + 1, new_lines)
+    }
+
+    /// Renders the line of a double-quoted string scalar replaced by another string,
+    /// so the new string is also double-quoted.
+    fn render_quoted_string(&self, node: &YamlNode, value: &Value) -> Option<String> {
+        let new_string = value.as_str()?;
+        if !matches!(node.kind, YamlNodeKind::Scalar) || node.start_line != node.end_line {
+            return None;
+        }
+        let line = &self.lines[node.start_line];
+        if !line.get(node.column..)?.starts_with('"') {
+            return None;
+        }
+        // JSON strings are valid double-quoted YAML strings.
+        let quoted_string = serde_json::to_string(new_string).ok()?;
+        Some(format!("{}{}", &line[..node.column], quoted_string))
+    }
+
+    /// Gets the text of a scalar or of a flow collection written on a single line,
+    /// without its comment.
+    fn get_inline_text(&self, node: &YamlNode) -> Option<&str> {
+        if !matches!(node.kind, YamlNodeKind::Scalar) || node.start_line != node.end_line {
+            return None;
+        }
+        let line = &self.lines[node.start_line];
+        let text_end = find_comment(line).unwrap_or(line.len());
+        let text = line.get(node.column..text_end)?.trim_end();
+        if text.is_empty() {
+            return None;
+        }
+        Some(text)
+    }
+
+    /// Replaces the element at the given JSON pointer by a single line of text, like
+    /// a quoted scalar or a flow collection.
+    fn replace_inline(&mut self, pointer: &str, text: &str) -> Result<(), String> {
+        let mut components = get_pointer_components(pointer);
+        let last_component = match components.pop() {
+            Some(c) => c,
+            None => return Err("Cannot replace the root of the document.".to_string()),
+        };
+        let parent = match self.get_node(&to_pointer(&components)) {
+            Some(p) => p,
+            None => return Err(format!("There is no element at {}.", pointer)),
+        };
+
+        let (start, node, prefix) = match &parent.kind {
+            YamlNodeKind::Mapping(entries) => {
+                let entry = match entries.iter().find(|e| e.key == last_component) {
+                    Some(e) => e,
+                    None => return Err(format!("There is no element at {}.", pointer)),
+                };
+                let key_line = &self.lines[entry.key_line];
+                let value_offset = match split_key(&key_line[entry.key_column..]) {
+                    Some((_, o)) => o,
+                    None => return Err(format!("There is no element at {}.", pointer)),
+                };
+                let prefix = format!("{} ", &key_line[..entry.key_column + value_offset]);
+                (entry.key_line, &entry.value, prefix)
+            }
+            YamlNodeKind::Sequence(items) => {
+                let item = match last_component
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| items.get(i))
+                {
+                    Some(i) => i,
+                    None => return Err(format!("There is no element at {}.", pointer)),
+                };
+                (item.start_line, item, format!("{:1$}- ", "", parent.column))
+            }
+            YamlNodeKind::Scalar => return Err(format!("There is no element at {}.", pointer)),
+        };
+        let new_line = match self.get_inline_text(node) {
+            Some(_) if node.start_line == start => {
+                format!("{}{}", &self.lines[start][..node.column], text)
+            }
+            _ => format!("{}{}", prefix, text),
+        };
+        let end = node.end_line;
+        self.replace_lines(start, end + 1, vec![new_line])
+    }
+
+    /// Replaces the item of a sequence at the given JSON pointer by the content of a
+    /// YAML document with a mapping, like a module manifest, so the comments and the
+    /// key order of the document are kept. The comments above the mapping are added
+    /// above the item.
+    pub fn replace_item_content(&mut self, pointer: &str, content: &str) -> Result<(), String> {
+        let item_document = YamlDocument::parse(content)?;
+        let item_root = match &item_document.root {
+            Some(r) if matches!(r.kind, YamlNodeKind::Mapping(_)) => r,
+            _ => return Err("The content to insert is not a mapping.".to_string()),
+        };
+
+        let mut components = get_pointer_components(pointer);
+        let last_component = match components.pop() {
+            Some(c) => c,
+            None => return Err("Cannot replace the root of the document.".to_string()),
+        };
+        let parent = match self.get_node(&to_pointer(&components)) {
+            Some(p) => p,
+            None => return Err(format!("There is no element at {}.", pointer)),
+        };
+        let item = match (&parent.kind, last_component.parse::<usize>()) {
+            (YamlNodeKind::Sequence(items), Ok(index)) if index < items.len() => &items[index],
+            _ => return Err(format!("There is no element at {}.", pointer)),
+        };
+
+        let dash_indent = parent.column;
+        let mut new_lines: Vec<String> = item_document.lines[..item_root.start_line]
+            .iter()
+            .map(|l| match l.trim() {
+                "" => String::new(),
+                l => format!("{:1$}{2}", "", dash_indent, l),
+            })
+            .collect();
+        let root_content = item_document.get_node_content("").unwrap_or_default();
+        for (line_index, line) in root_content.trim_end().lines().enumerate() {
+            new_lines.push(match line_index {
+                0 => format!("{:1$}- {2}", "", dash_indent, line),
+                _ if line.trim().is_empty() => String::new(),
+                _ => format!("{:1$}{2}", "", dash_indent + 2, line),
+            });
+        }
+        let (start, end) = (item.start_line, item.end_line);
+        self.replace_lines(start, end + 1, new_lines)
+    }
+
+    /// Removes the element at the given JSON pointer, with the comments directly above it.
+    pub fn remove(&mut self, pointer: &str) -> Result<(), String> {
+        let mut components = get_pointer_components(pointer);
+        let last_component = match components.pop() {
+            Some(c) => c,
+            None => return Err("Cannot remove the root of the document.".to_string()),
+        };
+        let parent_pointer = to_pointer(&components);
+        let parent = match self.get_node(&parent_pointer) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        match &parent.kind {
+            YamlNodeKind::Mapping(entries) => {
+                let entry_index = match entries.iter().position(|e| e.key == last_component) {
+                    Some(i) => i,
+                    None => return Ok(()),
+                };
+                if entries.len() == 1 {
+                    return self.replace(&parent_pointer, &Value::Mapping(Default::default()));
+                }
+                let entry = &entries[entry_index];
+                let prefix = self.get_line_prefix(entry.key_line, entry.key_column);
+                if prefix.trim().is_empty() {
+                    let min_line = match entry_index {
+                        0 => 0,
+                        i => entries[i - 1].value.end_line + 1,
+                    };
+                    let start = self.get_attached_comments_start(
+                        entry.key_line,
+                        entry.key_column,
+                        min_line,
+                    );
+                    let mut end = entry.value.end_line;
+                    if entry_index == 0 {
+                        end = self.get_blank_lines_end(end, entries[1].key_line);
+                    }
+                    return self.replace_lines(start, end + 1, vec![]);
+                }
+
+                // The entry starts a mapping in a sequence, so the dash of the
+                // item is moved to the next entry.
+                let next_entry = &entries[entry_index + 1];
+                let next_line = &self.lines[next_entry.key_line];
+                let next_line = format!("{}{}", prefix, &next_line[next_entry.key_column..]);
+                let (start, end, next_key_line) =
+                    (entry.key_line, entry.value.end_line, next_entry.key_line);
+                self.lines[next_key_line] = next_line;
+                self.replace_lines(start, end + 1, vec![])
+            }
+            YamlNodeKind::Sequence(items) => {
+                let item_index = match last_component.parse::<usize>() {
+                    Ok(i) if i < items.len() => i,
+                    _ => return Ok(()),
+                };
+                if items.len() == 1 {
+                    return self.replace(&parent_pointer, &Value::Sequence(vec![]));
+                }
+                let item = &items[item_index];
+                let min_line = match item_index {
+                    0 => 0,
+                    i => items[i - 1].end_line + 1,
+                };
+                let start =
+                    self.get_attached_comments_start(item.start_line, parent.column, min_line);
+                let mut end = item.end_line;
+                // The blank lines between the first item and the next one are removed,
+                // so the sequence does not start with a blank line.
+                if item_index == 0 {
+                    end = self.get_blank_lines_end(end, items[1].start_line);
+                }
+                self.replace_lines(start, end + 1, vec![])
+            }
+            YamlNodeKind::Scalar => Err(format!(
+                "Cannot edit {} since {} is not a block mapping or sequence.",
+                pointer, parent_pointer
+            )),
+        }
+    }
+
+    /// Inserts an item in the sequence at the given JSON pointer, at `index` or at
+    /// the end of the sequence.
+    pub fn insert_item(
+        &mut self,
+        pointer: &str,
+        index: Option<usize>,
+        value: &Value,
+    ) -> Result<(), String> {
+        let style = self.get_style();
+        let node = match self.get_node(pointer) {
+            Some(n) => n,
+            None => return Err(format!("There is no element at {}.", pointer)),
+        };
+        let items = match &node.kind {
+            YamlNodeKind::Sequence(items) => items,
+            _ => return Err(format!("{} is not a block sequence.", pointer)),
+        };
+
+        let new_lines = render_item(value, node.column, &style);
+        let position = match index {
+            Some(i) if i < items.len() => {
+                let min_line = match i {
+                    0 => 0,
+                    i => items[i - 1].end_line + 1,
+                };
+                self.get_attached_comments_start(items[i].start_line, node.column, min_line)
+            }
+            _ => node.end_line + 1,
+        };
+        self.replace_lines(position, position, new_lines)
+    }
+
+    /// Inserts an entry in the mapping at the given JSON pointer, after the entry
+    /// with `after_key` or else at the start of the mapping.
+    pub fn insert_entry(
+        &mut self,
+        pointer: &str,
+        after_key: Option<&str>,
+        key: &str,
+        value: &Value,
+    ) -> Result<(), String> {
+        let style = self.get_style();
+        let node = match self.get_node(pointer) {
+            Some(n) => n,
+            None => return Err(format!("There is no element at {}.", pointer)),
+        };
+        let entries = match &node.kind {
+            YamlNodeKind::Mapping(entries) => entries,
+            _ => {
+                let mut mapping = serde_yaml::Mapping::new();
+                mapping.insert(Value::String(key.to_string()), value.clone());
+                return self.replace(pointer, &Value::Mapping(mapping));
+            }
+        };
+
+        let new_lines = render_entry(key, value, node.column, &style);
+        let first_entry = &entries[0];
+        let starts_item = !self
+            .get_line_prefix(first_entry.key_line, first_entry.key_column)
+            .trim()
+            .is_empty();
+        let position = match after_key.and_then(|k| entries.iter().find(|e| e.key == k)) {
+            Some(e) => e.value.end_line + 1,
+            // The first entry of a mapping in a sequence holds the dash of the item.
+            None if starts_item => first_entry.value.end_line + 1,
+            None => self.get_attached_comments_start(first_entry.key_line, node.column, 0),
+        };
+        self.replace_lines(position, position, new_lines)
+    }
+
+    /// Updates the element at the given JSON pointer from `old_value` to `new_value`,
+    /// only editing the nodes that changed.
+    pub fn update(
+        &mut self,
+        pointer: &str,
+        old_value: &Value,
+        new_value: &Value,
+    ) -> Result<(), String> {
+        if old_value == new_value {
+            return Ok(());
+        }
+        let node_kind = self.get_node(pointer).map(|n| n.kind.clone());
+
+        match (old_value, new_value, node_kind) {
+            (
+                Value::Mapping(old_mapping),
+                Value::Mapping(new_mapping),
+                Some(YamlNodeKind::Mapping(_)),
+            ) => {
+                if old_mapping
+                    .keys()
+                    .chain(new_mapping.keys())
+                    .any(|k| k.as_str().is_none())
+                {
+                    return self.replace(pointer, new_value);
+                }
+                for key in old_mapping.keys() {
+                    if !new_mapping.contains_key(key) {
+                        self.remove(&get_child_pointer(pointer, key.as_str().unwrap()))?;
+                    }
+                }
+                let mut previous_key: Option<&str> = None;
+                for (key, value) in new_mapping {
+                    let key = key.as_str().unwrap();
+                    match old_mapping.get(key) {
+                        Some(old_value) => {
+                            self.update(&get_child_pointer(pointer, key), old_value, value)?
+                        }
+                        None => self.insert_entry(pointer, previous_key, key, value)?,
+                    };
+                    previous_key = Some(key);
+                }
+                Ok(())
+            }
+            (
+                Value::Sequence(old_items),
+                Value::Sequence(new_items),
+                Some(YamlNodeKind::Sequence(items)),
+            ) if items.len() == old_items.len() => {
+                // The items kept in the sequence are updated, so they keep their
+                // comments even when other items are added, removed or moved.
+                let matching_items = get_matching_items(old_items, new_items);
+                if matching_items.is_empty() {
+                    return self.replace(pointer, new_value);
+                }
+
+                for index in (0..old_items.len()).rev() {
+                    if !matching_items.iter().any(|(o, _)| *o == index) {
+                        self.remove(&get_child_pointer(pointer, &index.to_string()))?;
+                    }
+                }
+                for (index, new_item) in new_items.iter().enumerate() {
+                    match matching_items.iter().find(|(_, n)| *n == index) {
+                        Some((old_index, _)) => self.update(
+                            &get_child_pointer(pointer, &index.to_string()),
+                            &old_items[*old_index],
+                            new_item,
+                        )?,
+                        None => self.insert_item(pointer, Some(index), new_item)?,
+                    };
+                }
+                Ok(())
+            }
+            _ => self.replace(pointer, new_value),
+        }
+    }
+}
+
+/// Whether an item of a sequence is the same item once edited. The items of the
+/// manifests are identified by their name, like the modules.
+fn is_same_item(old_item: &Value, new_item: &Value) -> bool {
+    if old_item == new_item {
+        return true;
+    }
+    match (old_item.get("name"), new_item.get("name")) {
+        (Some(Value::String(old_name)), Some(Value::String(new_name))) => old_name == new_name,
+        _ => false,
+    }
+}
+
+/// Gets the indexes of the items of `old_items` that are updated to an item of
+/// `new_items`, in order. The other items are removed or added.
+fn get_matching_items(old_items: &[Value], new_items: &[Value]) -> Vec<(usize, usize)> {
+    let same_items = get_same_items(old_items, new_items);
+
+    // Between two items that did not change, the items at the same position are
+    // updated, like a scalar replaced by a mapping.
+    let mut matching_items: Vec<(usize, usize)> = vec![];
+    let (mut old_start, mut new_start) = (0, 0);
+    let ends = same_items
+        .iter()
+        .copied()
+        .chain(std::iter::once((old_items.len(), new_items.len())));
+    for (old_end, new_end) in ends {
+        let changed_length = (old_end - old_start).min(new_end - new_start);
+        for offset in 0..changed_length {
+            matching_items.push((old_start + offset, new_start + offset));
+        }
+        if old_end < old_items.len() {
+            matching_items.push((old_end, new_end));
+        }
+        old_start = old_end + 1;
+        new_start = new_end + 1;
+    }
+    matching_items
+}
+
+/// Gets the indexes of the items of `old_items` that are also in `new_items`, in
+/// order. This is the longest common subsequence of the two sequences.
+fn get_same_items(old_items: &[Value], new_items: &[Value]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0; new_items.len() + 1]; old_items.len() + 1];
+    for old_index in (0..old_items.len()).rev() {
+        for new_index in (0..new_items.len()).rev() {
+            lengths[old_index][new_index] =
+                if is_same_item(&old_items[old_index], &new_items[new_index]) {
+                    lengths[old_index + 1][new_index + 1] + 1
+                } else {
+                    lengths[old_index + 1][new_index].max(lengths[old_index][new_index + 1])
+                };
+        }
+    }
+
+    let mut matching_items: Vec<(usize, usize)> = vec![];
+    let (mut old_index, mut new_index) = (0, 0);
+    while old_index < old_items.len() && new_index < new_items.len() {
+        if is_same_item(&old_items[old_index], &new_items[new_index]) {
+            matching_items.push((old_index, new_index));
+            old_index += 1;
+            new_index += 1;
+        } else if lengths[old_index + 1][new_index] >= lengths[old_index][new_index + 1] {
+            old_index += 1;
+        } else {
+            new_index += 1;
+        }
+    }
+    matching_items
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    position: usize,
+}
+impl Parser {
+    fn current_line(&self) -> Option<&Line> {
+        self.lines.get(self.position)
+    }
+
+    fn previous_line_number(&self) -> usize {
+        self.lines[self.position - 1].number
+    }
+
+    /// Parses the node starting at the current line, if the line is indented more
+    /// than `parent_indent`.
+    fn parse_node(&mut self, parent_indent: isize) -> Option<YamlNode> {
+        let line = self.current_line()?;
+        if line.indent as isize <= parent_indent {
+            return None;
+        }
+        let indent = line.indent;
+        if is_sequence_item(&line.text) {
+            return Some(self.parse_sequence(indent));
+        }
+        if split_key(&line.text).is_some() {
+            return Some(self.parse_mapping(indent));
+        }
+        let (start_line, column) = (line.number, line.indent);
+        Some(self.parse_scalar(start_line, column, parent_indent))
+    }
+
+    /// Parses a scalar starting on the current line. The following lines that
+    /// are indented more than `parent_indent` are part of the scalar.
+    fn parse_scalar(&mut self, start_line: usize, column: usize, parent_indent: isize) -> YamlNode {
+        self.position += 1;
+        while let Some(line) = self.current_line() {
+            if line.indent as isize <= parent_indent {
+                break;
+            }
+            self.position += 1;
+        }
+        YamlNode {
+            kind: YamlNodeKind::Scalar,
+            start_line,
+            end_line: self.previous_line_number(),
+            column,
+        }
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> YamlNode {
+        let start_line = self.current_line().unwrap().number;
+        let mut entries: Vec<YamlEntry> = vec![];
+
+        while let Some(line) = self.current_line() {
+            if line.indent != indent || is_sequence_item(&line.text) {
+                break;
+            }
+            let (key, value_offset) = match split_key(&line.text) {
+                Some(k) => k,
+                None => break,
+            };
+            let key_line = line.number;
+            let value_text = line.text[value_offset..].trim_start().to_string();
+            let value_column = indent + line.text.len() - value_text.len();
+
+            let is_empty_value = value_text.is_empty()
+                || ((value_text.starts_with('&') || value_text.starts_with('!'))
+                    && !value_text.contains(' '));
+            let value = if is_empty_value {
+                self.position += 1;
+                let next_line = self.current_line();
+                let value = match next_line {
+                    Some(l) if l.indent == indent && is_sequence_item(&l.text) => {
+                        Some(self.parse_sequence(indent))
+                    }
+                    _ => self.parse_node(indent as isize),
+                };
+                match value {
+                    Some(v) => v,
+                    None => YamlNode {
+                        kind: YamlNodeKind::Scalar,
+                        start_line: key_line,
+                        end_line: key_line,
+                        column: value_column,
+                    },
+                }
+            } else {
+                self.parse_scalar(key_line, value_column, indent as isize)
+            };
+
+            entries.push(YamlEntry {
+                key,
+                key_line,
+                key_column: indent,
+                value,
+            });
+        }
+
+        YamlNode {
+            kind: YamlNodeKind::Mapping(entries),
+            start_line,
+            end_line: self.previous_line_number(),
+            column: indent,
+        }
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> YamlNode {
+        let start_line = self.current_line().unwrap().number;
+        let mut items: Vec<YamlNode> = vec![];
+
+        while let Some(line) = self.current_line() {
+            if line.indent != indent || !is_sequence_item(&line.text) {
+                break;
+            }
+            let item_line = line.number;
+            let item_text = line.text[1..].trim_start().to_string();
+
+            if item_text.is_empty() {
+                self.position += 1;
+                let item = match self.parse_node(indent as isize) {
+                    Some(mut i) => {
+                        i.start_line = item_line;
+                        i
+                    }
+                    None => YamlNode {
+                        kind: YamlNodeKind::Scalar,
+                        start_line: item_line,
+                        end_line: item_line,
+                        column: indent,
+                    },
+                };
+                items.push(item);
+                continue;
+            }
+
+            // The content of the item starts on the same line as the dash, so
+            // it is parsed as if it was on its own line, at the same column.
+            let item_indent = indent + line.text.len() - item_text.len();
+            self.lines[self.position] = Line {
+                number: item_line,
+                indent: item_indent,
+                text: item_text,
+            };
+            let mut item = self.parse_node(indent as isize).unwrap();
+            item.start_line = item_line;
+            items.push(item);
+        }
+
+        YamlNode {
+            kind: YamlNodeKind::Sequence(items),
+            start_line,
+            end_line: self.previous_line_number(),
+            column: indent,
+        }
+    }
+}
+
+fn is_sequence_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+/// Gets the indentation that the lines of a block scalar must exceed, if the
+/// value on a line starts a block scalar, like `key: |`.
+fn get_block_scalar_indent(text: &str, indent: usize) -> Option<usize> {
+    let (mut text, mut indent) = (text, indent);
+    let mut parent_indent: Option<usize> = None;
+    while is_sequence_item(text) {
+        let item_text = text[1..].trim_start();
+        parent_indent = Some(indent);
+        indent += text.len() - item_text.len();
+        text = item_text;
+    }
+    let value = match split_key(text) {
+        Some((_, value_offset)) => {
+            parent_indent = Some(indent);
+            text[value_offset..].trim()
+        }
+        None => text,
+    };
+    let header = value
+        .strip_prefix('|')
+        .or_else(|| value.strip_prefix('>'))?;
+    if header.len() > 2
+        || !header
+            .chars()
+            .all(|c| c == '+' || c == '-' || c.is_ascii_digit())
+    {
+        return None;
+    }
+    parent_indent
+}
+
+/// Gets the index where the quoted string starting at `start` ends, or the
+/// length of the text if the string is not closed.
+fn get_quoted_string_end(text: &str, start: usize) -> usize {
+    let bytes = text.as_bytes();
+    let quote = bytes[start];
+    let mut index = start + 1;
+    while index < bytes.len() {
+        if quote == b'"' && bytes[index] == b'\\' {
+            index += 2;
+            continue;
+        }
+        if bytes[index] == quote {
+            // Single quotes are escaped by doubling them.
+            if quote == b'\'' && bytes.get(index + 1) == Some(&b'\'') {
+                index += 2;
+                continue;
+            }
+            return index + 1;
+        }
+        index += 1;
+    }
+    bytes.len()
+}
+
+/// Quotes only start a string at the beginning of a token.
+fn is_token_start(text: &str, index: usize) -> bool {
+    if index == 0 {
+        return true;
+    }
+    matches!(
+        text.as_bytes()[index - 1],
+        b' ' | b'[' | b'{' | b',' | b':' | b'-'
+    )
+}
+
+/// Gets the index where the comment starts in a line, if the line has a comment.
+pub fn find_comment(line: &str) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' | b'\'' if is_token_start(line, index) => {
+                index = get_quoted_string_end(line, index);
+                continue;
+            }
+            b'#' if index == 0 || bytes[index - 1] == b' ' || bytes[index - 1] == b'\t' => {
+                return Some(index);
+            }
+            _ => {}
+        };
+        index += 1;
+    }
+    None
+}
+
+/// Splits a mapping entry, and returns its key and the index where its value starts.
+fn split_key(text: &str) -> Option<(String, usize)> {
+    if text.starts_with('[') || text.starts_with('{') {
+        return None;
+    }
+
+    if text.starts_with('"') || text.starts_with('\'') {
+        let key_end = get_quoted_string_end(text, 0);
+        let rest = &text[key_end..];
+        let rest_trimmed = rest.trim_start();
+        if !rest_trimmed.starts_with(':') {
+            return None;
+        }
+        let colon_index = key_end + rest.len() - rest_trimmed.len();
+        if text.len() > colon_index + 1 && !text[colon_index + 1..].starts_with(' ') {
+            return None;
+        }
+        let key = unquote(&text[..key_end]);
+        return Some((key, colon_index + 1));
+    }
+
+    let bytes = text.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b':' && (index + 1 == bytes.len() || bytes[index + 1] == b' ') {
+            return Some((text[..index].trim_end().to_string(), index + 1));
+        }
+        index += 1;
+    }
+    None
+}
+
+fn unquote(text: &str) -> String {
+    let inner = &text[1..text.len().max(2) - 1];
+    if text.starts_with('\'') {
+        return inner.replace("''", "'");
+    }
+    match serde_json::from_str::<String>(text) {
+        Ok(s) => s,
+        Err(_) => inner.to_string(),
+    }
+}
+
+/// Splits a JSON pointer into its unescaped components.
+pub fn get_pointer_components(pointer: &str) -> Vec<String> {
+    if pointer.is_empty() {
+        return vec![];
+    }
+    pointer
+        .strip_prefix('/')
+        .unwrap_or(pointer)
+        .split('/')
+        .map(|c| c.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+/// Builds a JSON pointer from its unescaped components.
+pub fn to_pointer(components: &[String]) -> String {
+    components
+        .iter()
+        .map(|c| format!("/{}", c.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+pub fn get_child_pointer(pointer: &str, component: &str) -> String {
+    format!("{}{}", pointer, to_pointer(&[component.to_string()]))
+}
+
+#[derive(Clone, Copy, Debug)]
+/// The indentation used for the nested nodes of a document.
+pub struct YamlStyle {
+    /// The indentation of the entries of a nested mapping, relative to its key.
+    pub mapping_indent: usize,
+    /// The indentation of the dashes of a nested sequence, relative to its key.
+    pub sequence_indent: usize,
+}
+impl Default for YamlStyle {
+    /// The style of the manifests dumped by flatpak-rs.
+    fn default() -> Self {
+        YamlStyle {
+            mapping_indent: 2,
+            sequence_indent: 0,
+        }
+    }
+}
+
+fn is_block_collection(value: &Value) -> bool {
+    match value {
+        Value::Mapping(m) => !m.is_empty(),
+        Value::Sequence(s) => !s.is_empty(),
+        _ => false,
+    }
+}
+
+/// Renders a scalar, or an empty collection. Multi-line strings are rendered as
+/// block scalars, so the following lines are indented relatively to the line
+/// of the scalar.
+fn render_scalar(value: &Value) -> Vec<String> {
+    let scalar_dump = serde_yaml::to_string(value).unwrap_or_else(|_| "null".to_string());
+    scalar_dump
+        .trim_end_matches('\n')
+        .lines()
+        .map(|l| l.to_string())
+        .collect()
+}
+
+fn render_entry(key: &str, value: &Value, indent: usize, style: &YamlStyle) -> Vec<String> {
+    let key = render_scalar(&Value::String(key.to_string())).join(" ");
+    let padding = " ".repeat(indent);
+
+    let mut lines: Vec<String> = vec![];
+    match value {
+        Value::Mapping(mapping) if !mapping.is_empty() => {
+            lines.push(format!("{}{}:", padding, key));
+            for (child_key, child_value) in mapping {
+                let child_key = render_scalar(child_key).join(" ");
+                lines.append(&mut render_entry(
+                    &child_key,
+                    child_value,
+                    indent + style.mapping_indent,
+                    style,
+                ));
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            lines.push(format!("{}{}:", padding, key));
+            for item in items {
+                lines.append(&mut render_item(
+                    item,
+                    indent + style.sequence_indent,
+                    style,
+                ));
+            }
+        }
+        _ => {
+            let scalar_lines = render_scalar(value);
+            lines.push(format!("{}{}: {}", padding, key, scalar_lines[0]));
+            for scalar_line in &scalar_lines[1..] {
+                lines.push(format!("{}{}", padding, scalar_line));
+            }
+        }
+    };
+    lines
+}
+
+fn render_item(value: &Value, dash_indent: usize, style: &YamlStyle) -> Vec<String> {
+    let content_indent = dash_indent + 2;
+    let mut lines: Vec<String> = match value {
+        Value::Mapping(mapping) if is_block_collection(value) => mapping
+            .iter()
+            .flat_map(|(k, v)| render_entry(&render_scalar(k).join(" "), v, content_indent, style))
+            .collect(),
+        Value::Sequence(items) if is_block_collection(value) => items
+            .iter()
+            .flat_map(|i| render_item(i, content_indent, style))
+            .collect(),
+        _ => {
+            let mut scalar_lines = render_scalar(value);
+            for scalar_line in scalar_lines.iter_mut().skip(1) {
+                *scalar_line = format!("{}{}", " ".repeat(dash_indent), scalar_line);
+            }
+            scalar_lines[0] = format!("{}{}", " ".repeat(content_indent), scalar_lines[0]);
+            scalar_lines
+        }
+    };
+    lines[0] = format!(
+        "{}- {}",
+        " ".repeat(dash_indent),
+        &lines[0][content_indent..]
+    );
+    lines
+}
+
+fn render_root(value: &Value, style: &YamlStyle) -> Vec<String> {
+    match value {
+        Value::Mapping(mapping) if is_block_collection(value) => mapping
+            .iter()
+            .flat_map(|(k, v)| render_entry(&render_scalar(k).join(" "), v, 0, style))
+            .collect(),
+        Value::Sequence(items) if is_block_collection(value) => items
+            .iter()
+            .flat_map(|i| render_item(i, 0, style))
+            .collect(),
+        _ => render_scalar(value),
+    }
+}
+
+/// Updates the content of a YAML manifest from `old_value` to `new_value`. Only
+/// the nodes that changed are edited, so the rest of the file is unchanged.
+pub fn update_content(
+    content: &str,
+    old_value: &Value,
+    new_value: &Value,
+) -> Result<String, String> {
+    let mut document = YamlDocument::parse(content)?;
+    // Editing a node with an anchor would drop the anchor, or silently change
+    // the nodes that are aliases of it.
+    if document.has_anchors {
+        return Err("the anchors and aliases of the manifest cannot be kept.".to_string());
+    }
+    document.update("", old_value, new_value)?;
+    Ok(document.dump())
+}
+
+/// Replaces the item of a sequence at the given JSON pointer of a YAML content by
+/// the content of another YAML document, keeping the comments of both.
+pub fn replace_item_content(
+    content: &str,
+    pointer: &str,
+    item_content: &str,
+) -> Result<String, String> {
+    let mut document = YamlDocument::parse(content)?;
+    document.replace_item_content(pointer, item_content)?;
+    Ok(document.dump())
+}
+
+/// Whether a YAML content uses anchors or aliases.
+pub fn has_anchors(content: &str) -> bool {
+    match YamlDocument::parse(content) {
+        Ok(d) => d.has_anchors,
+        Err(_) => false,
+    }
+}
+
+/// Whether a line has an anchor or an alias, like `base: &base` or `- *base`. They
+/// are only found where a node starts, since `&` and `*` are valid in plain scalars.
+fn has_anchor_or_alias(text: &str) -> bool {
+    let mut text = text;
+    while is_sequence_item(text) {
+        text = text[1..].trim_start();
+    }
+    if let Some((_, value_offset)) = split_key(text) {
+        text = text[value_offset..].trim_start();
+    }
+    if text.starts_with('&') || text.starts_with('*') {
+        return true;
+    }
+    if !text.starts_with('[') && !text.starts_with('{') {
+        return false;
+    }
+
+    // The nodes of a flow collection start after the brackets, the commas and the colons.
+    let bytes = text.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' | b'\'' if is_token_start(text, index) => {
+                index = get_quoted_string_end(text, index);
+                continue;
+            }
+            b'[' | b'{' | b',' | b':' => {
+                let node_text = text[index + 1..].trim_start();
+                if node_text.starts_with('&') || node_text.starts_with('*') {
+                    return true;
+                }
+            }
+            _ => {}
+        };
+        index += 1;
+    }
+    false
+}
+
+/// Gets the JSON pointer and the text of the nodes written on a single line, like the
+/// scalars and the flow collections, in document order.
+fn get_inline_nodes(
+    document: &YamlDocument,
+    node: &YamlNode,
+    pointer: &str,
+    inline_nodes: &mut Vec<(String, String)>,
+) {
+    let children: Vec<(String, &YamlNode)> = match &node.kind {
+        YamlNodeKind::Mapping(entries) => entries
+            .iter()
+            .map(|e| (get_child_pointer(pointer, &e.key), &e.value))
+            .collect(),
+        YamlNodeKind::Sequence(items) => items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (get_child_pointer(pointer, &i.to_string()), item))
+            .collect(),
+        YamlNodeKind::Scalar => vec![],
+    };
+    for (child_pointer, child) in children {
+        match document.get_inline_text(child) {
+            Some(text) => inline_nodes.push((child_pointer, text.to_string())),
+            None => get_inline_nodes(document, child, &child_pointer, inline_nodes),
+        };
+    }
+}
+
+/// Copies the text of the scalars and flow collections of `original_document` to the
+/// matching nodes of `formatted_document` when their value did not change, so that
+/// formatting keeps their quotes and their flow style.
+fn keep_inline_nodes(
+    original_document: &YamlDocument,
+    formatted_document: &YamlDocument,
+) -> Result<YamlDocument, String> {
+    let (original_root, original_value, formatted_value) = match (
+        &original_document.root,
+        serde_yaml::from_str::<serde_json::Value>(&original_document.dump()),
+        serde_yaml::from_str::<serde_json::Value>(&formatted_document.dump()),
+    ) {
+        (Some(r), Ok(o), Ok(f)) => (r, o, f),
+        _ => return Ok(formatted_document.clone()),
+    };
+
+    let mut inline_nodes: Vec<(String, String)> = vec![];
+    get_inline_nodes(original_document, original_root, "", &mut inline_nodes);
+
+    let mut document = formatted_document.clone();
+    for (pointer, text) in inline_nodes {
+        let value = original_value.pointer(&pointer);
+        if value.is_none() || value != formatted_value.pointer(&pointer) {
+            continue;
+        }
+        let formatted_text = document
+            .get_node(&pointer)
+            .and_then(|n| document.get_inline_text(n));
+        if formatted_text != Some(&text) {
+            document.replace_inline(&pointer, &text)?;
+        }
+    }
+    Ok(document)
+}
+
+/// Gets the first line of every node, with the JSON pointer of the node, in
+/// document order.
+fn get_node_lines(
+    node: &YamlNode,
+    pointer: &str,
+    line: usize,
+    node_lines: &mut Vec<(String, usize)>,
+) {
+    node_lines.push((pointer.to_string(), line));
+    match &node.kind {
+        YamlNodeKind::Mapping(entries) => {
+            for entry in entries {
+                let entry_pointer = get_child_pointer(pointer, &entry.key);
+                get_node_lines(&entry.value, &entry_pointer, entry.key_line, node_lines);
+            }
+        }
+        YamlNodeKind::Sequence(items) => {
+            for (index, item) in items.iter().enumerate() {
+                let item_pointer = get_child_pointer(pointer, &index.to_string());
+                get_node_lines(item, &item_pointer, item.start_line, node_lines);
+            }
+        }
+        YamlNodeKind::Scalar => {
+            // The lines of a multi-line scalar are content, even when they look
+            // like comments.
+            for scalar_line in node.start_line + 1..=node.end_line {
+                node_lines.push(("".to_string(), scalar_line));
+            }
+        }
+    };
+}
+
+/// Indents the nested nodes of a document with the given style.
+fn apply_style(document: &YamlDocument, style: &YamlStyle) -> Result<YamlDocument, String> {
+    let root = match &document.root {
+        Some(r) => r,
+        None => return Ok(document.clone()),
+    };
+    let mut shifts: Vec<isize> = vec![0; document.lines.len()];
+    get_style_shifts(root, 0, style, &mut shifts);
+
+    let mut styled_document = document.clone();
+    for (line, shift) in styled_document.lines.iter_mut().zip(shifts) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if shift >= 0 {
+            *line = format!("{}{}", " ".repeat(shift as usize), line);
+        } else {
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            *line = line[indent.min(-shift as usize)..].to_string();
+        }
+    }
+    styled_document.reparse()?;
+    Ok(styled_document)
+}
+
+/// Gets how much every line of a node has to be shifted, so its nested nodes
+/// are indented with the given style.
+fn get_style_shifts(node: &YamlNode, shift: isize, style: &YamlStyle, shifts: &mut [isize]) {
+    for line_shift in &mut shifts[node.start_line..=node.end_line] {
+        *line_shift = shift;
+    }
+    match &node.kind {
+        YamlNodeKind::Mapping(entries) => {
+            for entry in entries {
+                let mut value_shift = shift;
+                if entry.value.start_line > entry.key_line {
+                    let indent = entry.value.column as isize - entry.key_column as isize;
+                    value_shift = match &entry.value.kind {
+                        YamlNodeKind::Mapping(_) => shift + style.mapping_indent as isize - indent,
+                        YamlNodeKind::Sequence(_) => {
+                            shift + style.sequence_indent as isize - indent
+                        }
+                        YamlNodeKind::Scalar => shift,
+                    };
+                }
+                get_style_shifts(&entry.value, value_shift, style, shifts);
+            }
+        }
+        YamlNodeKind::Sequence(items) => {
+            for item in items {
+                get_style_shifts(item, shift, style, shifts);
+            }
+        }
+        YamlNodeKind::Scalar => {}
+    };
+}
+
+/// The comments and blank lines around a node.
+#[derive(Default)]
+struct NodeComments {
+    leading_lines: Vec<String>,
+    trailing_comment: Option<String>,
+}
+
+/// Copies the comments and blank lines of `original_content` to the matching nodes
+/// of `formatted_content`, which is the same manifest dumped by flatpak-rs.
+pub fn reattach_comments(
+    original_content: &str,
+    formatted_content: &str,
+) -> Result<String, String> {
+    let original_document = YamlDocument::parse(original_content)?;
+    let formatted_document = YamlDocument::parse(formatted_content)?;
+    // The formatted content uses the indentation of the original content, so
+    // formatting does not re-indent the whole file.
+    let formatted_document = apply_style(&formatted_document, &original_document.get_style())?;
+    let formatted_document = keep_inline_nodes(&original_document, &formatted_document)?;
+    let (original_root, formatted_root) = match (&original_document.root, &formatted_document.root)
+    {
+        (Some(o), Some(f)) => (o, f),
+        _ => return Ok(formatted_content.to_string()),
+    };
+
+    let mut original_node_lines: Vec<(String, usize)> = vec![];
+    get_node_lines(
+        original_root,
+        "",
+        original_root.start_line,
+        &mut original_node_lines,
+    );
+    let mut content_lines: Vec<bool> = vec![false; original_document.lines.len()];
+    for (_, line) in &original_node_lines {
+        content_lines[*line] = true;
+    }
+
+    let mut comments: HashMap<String, NodeComments> = HashMap::new();
+    let mut previous_content_line: Option<usize> = None;
+    for (pointer, line) in &original_node_lines {
+        if pointer.is_empty() && *line != original_root.start_line {
+            previous_content_line = Some(*line);
+            continue;
+        }
+        let is_first_node_on_line = previous_content_line != Some(*line);
+        let node_comments = comments.entry(pointer.to_string()).or_default();
+        if is_first_node_on_line {
+            let first_trivia_line = previous_content_line.map(|l| l + 1).unwrap_or(0);
+            for trivia_line in first_trivia_line..*line {
+                node_comments
+                    .leading_lines
+                    .push(original_document.lines[trivia_line].trim().to_string());
+            }
+        }
+        previous_content_line = Some(*line);
+    }
+    // The trailing comment of a line goes to the last node starting on the line.
+    let mut last_node_on_line: HashMap<usize, String> = HashMap::new();
+    for (pointer, line) in &original_node_lines {
+        if !pointer.is_empty() || *line == original_root.start_line {
+            last_node_on_line.insert(*line, pointer.to_string());
+        }
+    }
+    for (line, pointer) in last_node_on_line {
+        if let Some(trailing_comment) = original_document.get_trailing_comment(line) {
+            comments.entry(pointer).or_default().trailing_comment = Some(trailing_comment);
+        }
+    }
+    let last_content_line = content_lines.iter().rposition(|l| *l).unwrap_or(0);
+    let footer_lines: Vec<String> = original_document.lines[last_content_line + 1..]
+        .iter()
+        .map(|l| l.trim().to_string())
+        .collect();
+
+    let mut formatted_node_lines: Vec<(String, usize)> = vec![];
+    get_node_lines(
+        formatted_root,
+        "",
+        formatted_root.start_line,
+        &mut formatted_node_lines,
+    );
+    let mut leading_lines: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut trailing_comments: HashMap<usize, String> = HashMap::new();
+    for (pointer, line) in &formatted_node_lines {
+        if pointer.is_empty() && *line != formatted_root.start_line {
+            continue;
+        }
+        let node_comments = match comments.get(pointer) {
+            Some(c) => c,
+            None => continue,
+        };
+        leading_lines
+            .entry(*line)
+            .or_default()
+            .extend(node_comments.leading_lines.iter().cloned());
+        if let Some(trailing_comment) = &node_comments.trailing_comment {
+            trailing_comments.insert(*line, trailing_comment.to_string());
+        }
+    }
+
+    let mut lines: Vec<String> = vec![];
+    for (line_index, line) in formatted_document.lines.iter().enumerate() {
+        let indent = line.len() - line.trim_start().len();
+        for leading_line in leading_lines.remove(&line_index).unwrap_or_default() {
+            push_trivia_line(&mut lines, &leading_line, indent);
+        }
+        match trailing_comments.get(&line_index) {
+            Some(comment) => lines.push(format!("{}{}", line, comment)),
+            None => lines.push(line.to_string()),
+        };
+    }
+    for footer_line in footer_lines {
+        push_trivia_line(&mut lines, &footer_line, 0);
+    }
+
+    let mut content = lines.join("\n");
+    if formatted_document.has_final_newline {
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// Adds a comment or a blank line at the given indentation. Consecutive blank
+/// lines are merged.
+fn push_trivia_line(lines: &mut Vec<String>, trivia_line: &str, indent: usize) {
+    if trivia_line.is_empty() {
+        if lines.last().map(|l| l.is_empty()).unwrap_or(true) {
+            return;
+        }
+        lines.push("".to_string());
+        return;
+    }
+    lines.push(format!("{}{}", " ".repeat(indent), trivia_line));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"# The application.
+app-id: org.example.App
+runtime-version: "49"
+finish-args:
+  - --share=network # For the updates.
+modules:
+  # The dependency.
+  - name: dependency
+    build-commands:
+      - make
+  - name: app
+    post-install: |
+      # Not a comment.
+      install -D app /app/bin/app
+      # Still not a comment.
+  # Before the footer.
+"#;
+
+    fn to_value(content: &str) -> Value {
+        serde_yaml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn parse_locates_the_nodes() {
+        let document = YamlDocument::parse(MANIFEST).unwrap();
+        assert_eq!(document.get_location("/app-id"), Some((1, 0)));
+        assert_eq!(document.get_location("/finish-args/0"), Some((4, 4)));
+        assert_eq!(document.get_location("/modules/1"), Some((10, 4)));
+        assert_eq!(document.get_location("/modules/1/name"), Some((10, 4)));
+        assert_eq!(document.get_location("/modules/2"), None);
+        assert_eq!(
+            document.get_node_content("/modules/0/build-commands"),
+            Some("- make\n".to_string())
+        );
+        assert_eq!(document.dump(), MANIFEST);
+    }
+
+    #[test]
+    fn parse_keeps_the_comment_lines_of_block_scalars() {
+        let document = YamlDocument::parse(MANIFEST).unwrap();
+        let post_install = document.get_node("/modules/1/post-install").unwrap();
+        assert_eq!((post_install.start_line, post_install.end_line), (11, 14));
+        assert_eq!(document.get_location("/modules"), Some((5, 0)));
+    }
+
+    #[test]
+    fn parse_rejects_unsupported_documents() {
+        assert!(YamlDocument::parse("a: 1\n---\nb: 2\n").is_err());
+        assert!(YamlDocument::parse("a:\n\t- 1\n").is_err());
+    }
+
+    #[test]
+    fn replace_keeps_the_trailing_comment() {
+        let mut document = YamlDocument::parse(MANIFEST).unwrap();
+        document
+            .replace("/finish-args/0", &Value::String("--share=ipc".to_string()))
+            .unwrap();
+        document
+            .replace("/runtime-version", &Value::String("50".to_string()))
+            .unwrap();
+        let content = document.dump();
+        assert!(content.contains("  - --share=ipc # For the updates.\n"));
+        assert!(content.contains("runtime-version: \"50\"\n"));
+        assert_eq!(
+            content
+                .replace("--share=ipc", "--share=network")
+                .replace("50", "49"),
+            MANIFEST
+        );
+    }
+
+    #[test]
+    fn replace_adds_a_missing_entry() {
+        let mut document = YamlDocument::parse(MANIFEST).unwrap();
+        document
+            .replace(
+                "/modules/0/buildsystem",
+                &Value::String("simple".to_string()),
+            )
+            .unwrap();
+        assert!(document
+            .dump()
+            .contains("    build-commands:\n      - make\n    buildsystem: simple\n"));
+        assert!(document.replace("/missing/key", &Value::Null).is_err());
+    }
+
+    #[test]
+    fn insert_item_before_the_attached_comments() {
+        let mut document = YamlDocument::parse(MANIFEST).unwrap();
+        document
+            .insert_item("/modules", Some(0), &to_value("name: first"))
+            .unwrap();
+        document
+            .insert_item(
+                "/finish-args",
+                None,
+                &Value::String("--share=ipc".to_string()),
+            )
+            .unwrap();
+        let content = document.dump();
+        assert!(content.contains("modules:\n  - name: first\n  # The dependency.\n"));
+        assert!(content.contains("# For the updates.\n  - --share=ipc\n"));
+        assert!(document.insert_item("/app-id", None, &Value::Null).is_err());
+    }
+
+    #[test]
+    fn update_only_edits_the_changed_nodes() {
+        let old_value = to_value(MANIFEST);
+        let mut new_value = old_value.clone();
+        let modules = new_value["modules"].as_sequence_mut().unwrap();
+        modules.swap(0, 1);
+        modules[0]["name"] = Value::String("application".to_string());
+        new_value["finish-args"]
+            .as_sequence_mut()
+            .unwrap()
+            .push(Value::String("--socket=x11".to_string()));
+
+        let content = update_content(MANIFEST, &old_value, &new_value).unwrap();
+        assert_eq!(to_value(&content), new_value);
+        assert!(content.starts_with("# The application.\n"));
+        assert!(content.contains("  - --share=network # For the updates.\n  - --socket=x11\n"));
+        assert!(content.contains("      # Not a comment.\n"));
+        assert!(content.contains("  # The dependency.\n  - name: dependency\n"));
+    }
+
+    #[test]
+    fn reattach_comments_to_the_formatted_nodes() {
+        let formatted_content = serde_yaml::to_string(&to_value(MANIFEST)).unwrap();
+        let content = reattach_comments(MANIFEST, &formatted_content).unwrap();
+        assert_eq!(to_value(&content), to_value(MANIFEST));
+        assert!(content.starts_with("# The application.\napp-id: org.example.App\n"));
+        assert!(content.contains("  - --share=network # For the updates.\n"));
+        assert!(content.contains("  # The dependency.\n  - name: dependency\n"));
+        assert!(content.contains("      # Still not a comment.\n"));
+        assert!(content.ends_with("# Before the footer.\n"));
+    }
+
+    #[test]
+    fn reattach_comments_keeps_the_quotes_and_the_flow_style() {
+        let original_content = "runtime-version: \"49\"\nfinish-args: [--share=ipc, '--socket=x11'] # Flow.\nmodules:\n  - name: app\n    config-opts: [\"--prefix=/app\"]\n    build-options: {cflags: -O2}\n";
+        let formatted_content = serde_yaml::to_string(&to_value(original_content)).unwrap();
+        let content = reattach_comments(original_content, &formatted_content).unwrap();
+        assert_eq!(content, original_content);
+    }
+
+    #[test]
+    fn reattach_comments_formats_the_changed_nodes() {
+        let original_content = "runtime-version: \"49\"\nfinish-args: [--share=ipc] # Flow.\n";
+        let mut formatted_value = to_value(original_content);
+        formatted_value["runtime-version"] = Value::String("50".to_string());
+        formatted_value["finish-args"]
+            .as_sequence_mut()
+            .unwrap()
+            .push(Value::String("--socket=x11".to_string()));
+        let formatted_content = serde_yaml::to_string(&formatted_value).unwrap();
+        let content = reattach_comments(original_content, &formatted_content).unwrap();
+        assert_eq!(
+            content,
+            "runtime-version: '50'\nfinish-args: # Flow.\n- --share=ipc\n- --socket=x11\n"
+        );
+    }
+
+    #[test]
+    fn anchors_and_aliases() {
+        for content in [
+            "build-options: &options\n  cflags: -O2\n",
+            "build-options: *options\n",
+            "<<: *base\n",
+            "modules:\n  - *module\n",
+            "finish-args: [--share=ipc, *x11]\n",
+            "build-options: {cflags: &cflags -O2}\n",
+        ] {
+            assert!(
+                YamlDocument::parse(content).unwrap().has_anchors,
+                "{}",
+                content
+            );
+        }
+        for content in [
+            "build-commands:\n  - cp *.so /app/lib\n",
+            "command: a&b\n",
+            "finish-args: ['*x11', \"&x11\"]\n",
+            "post-install: |\n  *not an alias\n",
+            "app-id: org.example.App # &not-an-anchor\n",
+        ] {
+            assert!(
+                !YamlDocument::parse(content).unwrap().has_anchors,
+                "{}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn update_refuses_anchors_and_aliases() {
+        let content = "modules:\n  - name: a\n    build-options: &options\n      cflags: -O2\n  - name: b\n    build-options: *options\n";
+        let old_value = to_value(content);
+        let mut new_value = old_value.clone();
+        new_value["modules"][1]["name"] = Value::String("c".to_string());
+        assert!(update_content(content, &old_value, &new_value).is_err());
+    }
+
+    #[test]
+    fn replace_item_content_keeps_the_comments() {
+        let mut document =
+            YamlDocument::parse("modules:\n  - name: a\n  - name: zlib\n    sources: []\n")
+                .unwrap();
+        document
+            .replace_item_content(
+                "/modules/1",
+                "# The compression library.\nname: zlib # 1.3\n\nsources:\n  - type: dir\n    path: zlib\n",
+            )
+            .unwrap();
+        assert_eq!(
+            document.dump(),
+            "modules:\n  - name: a\n  # The compression library.\n  - name: zlib # 1.3\n\n    sources:\n      - type: dir\n        path: zlib\n"
+        );
+        assert!(document
+            .replace_item_content("/modules/2", "name: b\n")
+            .is_err());
+        assert!(document
+            .replace_item_content("/modules/0", "- name: b\n")
+            .is_err());
+    }
+
+    #[test]
+    fn reattach_comments_keeps_the_indentation() {
+        for original_content in [
+            "modules:\n- name: app\n  sources:\n  - type: dir\n    path: .\n",
+            "modules:\n  - name: app\n    sources:\n      - type: dir\n        path: .\n",
+            "build-options:\n    env:\n        V: x\n",
+        ] {
+            let formatted_content = serde_yaml::to_string(&to_value(original_content)).unwrap();
+            let content = reattach_comments(original_content, &formatted_content).unwrap();
+            assert_eq!(content, original_content);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Tests of the bootstrap command on local source trees.
+mod common;
+
+use std::fs;
+
+use common::{fpcli, get_test_dir, stderr, write_file};
+
+const SERDE_SHA256: &str = "8b917c4b6163bc82ef4aff025c6f5f4d54205232c4595f39b7b43008256a6cb7";
+
+/// Writes a cargo project depending on serde, with its lockfile.
+fn write_cargo_project(project_dir: &std::path::Path) {
+    write_file(
+        &project_dir.join("Cargo.toml"),
+        "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\n",
+    );
+    write_file(
+        &project_dir.join("Cargo.lock"),
+        &format!(
+            "version = 3\n\n[[package]]\nname = \"hello\"\nversion = \"0.1.0\"\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.200\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\nchecksum = \"{}\"\n",
+            SERDE_SHA256
+        ),
+    );
+}
+
+#[test]
+fn bootstrap_an_application_with_the_sources_of_the_lockfile() {
+    let test_dir = get_test_dir("bootstrap_an_application_with_the_sources_of_the_lockfile");
+    write_cargo_project(&test_dir.join("hello"));
+    let output = fpcli(&test_dir)
+        .args(["bootstrap", "--from", "hello", "--output", "build/app.yaml"])
+        .output()
+        .unwrap();
+    // The output directory does not exist yet.
+    assert!(!output.status.success());
+
+    fs::create_dir_all(test_dir.join("build")).unwrap();
+    let output = fpcli(&test_dir)
+        .args(["bootstrap", "--from", "hello", "--output", "build/app.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let manifest = fs::read_to_string(test_dir.join("build/app.yaml")).unwrap();
+    assert!(
+        manifest
+            .contains("  sources:\n  - type: dir\n    path: ../hello\n  - cargo-sources.json\n"),
+        "{}",
+        manifest
+    );
+    let sources = fs::read_to_string(test_dir.join("build/cargo-sources.json")).unwrap();
+    assert!(sources.contains(SERDE_SHA256));
+
+    // The generated sources are not replaced.
+    fs::remove_file(test_dir.join("build/app.yaml")).unwrap();
+    let output = fpcli(&test_dir)
+        .args(["bootstrap", "--from", "hello", "--output", "build/app.yaml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("cargo-sources.json already exists."));
+    assert!(!test_dir.join("build/app.yaml").exists());
+}
+
+#[test]
+fn bootstrap_an_application_without_a_lockfile() {
+    let test_dir = get_test_dir("bootstrap_an_application_without_a_lockfile");
+    write_file(
+        &test_dir.join("hello/meson.build"),
+        "project('hello', 'c')\n",
+    );
+    let output = fpcli(&test_dir)
+        .args(["bootstrap", "--from", "hello", "--output", "app.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let manifest = fs::read_to_string(test_dir.join("app.yaml")).unwrap();
+    assert!(manifest.contains("  sources:\n  - type: dir\n    path: hello\n  config-opts:"));
+    assert!(!test_dir.join("cargo-sources.json").exists());
+}
@@ -0,0 +1,65 @@
+//! Helpers shared by the integration tests, which run the fpcli binary.
+#![allow(dead_code)]
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path;
+use std::process::{Command, Output};
+
+/// Gets an empty directory for a test, inside the temporary directory of cargo.
+pub fn get_test_dir(name: &str) -> path::PathBuf {
+    let test_dir = path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if test_dir.exists() {
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+    fs::create_dir_all(&test_dir).unwrap();
+    test_dir
+}
+
+/// Writes a file, creating its parent directories.
+pub fn write_file(file_path: &path::Path, content: &str) {
+    fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+    fs::write(file_path, content).unwrap();
+}
+
+pub fn write_executable(file_path: &path::Path, content: &str) {
+    write_file(file_path, content);
+    fs::set_permissions(file_path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Gets a command running fpcli from `dir`, with a cache directory inside `dir`.
+pub fn fpcli(dir: &path::Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fpcli"));
+    command.current_dir(dir);
+    command.env("XDG_CACHE_HOME", dir.join("cache"));
+    command
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+/// Runs git in `dir`, with an identity so that commits can be created.
+pub fn git(dir: &path::Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=fpcli",
+            "-c",
+            "user.email=fpcli@example.org",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?}: {}",
+        args,
+        stderr(&output)
+    );
+    stdout(&output).trim().to_string()
+}
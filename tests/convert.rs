@@ -0,0 +1,139 @@
+//! Tests of the convert command.
+mod common;
+
+use std::fs;
+use std::path;
+
+use common::{fpcli, get_test_dir, stderr, write_file};
+
+const APPLICATION_MANIFEST: &str = r#"
+app-id: org.example.App
+runtime: org.example.Platform
+runtime-version: '1.0'
+sdk: org.example.Sdk
+command: app
+finish-args:
+  - --share=ipc
+modules:
+  - name: app
+    sources:
+      - type: dir
+        path: .
+    buildsystem: simple
+    build-commands:
+      - install -D app /app/bin/app
+"#;
+
+const MODULE_MANIFEST: &str = r#"
+name: zlib
+sources:
+  - type: archive
+    url: https://example.org/zlib-1.3.tar.gz
+    sha256: 6667b2d1aab6a00caa5aee5af8ad9f1465e567abf1c209d15727d57b3e8f6e5f
+config-opts:
+  - --static
+"#;
+
+const SOURCES_MANIFEST: &str = r#"
+- type: git
+  url: https://example.org/app.git
+  tag: v1.0
+- type: file
+  path: app.desktop
+"#;
+
+fn convert(test_dir: &path::Path, path: &str, format_name: &str, output: &str) {
+    let output = fpcli(test_dir)
+        .args(["convert", path, format_name, "-o", output])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+fn read_yaml(file_path: &path::Path) -> serde_yaml::Value {
+    serde_yaml::from_str(&fs::read_to_string(file_path).unwrap()).unwrap()
+}
+
+#[test]
+fn convert_round_trip() {
+    let test_dir = get_test_dir("convert_round_trip");
+    for (file_name, manifest) in [
+        ("org.example.App.yaml", APPLICATION_MANIFEST),
+        ("zlib.yaml", MODULE_MANIFEST),
+        ("sources.yaml", SOURCES_MANIFEST),
+    ] {
+        let name = file_name.trim_end_matches(".yaml");
+        write_file(&test_dir.join(file_name), manifest);
+        convert(&test_dir, file_name, "json", &format!("{}-1.json", name));
+        convert(
+            &test_dir,
+            &format!("{}-1.json", name),
+            "yaml",
+            &format!("{}-2.yaml", name),
+        );
+        convert(
+            &test_dir,
+            &format!("{}-2.yaml", name),
+            "json",
+            &format!("{}-3.json", name),
+        );
+
+        assert_eq!(
+            read_yaml(&test_dir.join(format!("{}-2.yaml", name))),
+            read_yaml(&test_dir.join(file_name)),
+            "{}",
+            file_name
+        );
+        assert_eq!(
+            fs::read_to_string(test_dir.join(format!("{}-3.json", name))).unwrap(),
+            fs::read_to_string(test_dir.join(format!("{}-1.json", name))).unwrap(),
+            "{}",
+            file_name
+        );
+    }
+}
+
+#[test]
+fn convert_in_place_changes_the_extension() {
+    let test_dir = get_test_dir("convert_in_place_changes_the_extension");
+    write_file(&test_dir.join("zlib.yml"), MODULE_MANIFEST);
+    let output = fpcli(&test_dir)
+        .args(["convert", "zlib.yml", "json", "--in-place"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!test_dir.join("zlib.yml").exists());
+    let module: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_dir.join("zlib.json")).unwrap()).unwrap();
+    assert_eq!(module["name"], "zlib");
+}
+
+#[test]
+fn convert_does_not_overwrite_the_output() {
+    let test_dir = get_test_dir("convert_does_not_overwrite_the_output");
+    write_file(&test_dir.join("zlib.yaml"), MODULE_MANIFEST);
+    write_file(&test_dir.join("zlib.json"), "{}\n");
+
+    let output = fpcli(&test_dir)
+        .args(["convert", "zlib.yaml", "json", "-o", "zlib.json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("zlib.json already exists."));
+
+    let output = fpcli(&test_dir)
+        .args(["convert", "zlib.yaml", "json", "--in-place"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("zlib.json already exists."));
+
+    assert_eq!(
+        fs::read_to_string(test_dir.join("zlib.json")).unwrap(),
+        "{}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(test_dir.join("zlib.yaml")).unwrap(),
+        MODULE_MANIFEST
+    );
+}
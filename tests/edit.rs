@@ -0,0 +1,125 @@
+//! Tests of the add-module command.
+mod common;
+
+use std::fs;
+use std::path;
+
+use common::{fpcli, get_test_dir, stderr, write_file};
+
+const MANIFEST: &str = r#"app-id: org.example.App
+runtime: org.gnome.Platform
+runtime-version: "49"
+sdk: org.gnome.Sdk
+modules:
+  # The application.
+  - name: app
+    sources:
+      - type: dir
+        path: .
+    modules:
+      - name: glib
+"#;
+
+const MODULE_MANIFEST: &str = r#"# The compression library.
+name: zlib
+buildsystem: simple
+build-commands:
+  - make install # Without the tests.
+sources:
+  - type: patch
+    path: zlib.patch
+"#;
+
+/// Writes the application manifest, and the zlib module in `shared/zlib.yaml`.
+fn write_manifests(test_dir: &path::Path) {
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    write_file(&test_dir.join("shared/zlib.yaml"), MODULE_MANIFEST);
+}
+
+fn add_module(test_dir: &path::Path, args: &[&str]) -> String {
+    let output = fpcli(test_dir)
+        .args(["add-module", "org.example.App.yaml", "shared/zlib.yaml"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap()
+}
+
+#[test]
+fn add_module_before_and_after_a_module() {
+    let test_dir = get_test_dir("add_module_before_and_after_a_module");
+    write_manifests(&test_dir);
+    let content = add_module(&test_dir, &["--before", "app"]);
+    assert!(
+        content.contains("modules:\n  - shared/zlib.yaml\n  # The application.\n  - name: app\n"),
+        "{}",
+        content
+    );
+
+    write_manifests(&test_dir);
+    let content = add_module(&test_dir, &["--after", "glib"]);
+    // The module is added next to the other module, at any depth.
+    assert_eq!(
+        content,
+        MANIFEST.replace(
+            "      - name: glib\n",
+            "      - name: glib\n      - shared/zlib.yaml\n"
+        )
+    );
+
+    write_manifests(&test_dir);
+    let output = fpcli(&test_dir)
+        .args([
+            "add-module",
+            "org.example.App.yaml",
+            "shared/zlib.yaml",
+            "--before",
+            "missing",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Could not find module missing."));
+    assert_eq!(
+        fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap(),
+        MANIFEST
+    );
+}
+
+#[test]
+fn add_module_to_a_parent_module() {
+    let test_dir = get_test_dir("add_module_to_a_parent_module");
+    write_manifests(&test_dir);
+    let content = add_module(&test_dir, &["--parent", "app"]);
+    assert_eq!(
+        content,
+        MANIFEST.replace(
+            "      - name: glib\n",
+            "      - name: glib\n      - shared/zlib.yaml\n"
+        )
+    );
+}
+
+#[test]
+fn add_inline_module_keeps_its_formatting() {
+    let test_dir = get_test_dir("add_inline_module_keeps_its_formatting");
+    write_manifests(&test_dir);
+    let content = add_module(&test_dir, &["--inline", "--parent", "app"]);
+    assert_eq!(
+        content,
+        MANIFEST.replace(
+            "      - name: glib\n",
+            r#"      - name: glib
+      # The compression library.
+      - name: zlib
+        buildsystem: simple
+        build-commands:
+          - make install # Without the tests.
+        sources:
+          - type: patch
+            path: shared/zlib.patch
+"#
+        )
+    );
+}
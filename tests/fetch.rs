@@ -0,0 +1,152 @@
+//! Tests of the fetch and cache commands, with file:// URLs.
+mod common;
+
+use std::fs;
+use std::path;
+
+use common::{fpcli, get_test_dir, stderr, stdout, write_file};
+
+/// Writes a file to download, and a manifest with a file source for it.
+fn write_manifest(test_dir: &path::Path, sha256: &str) {
+    let manifest = format!(
+        r#"
+name: module
+buildsystem: simple
+build-commands:
+  - install -D data.txt ${{FLATPAK_DEST}}/share/data.txt
+sources:
+  - type: file
+    url: file://{}
+    sha256: {}
+"#,
+        test_dir.join("data.txt").display(),
+        sha256
+    );
+    write_file(&test_dir.join("module.yaml"), &manifest);
+}
+
+const DATA: &str = "data\n";
+const DATA_SHA256: &str = "6667b2d1aab6a00caa5aee5af8ad9f1465e567abf1c209d15727d57b3e8f6e5f";
+const UNUSED: &str = "unused\n";
+const UNUSED_SHA256: &str = "2a37b3fecb9e5b1ea21167eae81c526f9a1449a226ccb1ce87b83e068ae79f23";
+
+fn get_downloads_dir(test_dir: &path::Path) -> path::PathBuf {
+    test_dir.join("cache/fpcli/downloads")
+}
+
+#[test]
+fn fetch_downloads_the_sources_once() {
+    let test_dir = get_test_dir("fetch_downloads_the_sources_once");
+    write_file(&test_dir.join("data.txt"), DATA);
+    write_manifest(&test_dir, DATA_SHA256);
+
+    let output = fpcli(&test_dir)
+        .args(["fetch", "module.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("Downloaded file://"));
+    let cached_file = get_downloads_dir(&test_dir).join(DATA_SHA256);
+    assert_eq!(fs::read_to_string(&cached_file).unwrap(), DATA);
+
+    let output = fpcli(&test_dir)
+        .args(["fetch", "module.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).ends_with("is already cached.\n"));
+}
+
+#[test]
+fn fetch_fails_on_a_wrong_checksum() {
+    let test_dir = get_test_dir("fetch_fails_on_a_wrong_checksum");
+    write_file(&test_dir.join("data.txt"), "other data\n");
+    write_manifest(&test_dir, DATA_SHA256);
+
+    let output = fpcli(&test_dir)
+        .args(["fetch", "module.yaml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Wrong sha256"));
+    assert_eq!(
+        fs::read_dir(get_downloads_dir(&test_dir)).unwrap().count(),
+        0
+    );
+}
+
+#[test]
+fn fetch_accepts_uppercase_checksums() {
+    let test_dir = get_test_dir("fetch_accepts_uppercase_checksums");
+    write_file(&test_dir.join("data.txt"), DATA);
+    write_manifest(&test_dir, &DATA_SHA256.to_ascii_uppercase());
+    let output = fpcli(&test_dir)
+        .args(["fetch", "module.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(get_downloads_dir(&test_dir).join(DATA_SHA256).is_file());
+}
+
+#[test]
+fn fetch_rejects_checksums_that_are_paths() {
+    let test_dir = get_test_dir("fetch_rejects_checksums_that_are_paths");
+    write_file(&test_dir.join("data.txt"), DATA);
+    write_file(&test_dir.join("victim.txt"), "victim\n");
+    let victim_path = test_dir.join("victim.txt");
+    let relative_victim_path = "../../../victim.txt";
+    for sha256 in [victim_path.to_str().unwrap(), relative_victim_path] {
+        write_manifest(&test_dir, sha256);
+        let output = fpcli(&test_dir)
+            .args(["fetch", "module.yaml"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(
+            stderr(&output).contains("Invalid sha256"),
+            "{}",
+            stderr(&output)
+        );
+        assert!(victim_path.is_file());
+    }
+}
+
+#[test]
+fn cache_gc_removes_the_unused_files() {
+    let test_dir = get_test_dir("cache_gc_removes_the_unused_files");
+    write_file(&test_dir.join("data.txt"), DATA);
+    write_manifest(&test_dir, DATA_SHA256);
+    let output = fpcli(&test_dir)
+        .args(["fetch", "module.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    // A valid cache entry, which is not used by the manifest.
+    let unused_file = get_downloads_dir(&test_dir).join(UNUSED_SHA256);
+    write_file(&unused_file, UNUSED);
+    // A cache entry whose content does not match its checksum.
+    let corrupted_file = get_downloads_dir(&test_dir).join("a".repeat(64));
+    write_file(&corrupted_file, UNUSED);
+
+    let output = fpcli(&test_dir).args(["cache", "ls"]).output().unwrap();
+    assert!(stdout(&output).contains("3 file(s)"));
+
+    let output = fpcli(&test_dir)
+        .args(["cache", "gc", "module.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.contains(&format!("Removed {} (unused).", UNUSED_SHA256)));
+    assert!(stdout.contains(&format!("Removed {} (corrupted).", "a".repeat(64))));
+    assert!(!unused_file.exists());
+    assert!(!corrupted_file.exists());
+    assert!(get_downloads_dir(&test_dir).join(DATA_SHA256).is_file());
+
+    let output = fpcli(&test_dir).args(["cache", "clear"]).output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fs::read_dir(get_downloads_dir(&test_dir)).unwrap().count(),
+        0
+    );
+}
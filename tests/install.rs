@@ -0,0 +1,336 @@
+//! Tests of the install command in the build sandbox, with a fake flatpak
+//! command that logs its arguments and runs the build commands on the host.
+mod common;
+
+use std::fs;
+use std::path;
+
+use common::{fpcli, get_test_dir, stderr, write_executable, write_file};
+
+const FAKE_FLATPAK: &str = r#"#!/bin/sh
+echo "$@" >> "$FAKE_FLATPAK_LOG"
+case "$1" in
+  build-init)
+    mkdir -p "$2/files" && touch "$2/metadata"
+    ;;
+  build)
+    shift
+    while [ "${1#--}" != "$1" ]; do
+      case "$1" in
+        --build-dir=*) cd "${1#--build-dir=}" || exit 1 ;;
+      esac
+      shift
+    done
+    # The application directory.
+    shift
+    exec "$@"
+    ;;
+esac
+"#;
+
+const APPLICATION_MANIFEST: &str = r#"
+app-id: org.example.App
+runtime: org.example.Platform
+runtime-version: "1.0"
+sdk: org.example.Sdk
+modules:
+  - name: dependency
+    buildsystem: simple
+    build-commands:
+      - echo building dependency
+      - touch dependency-built
+    sources:
+      - type: dir
+        path: dependency
+  - name: app
+    buildsystem: simple
+    build-commands:
+      - echo building app
+    sources:
+      - type: dir
+        path: app
+"#;
+
+/// Writes the fake flatpak command and the application manifest, and gets the
+/// install command using the fake flatpak command.
+fn setup(test_dir: &path::Path, manifest: &str) -> std::process::Command {
+    let bin_dir = test_dir.join("bin");
+    write_executable(&bin_dir.join("flatpak"), FAKE_FLATPAK);
+    write_file(&test_dir.join("org.example.App.yaml"), manifest);
+    write_file(&test_dir.join("dependency/README"), "dependency");
+    write_file(&test_dir.join("app/README"), "app");
+
+    let mut command = fpcli(test_dir);
+    command.arg("install");
+    command.env("FAKE_FLATPAK_LOG", test_dir.join("flatpak.log"));
+    command.env(
+        "PATH",
+        format!(
+            "{}:{}",
+            bin_dir.display(),
+            std::env::var("PATH").unwrap_or_default()
+        ),
+    );
+    command
+}
+
+fn get_flatpak_calls(test_dir: &path::Path) -> Vec<String> {
+    fs::read_to_string(test_dir.join("flatpak.log"))
+        .unwrap()
+        .lines()
+        .map(|l| l.to_string())
+        .collect()
+}
+
+#[test]
+fn install_initializes_the_sandbox_from_the_manifest() {
+    let test_dir = get_test_dir("install_initializes_the_sandbox_from_the_manifest");
+    let output = setup(&test_dir, APPLICATION_MANIFEST)
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let state_dir = fs::canonicalize(test_dir.join(".fpcli")).unwrap();
+    let calls = get_flatpak_calls(&test_dir);
+    assert_eq!(
+        calls[0],
+        format!(
+            "build-init {} org.example.App org.example.Sdk org.example.Platform 1.0",
+            state_dir.join("app").display()
+        )
+    );
+    assert!(state_dir.join("app/metadata").is_file());
+}
+
+#[test]
+fn install_runs_the_commands_in_the_sandbox_in_build_order() {
+    let test_dir = get_test_dir("install_runs_the_commands_in_the_sandbox_in_build_order");
+    let output = setup(&test_dir, APPLICATION_MANIFEST)
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let state_dir = fs::canonicalize(test_dir.join(".fpcli")).unwrap();
+    let build_dir = state_dir.join("build/dependency");
+    let calls = get_flatpak_calls(&test_dir);
+    assert_eq!(calls.len(), 4);
+    assert_eq!(
+        calls[1],
+        format!(
+            "build --die-with-parent --filesystem={0} --build-dir={0} {1} /bin/sh -c echo building dependency",
+            build_dir.display(),
+            state_dir.join("app").display()
+        )
+    );
+    assert!(calls[3].ends_with("/bin/sh -c echo building app"));
+
+    // The sources are copied in the build directory, where the commands run.
+    assert!(build_dir.join("README").is_file());
+    assert!(build_dir.join("dependency-built").is_file());
+
+    // The output of the commands is streamed with the module name, and logged.
+    let stdout = common::stdout(&output);
+    assert!(stdout.contains("[dependency] building dependency"));
+    assert!(stdout.contains("[app] building app"));
+    let log = fs::read_to_string(state_dir.join("logs/app.log")).unwrap();
+    assert_eq!(log, "building app\n");
+}
+
+#[test]
+fn install_does_not_initialize_an_initialized_sandbox() {
+    let test_dir = get_test_dir("install_does_not_initialize_an_initialized_sandbox");
+    let output = setup(&test_dir, APPLICATION_MANIFEST)
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    fs::remove_file(test_dir.join("flatpak.log")).unwrap();
+
+    let output = setup(&test_dir, APPLICATION_MANIFEST)
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let calls = get_flatpak_calls(&test_dir);
+    assert!(calls.iter().all(|c| !c.starts_with("build-init")));
+}
+
+#[test]
+fn install_stops_at_the_first_failing_module() {
+    let test_dir = get_test_dir("install_stops_at_the_first_failing_module");
+    let manifest = APPLICATION_MANIFEST.replace("touch dependency-built", "exit 3");
+    let output = setup(&test_dir, &manifest)
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    let calls = get_flatpak_calls(&test_dir);
+    assert!(calls.iter().all(|c| !c.contains("echo building app")));
+    assert!(stderr(&output).contains("Failed to install module dependency"));
+}
+
+#[test]
+fn install_requires_a_runtime_version_for_module_manifests() {
+    let test_dir = get_test_dir("install_requires_a_runtime_version_for_module_manifests");
+    write_file(
+        &test_dir.join("module.yaml"),
+        "name: module\nbuildsystem: simple\nbuild-commands:\n  - echo module\nsources:\n  - type: dir\n    path: app\n",
+    );
+    let output = setup(&test_dir, APPLICATION_MANIFEST)
+        .args(["module.yaml", "--sdk", "org.example.Sdk"])
+        .args(["--runtime", "org.example.Platform"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--runtime-version"));
+    assert!(!test_dir.join("flatpak.log").exists());
+
+    let output = setup(&test_dir, APPLICATION_MANIFEST)
+        .args(["module.yaml", "--sdk", "org.example.Sdk"])
+        .args([
+            "--runtime",
+            "org.example.Platform",
+            "--runtime-version",
+            "1.0",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let calls = get_flatpak_calls(&test_dir);
+    assert!(
+        calls[0].ends_with("org.flatpak.fpcli.Install org.example.Sdk org.example.Platform 1.0")
+    );
+}
+
+#[test]
+fn install_print_does_not_create_the_state_dir() {
+    let test_dir = get_test_dir("install_print_does_not_create_the_state_dir");
+    for args in [vec!["--print"], vec!["--bare", "--print"]] {
+        let output = setup(&test_dir, APPLICATION_MANIFEST)
+            .arg("org.example.App.yaml")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(!test_dir.join(".fpcli").exists());
+        assert!(!test_dir.join("flatpak.log").exists());
+    }
+}
+
+#[test]
+fn install_print_includes_the_build_environment() {
+    let test_dir = get_test_dir("install_print_includes_the_build_environment");
+    let manifest = APPLICATION_MANIFEST.replace(
+        "sdk: org.example.Sdk\n",
+        "sdk: org.example.Sdk\nbuild-options:\n  cflags: -O2 -g\n  env:\n    GREETING: it's built\n",
+    );
+    let output = setup(&test_dir, &manifest)
+        .args(["org.example.App.yaml", "--bare", "--print"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = common::stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    let build_dir = std::env::current_dir()
+        .unwrap()
+        .join(&test_dir)
+        .join(".fpcli/build/dependency");
+    assert!(lines[0].starts_with(&format!("cd {} && ", build_dir.display())));
+    assert!(lines[0].contains(" CFLAGS='-O2 -g' GREETING='it'\\''s built' "));
+    assert!(lines[0].contains(" PATH="));
+    assert!(lines[0].ends_with(" /bin/sh -c 'echo building dependency'"));
+}
+
+#[test]
+fn install_passes_the_build_options_to_the_sandbox() {
+    let test_dir = get_test_dir("install_passes_the_build_options_to_the_sandbox");
+    let manifest = APPLICATION_MANIFEST.replace(
+        "sdk: org.example.Sdk\n",
+        "sdk: org.example.Sdk\nbuild-options:\n  cflags: -O2 -g\n  append-path: /app/tools/bin\n  env:\n    GREETING: hello\n",
+    );
+    let output = setup(&test_dir, &manifest)
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let calls = get_flatpak_calls(&test_dir);
+    assert!(calls[1].contains(" --env=CFLAGS=-O2 -g "));
+    assert!(calls[1].contains(" --env=PATH=/app/bin:/usr/bin:/app/tools/bin "));
+    assert!(calls[1].contains(" --env=GREETING=hello "));
+    // The search paths that are not changed by the build options are left to the sandbox.
+    assert!(!calls[1].contains("--env=LD_LIBRARY_PATH="));
+}
+
+#[test]
+fn install_print_does_not_require_a_runtime_for_module_manifests() {
+    let test_dir = get_test_dir("install_print_does_not_require_a_runtime_for_module_manifests");
+    write_file(
+        &test_dir.join("module.yaml"),
+        "name: module\nbuildsystem: simple\nbuild-options:\n  cxxflags: -O3\nbuild-commands:\n  - echo module\nsources:\n  - type: dir\n    path: app\n",
+    );
+    let output = setup(&test_dir, APPLICATION_MANIFEST)
+        .args(["module.yaml", "--print"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = common::stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains(" flatpak build --die-with-parent "));
+    assert!(lines[0].contains(" --env=CXXFLAGS=-O3 "));
+    assert!(lines[0].ends_with(" /bin/sh -c 'echo module'"));
+    assert!(!test_dir.join("flatpak.log").exists());
+}
+
+#[test]
+fn install_rejects_module_names_outside_of_the_state_dir() {
+    let test_dir = get_test_dir("install_rejects_module_names_outside_of_the_state_dir");
+    write_file(&test_dir.join("victim/important"), "important");
+    for name in ["../../victim", "/victim", "..", ""] {
+        let manifest =
+            APPLICATION_MANIFEST.replace("name: dependency", &format!("name: '{}'", name));
+        for args in [vec!["--bare"], vec!["--bare", "--print"], vec![]] {
+            let output = setup(&test_dir, &manifest)
+                .arg("org.example.App.yaml")
+                .args(&args)
+                .output()
+                .unwrap();
+            assert!(!output.status.success());
+            assert!(stderr(&output).contains("Invalid module name"));
+            assert!(test_dir.join("victim/important").is_file());
+            assert!(!test_dir.join(".fpcli/build").exists());
+            assert!(!test_dir.join(".fpcli/logs").exists());
+        }
+    }
+}
+
+#[test]
+fn install_runs_the_shell_sources_in_the_sandbox() {
+    let test_dir = get_test_dir("install_runs_the_shell_sources_in_the_sandbox");
+    let manifest = APPLICATION_MANIFEST.replace(
+        "        path: app\n",
+        "        path: app\n      - type: shell\n        commands:\n          - touch shell-ran\n",
+    );
+    let output = setup(&test_dir, &manifest)
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let state_dir = fs::canonicalize(test_dir.join(".fpcli")).unwrap();
+    let build_dir = state_dir.join("build/app");
+    let calls = get_flatpak_calls(&test_dir);
+    assert!(calls.contains(&format!(
+        "build --die-with-parent --filesystem={0} --build-dir={0} {1} /bin/sh -c touch shell-ran",
+        build_dir.display(),
+        state_dir.join("app").display()
+    )));
+    assert!(build_dir.join("shell-ran").is_file());
+}
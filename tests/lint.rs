@@ -0,0 +1,230 @@
+//! Tests of the output of the lint command.
+mod common;
+
+use std::process::Stdio;
+
+use common::{fpcli, get_test_dir, stderr, write_file};
+
+/// Writes a manifest with enough diagnostics to fill a pipe.
+fn write_manifest_with_diagnostics(test_dir: &std::path::Path) {
+    let mut manifest =
+        "app-id: org.example.App\nruntime: org.gnome.Platform\nruntime-version: \"49\"\nsdk: org.gnome.Sdk\nmodules:\n"
+            .to_string();
+    for module_index in 0..500 {
+        manifest += &format!(
+            "  - name: module-{0}\n    sources:\n      - type: archive\n        url: https://example.org/module-{0}.tar.gz\n",
+            module_index
+        );
+    }
+    write_file(&test_dir.join("org.example.App.yaml"), &manifest);
+}
+
+#[test]
+fn lint_output_to_a_closed_pipe() {
+    let test_dir = get_test_dir("lint_output_to_a_closed_pipe");
+    write_manifest_with_diagnostics(&test_dir);
+    for output_format in ["text", "json", "sarif"] {
+        let mut child = fpcli(&test_dir)
+            .args(["lint", "--check", "--output-format", output_format])
+            .arg("org.example.App.yaml")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        drop(child.stdout.take());
+        let output = child.wait_with_output().unwrap();
+        assert!(!stderr(&output).contains("panicked"), "{}", stderr(&output));
+        // The manifest has errors, which is not a crash.
+        assert_eq!(output.status.code(), Some(1));
+    }
+}
+
+#[test]
+fn lint_json_output() {
+    let test_dir = get_test_dir("lint_json_output");
+    write_manifest_with_diagnostics(&test_dir);
+    let output = fpcli(&test_dir)
+        .args(["lint", "--check", "--output-format", "json"])
+        .arg("org.example.App.yaml")
+        .output()
+        .unwrap();
+    let diagnostics: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics: Vec<&serde_json::Value> = diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|d| d["rule_id"] == "archive-missing-sha256")
+        .collect();
+    assert_eq!(diagnostics.len(), 500);
+    assert_eq!(diagnostics[1]["pointer"], "/modules/1/sources/0");
+    assert_eq!(diagnostics[1]["line"], 12);
+}
+
+#[test]
+fn lint_keeps_the_indentation_of_the_manifest() {
+    let test_dir = get_test_dir("lint_keeps_the_indentation_of_the_manifest");
+    let manifest = "app-id: org.example.App\nruntime: org.gnome.Platform\nruntime-version: '49'\nsdk: org.gnome.Sdk\nmodules:\n    # The application.\n    - name: app\n      sources:\n          - type: dir\n            path: .\n      buildsystem: simple\n";
+    write_file(&test_dir.join("org.example.App.yaml"), manifest);
+    let output = fpcli(&test_dir)
+        .args(["lint", "--check", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", common::stdout(&output));
+}
+
+#[test]
+fn lint_does_not_drop_the_comments() {
+    let test_dir = get_test_dir("lint_does_not_drop_the_comments");
+    // Flow sequences on several lines cannot be edited in place.
+    let manifest = "app-id: org.example.App\nruntime: org.gnome.Platform\nruntime-version: \"49\"\nsdk: org.gnome.Sdk\nfinish-args: [\n  --share=ipc\n]\n# The modules.\nmodules: []\n";
+    write_file(&test_dir.join("org.example.App.yaml"), manifest);
+    let output = fpcli(&test_dir)
+        .args(["lint", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("without losing its comments"));
+    assert_eq!(
+        std::fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap(),
+        manifest
+    );
+}
+
+#[test]
+fn lint_check_prints_a_diff_without_writing() {
+    let test_dir = get_test_dir("lint_check_prints_a_diff_without_writing");
+    let manifest = "app-id: org.example.App\nsdk: org.gnome.Sdk\nruntime: org.gnome.Platform\nruntime-version: '49'\nmodules:\n  - name: app\n    buildsystem: simple\n    sources:\n      - type: dir\n        path: .\n";
+    write_file(&test_dir.join("org.example.App.yaml"), manifest);
+    let output = fpcli(&test_dir)
+        .args(["lint", "--check", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        common::stdout(&output),
+        "org.example.App.yaml:1:1: error [formatting] The manifest is not formatted correctly.
+--- org.example.App.yaml
++++ org.example.App.yaml
+@@ -1,10 +1,10 @@
+ app-id: org.example.App
+-sdk: org.gnome.Sdk
+ runtime: org.gnome.Platform
+ runtime-version: '49'
++sdk: org.gnome.Sdk
+ modules:
+   - name: app
+-    buildsystem: simple
+     sources:
+       - type: dir
+         path: .
++    buildsystem: simple
+"
+    );
+    assert_eq!(
+        std::fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap(),
+        manifest
+    );
+
+    // Without --check, the manifest is formatted and is then accepted by --check.
+    let output = fpcli(&test_dir)
+        .args(["lint", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = fpcli(&test_dir)
+        .args(["lint", "--check", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", common::stdout(&output));
+    assert_eq!(common::stdout(&output), "");
+}
+
+/// Gets the diagnostics of `lint --check` on a manifest, without the formatting ones.
+fn get_lint_diagnostics(test_dir: &std::path::Path, path: &str) -> Vec<serde_json::Value> {
+    let output = fpcli(test_dir)
+        .args(["lint", "--check", "--output-format", "json", path])
+        .output()
+        .unwrap();
+    let diagnostics: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|d| d["rule_id"] != "formatting")
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn lint_single_source_manifest() {
+    let test_dir = get_test_dir("lint_single_source_manifest");
+    write_file(
+        &test_dir.join("source.json"),
+        "{\n  \"type\": \"archive\",\n  \"url\": \"https://example.org/a.tar.gz\"\n}\n",
+    );
+    let diagnostics = get_lint_diagnostics(&test_dir, "source.json");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["rule_id"], "archive-missing-sha256");
+    assert_eq!(diagnostics[0]["pointer"], "");
+    assert_eq!(diagnostics[0]["line"], 1);
+}
+
+#[test]
+fn lint_source_list_manifest() {
+    let test_dir = get_test_dir("lint_source_list_manifest");
+    write_file(
+        &test_dir.join("sources.yaml"),
+        "- type: git\n  url: https://example.org/a.git\n- type: archive\n  url: https://example.org/b.tar.gz\n  sha256: 6667b2d1aab6a00caa5aee5af8ad9f1465e567abf1c209d15727d57b3e8f6e5f\n- type: archive\n  url: https://example.org/c.tar.gz\n",
+    );
+    let diagnostics = get_lint_diagnostics(&test_dir, "sources.yaml");
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["rule_id"], "git-source-not-pinned");
+    assert_eq!(diagnostics[0]["pointer"], "/0");
+    assert_eq!(diagnostics[0]["line"], 1);
+    assert_eq!(diagnostics[1]["rule_id"], "archive-missing-sha256");
+    assert_eq!(diagnostics[1]["pointer"], "/2");
+    assert_eq!(diagnostics[1]["line"], 6);
+
+    // The source list is already formatted.
+    let output = fpcli(&test_dir)
+        .args(["lint", "--check", "sources.yaml"])
+        .output()
+        .unwrap();
+    assert!(!common::stdout(&output).contains("[formatting]"));
+}
+
+#[test]
+fn lint_keeps_the_quotes_and_the_flow_style() {
+    let test_dir = get_test_dir("lint_keeps_the_quotes_and_the_flow_style");
+    let manifest = "app-id: org.example.App\nsdk: org.gnome.Sdk\nruntime: org.gnome.Platform\nruntime-version: \"49\"\nfinish-args: [--share=ipc, --socket=wayland]\nmodules:\n  - name: app\n    buildsystem: simple\n    build-commands: [\"cp *.so /app/lib\"]\n    sources:\n      - type: dir\n        path: .\n";
+    write_file(&test_dir.join("org.example.App.yaml"), manifest);
+    let output = fpcli(&test_dir)
+        .args(["lint", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        std::fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap(),
+        "app-id: org.example.App\nruntime: org.gnome.Platform\nruntime-version: \"49\"\nsdk: org.gnome.Sdk\nfinish-args: [--share=ipc, --socket=wayland]\nmodules:\n  - name: app\n    sources:\n      - type: dir\n        path: .\n    buildsystem: simple\n    build-commands: [\"cp *.so /app/lib\"]\n"
+    );
+}
+
+#[test]
+fn lint_does_not_format_anchors_and_aliases() {
+    let test_dir = get_test_dir("lint_does_not_format_anchors_and_aliases");
+    let manifest = "app-id: org.example.App\nsdk: org.gnome.Sdk\nruntime: org.gnome.Platform\nruntime-version: '49'\nmodules:\n  - name: a\n    build-options: &options\n      cflags: -O2\n    sources: []\n  - name: b\n    build-options: *options\n    sources: []\n";
+    write_file(&test_dir.join("org.example.App.yaml"), manifest);
+    for args in [vec!["lint"], vec!["lint", "--check"]] {
+        let output = fpcli(&test_dir)
+            .args(args)
+            .arg("org.example.App.yaml")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert!(stderr(&output).contains("anchors and aliases would be lost"));
+        assert_eq!(
+            std::fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap(),
+            manifest
+        );
+    }
+}
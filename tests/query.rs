@@ -0,0 +1,90 @@
+//! Tests of the get and set commands.
+mod common;
+
+use std::fs;
+
+use common::{fpcli, get_test_dir, stderr, write_file};
+
+const MANIFEST: &str = r#"app-id: org.example.App
+runtime: org.gnome.Platform
+# The runtime version.
+runtime-version: "49"
+sdk: org.gnome.Sdk
+modules:
+  - name: app
+    sources:
+      - type: dir
+        path: .
+"#;
+
+#[test]
+fn set_a_field() {
+    let test_dir = get_test_dir("set_a_field");
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    let output = fpcli(&test_dir)
+        .args(["set", "org.example.App.yaml", "runtime-version", "50"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap(),
+        MANIFEST.replace("\"49\"", "\"50\"")
+    );
+
+    let output = fpcli(&test_dir)
+        .args(["get", "org.example.App.yaml", "runtime-version"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(common::stdout(&output), "50\n");
+}
+
+#[test]
+fn set_an_unknown_field() {
+    let test_dir = get_test_dir("set_an_unknown_field");
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    for query in ["runtme-version", "modules[0].sources[0].nonsense"] {
+        let output = fpcli(&test_dir)
+            .args(["set", "org.example.App.yaml", query, "value"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", query);
+        assert!(stderr(&output).contains("the field is not supported by the manifest"));
+        assert_eq!(
+            fs::read_to_string(test_dir.join("org.example.App.yaml")).unwrap(),
+            MANIFEST
+        );
+    }
+}
+
+#[test]
+fn set_an_unknown_field_does_not_write_the_manifest() {
+    let test_dir = get_test_dir("set_an_unknown_field_does_not_write_the_manifest");
+    // The JSON manifests are rewritten in another format when they are written.
+    let manifest = r#"{"name": "zlib", "sources": [{"type": "dir", "path": "."}]}"#;
+    write_file(&test_dir.join("zlib.json"), manifest);
+    let output = fpcli(&test_dir)
+        .args(["set", "zlib.json", "sources[0].nonsense", "value"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("the field is not supported by the manifest"));
+    assert_eq!(
+        fs::read_to_string(test_dir.join("zlib.json")).unwrap(),
+        manifest
+    );
+}
+
+#[test]
+fn get_a_missing_field() {
+    let test_dir = get_test_dir("get_a_missing_field");
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    for query in ["modules[1]", "modules[0].sources[0].nonsense"] {
+        let output = fpcli(&test_dir)
+            .args(["get", "org.example.App.yaml", query])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", query);
+        assert!(!stderr(&output).contains("panicked"));
+    }
+}
@@ -0,0 +1,119 @@
+//! Tests of the resolve and tree commands on imported module manifests.
+mod common;
+
+use common::{fpcli, get_test_dir, stderr, write_file};
+
+const MANIFEST: &str = r#"app-id: org.example.App
+runtime: org.gnome.Platform
+runtime-version: "49"
+sdk: org.gnome.Sdk
+modules:
+  - a/one.json
+"#;
+
+/// Writes an application importing `a/one.json`, which imports `a/b/two.json`,
+/// which imports `a/one.json` again.
+fn write_include_cycle(test_dir: &std::path::Path) {
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    write_file(
+        &test_dir.join("a/one.json"),
+        r#"{"name": "one", "sources": [{"type": "dir", "path": "."}], "modules": ["b/two.json"]}"#,
+    );
+    write_file(
+        &test_dir.join("a/b/two.json"),
+        r#"{"name": "two", "sources": [{"type": "dir", "path": "."}], "modules": ["../one.json"]}"#,
+    );
+}
+
+#[test]
+fn tree_and_resolve_report_the_same_include_cycle() {
+    let test_dir = get_test_dir("tree_and_resolve_report_the_same_include_cycle");
+    write_include_cycle(&test_dir);
+    let cycle_error = "Include cycle detected: a/one.json → a/b/two.json → a/one.json.";
+
+    let output = fpcli(&test_dir)
+        .args(["resolve", "--check", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains(cycle_error), "{}", stderr(&output));
+
+    let output = fpcli(&test_dir)
+        .args(["tree", "--resolve", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains(cycle_error), "{}", stderr(&output));
+}
+
+#[test]
+fn tree_reports_the_include_chain_of_missing_manifests() {
+    let test_dir = get_test_dir("tree_reports_the_include_chain_of_missing_manifests");
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    write_file(
+        &test_dir.join("a/one.json"),
+        r#"{"name": "one", "sources": [{"type": "dir", "path": "."}], "modules": ["missing.json"]}"#,
+    );
+
+    let output = fpcli(&test_dir)
+        .args(["tree", "--resolve", "org.example.App.yaml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains(
+            "Could not import a/missing.json: No such file or directory (os error 2) (included from org.example.App.yaml → a/one.json)"
+        ),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn resolve_to_another_directory_rebases_the_local_paths() {
+    let test_dir = get_test_dir("resolve_to_another_directory_rebases_the_local_paths");
+    write_file(
+        &test_dir.join("org.example.App.yaml"),
+        &MANIFEST.replace(
+            "  - a/one.json\n",
+            "  - a/one.json\n  - name: app\n    sources:\n      - type: dir\n        path: src\n",
+        ),
+    );
+    write_file(
+        &test_dir.join("a/one.json"),
+        r#"{"name": "one", "sources": [{"type": "dir", "path": "."}, {"type": "archive", "url": "https://example.org/one.tar.gz"}], "modules": ["b/two.json"]}"#,
+    );
+    write_file(
+        &test_dir.join("a/b/two.json"),
+        r#"{"name": "two", "sources": [{"type": "patch", "path": "fix.patch"}, "sources.json"]}"#,
+    );
+    write_file(
+        &test_dir.join("a/b/sources.json"),
+        r#"[{"type": "file", "path": "data.txt"}]"#,
+    );
+    std::fs::create_dir_all(test_dir.join("out")).unwrap();
+
+    let output = fpcli(&test_dir)
+        .args(["resolve", "org.example.App.yaml", "-o", "out/resolved.yaml"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let resolved: serde_yaml::Value =
+        serde_yaml::from_str(&std::fs::read_to_string(test_dir.join("out/resolved.yaml")).unwrap())
+            .unwrap();
+    let one = &resolved["modules"][0];
+    assert_eq!(one["sources"][0]["path"], "../a");
+    assert_eq!(one["sources"][1]["url"], "https://example.org/one.tar.gz");
+    let two = &one["modules"][0];
+    assert_eq!(two["sources"][0]["path"], "../a/b/fix.patch");
+    assert_eq!(two["sources"][1]["path"], "../a/b/data.txt");
+    assert_eq!(resolved["modules"][1]["sources"][0]["path"], "../src");
+
+    // The manifest itself is not changed.
+    assert!(
+        std::fs::read_to_string(test_dir.join("org.example.App.yaml"))
+            .unwrap()
+            .contains("  - a/one.json\n")
+    );
+}
@@ -0,0 +1,68 @@
+//! Tests of the split command.
+mod common;
+
+use std::fs;
+use std::path;
+
+use common::{fpcli, get_test_dir, stderr, stdout, write_file};
+
+const MANIFEST: &str = r#"app-id: org.example.App
+runtime: org.gnome.Platform
+runtime-version: "49"
+sdk: org.gnome.Sdk
+modules:
+  - name: app
+    buildsystem: simple
+    build-commands:
+      - make install
+    sources:
+      - type: dir
+        path: .
+    modules:
+      # The compression library.
+      - name: zlib
+        buildsystem: simple
+        build-commands:
+          - make install
+        sources:
+          - type: patch
+            path: patches/zlib.patch
+"#;
+
+fn resolve(test_dir: &path::Path) -> serde_yaml::Value {
+    let output = fpcli(test_dir)
+        .args(["resolve", "org.example.App.yaml", "-o", "-"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    serde_yaml::from_str(&stdout(&output)).unwrap()
+}
+
+#[test]
+fn split_and_resolve_round_trip() {
+    let test_dir = get_test_dir("split_and_resolve_round_trip");
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    let resolved = resolve(&test_dir);
+
+    let output = fpcli(&test_dir)
+        .args(["split", "org.example.App.yaml", "--dir", "parts"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    assert!(fs::read_to_string(test_dir.join("org.example.App.yaml"))
+        .unwrap()
+        .contains("  - parts/app.yaml\n"));
+    let app = fs::read_to_string(test_dir.join("parts/app.yaml")).unwrap();
+    assert!(app.contains("path: ..\n"), "{}", app);
+    // The comment of the nested module is kept above its reference.
+    assert!(
+        app.contains("  # The compression library.\n  - zlib.yaml\n"),
+        "{}",
+        app
+    );
+    let zlib = fs::read_to_string(test_dir.join("parts/zlib.yaml")).unwrap();
+    assert!(zlib.contains("path: ../patches/zlib.patch\n"), "{}", zlib);
+
+    assert_eq!(resolve(&test_dir), resolved);
+}
@@ -0,0 +1,152 @@
+//! Tests of the output formats of the tree command.
+mod common;
+
+use std::path;
+
+use common::{fpcli, get_test_dir, stderr, stdout, write_file};
+
+/// The module names have quotes, a backslash, and characters that are special in
+/// Mermaid and HTML.
+const MANIFEST: &str = r#"app-id: org.example.App
+runtime: org.gnome.Platform
+runtime-version: "49"
+sdk: org.gnome.Sdk
+modules:
+  - name: app "main"
+    buildsystem: meson
+    sources:
+      - type: dir
+        path: .
+      - type: git
+        url: https://example.org/app.git
+    modules:
+      - name: zlib\x
+        sources:
+          - type: archive
+            url: https://example.org/zlib.tar.gz
+            sha256: 6667b2d1aab6a00caa5aee5af8ad9f1465e567abf1c209d15727d57b3e8f6e5f
+  - parts/data.json
+"#;
+
+const DATA_MANIFEST: &str = r#"{"name": "data #1 <b>", "buildsystem": "simple", "build-commands": [], "sources": [{"type": "file", "path": "data.txt"}]}"#;
+
+fn tree(test_dir: &path::Path, format_name: &str) -> String {
+    let output = fpcli(test_dir)
+        .args([
+            "tree",
+            "--resolve",
+            "-f",
+            format_name,
+            "org.example.App.yaml",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output)
+}
+
+fn write_manifests(test_dir: &path::Path) {
+    write_file(&test_dir.join("org.example.App.yaml"), MANIFEST);
+    write_file(&test_dir.join("parts/data.json"), DATA_MANIFEST);
+}
+
+#[test]
+fn tree_as_text() {
+    let test_dir = get_test_dir("tree_as_text");
+    write_manifests(&test_dir);
+    assert_eq!(
+        tree(&test_dir, "text"),
+        r#"org.example.App
+↪ app "main"   meson      2 sources (dir, git)  org.example.App.yaml
+  ↪ zlib\x     autotools  1 source (archive)    org.example.App.yaml
+↪ data #1 <b>  simple     1 source (file)       parts/data.json
+"#
+    );
+}
+
+#[test]
+fn tree_as_json() {
+    let test_dir = get_test_dir("tree_as_json");
+    write_manifests(&test_dir);
+    assert_eq!(
+        tree(&test_dir, "json"),
+        r#"{
+  "name": "org.example.App",
+  "file": "org.example.App.yaml",
+  "source-count": 0,
+  "source-types": [],
+  "modules": [
+    {
+      "name": "app \"main\"",
+      "file": "org.example.App.yaml",
+      "buildsystem": "meson",
+      "source-count": 2,
+      "source-types": [
+        "dir",
+        "git"
+      ],
+      "modules": [
+        {
+          "name": "zlib\\x",
+          "file": "org.example.App.yaml",
+          "buildsystem": "autotools",
+          "source-count": 1,
+          "source-types": [
+            "archive"
+          ],
+          "modules": []
+        }
+      ]
+    },
+    {
+      "name": "data #1 <b>",
+      "file": "parts/data.json",
+      "buildsystem": "simple",
+      "source-count": 1,
+      "source-types": [
+        "file"
+      ],
+      "modules": []
+    }
+  ]
+}
+"#
+    );
+}
+
+#[test]
+fn tree_as_dot() {
+    let test_dir = get_test_dir("tree_as_dot");
+    write_manifests(&test_dir);
+    assert_eq!(
+        tree(&test_dir, "dot"),
+        r#"digraph modules {
+  n0 [label="org.example.App"];
+  n1 [label="app \"main\"\nmeson"];
+  n2 [label="zlib\\x\nautotools"];
+  n3 [label="data #1 <b>\nsimple"];
+  n0 -> n1;
+  n1 -> n2;
+  n0 -> n3;
+}
+"#
+    );
+}
+
+#[test]
+fn tree_as_mermaid() {
+    let test_dir = get_test_dir("tree_as_mermaid");
+    write_manifests(&test_dir);
+    assert_eq!(
+        tree(&test_dir, "mermaid"),
+        r#"graph TD
+  n0["org.example.App"]
+  n1["app #quot;main#quot;<br/>meson"]
+  n2["zlib\x<br/>autotools"]
+  n3["data #35;1 #lt;b#gt;<br/>simple"]
+  n0 --> n1
+  n1 --> n2
+  n0 --> n3
+"#
+    );
+}